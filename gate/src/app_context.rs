@@ -14,8 +14,14 @@
 
 use std::marker::PhantomData;
 
-use crate::asset_id::{AppAssetId, IdU16};
-use crate::core::CoreAudio;
+use crate::asset_id::{AppAssetId, ChannelGroup, IdU16, SoundAsset};
+use crate::core::{CoreAudio, StreamSource};
+use crate::collision::Collider;
+use crate::renderer::SpriteInfo;
+use crate::save::{self, SaveError, SaveStorage};
+use crate::voices::Voices;
+
+pub(crate) const MAX_COOKIE_LEN: usize = 700;
 
 /// Context passed to methods in `App`.
 pub struct AppContext<A: AppAssetId> {
@@ -29,12 +35,23 @@ pub struct AppContext<A: AppAssetId> {
     desires_fullscreen: bool,
     cookie: Vec<u8>,
     cookie_updated: bool,
+    audio_available: bool,
+    load_progress: f64,
+    assets_loaded: bool,
+    storage: Box<dyn SaveStorage>,
+    sprite_infos: Vec<SpriteInfo>,
+    sprite_colliders: Vec<Collider>,
 }
 
 impl<A: AppAssetId> AppContext<A> {
-    pub(crate) fn new(audio: CoreAudio, dims: (f64, f64), native_px: f64) -> AppContext<A> {
+    pub(crate) fn new(audio: CoreAudio, audio_channels: u16, storage: Box<dyn SaveStorage>,
+                      dims: (f64, f64), native_px: f64) -> AppContext<A> {
+        let group_channels: Vec<_> = (0..A::SoundGroup::count())
+            .map(|id| A::SoundGroup::from_u16(id).unwrap().channel_count())
+            .collect();
+        let voices = Voices::new(audio_channels, &group_channels, A::Sound::count());
         AppContext {
-            audio: Audio { core: audio, phantom: PhantomData },
+            audio: Audio { core: audio, voices, phantom: PhantomData },
             dims,
             cursor: (0., 0.),
             close_requested: false,
@@ -43,6 +60,12 @@ impl<A: AppAssetId> AppContext<A> {
             desires_fullscreen: false,
             cookie: Vec::new(),
             cookie_updated: false,
+            audio_available: false,
+            load_progress: 0.,
+            assets_loaded: false,
+            storage,
+            sprite_infos: Vec::new(),
+            sprite_colliders: Vec::new(),
         }
     }
 
@@ -115,6 +138,67 @@ impl<A: AppAssetId> AppContext<A> {
         self.desires_fullscreen = is_fullscreen;
     }
 
+    /// Returns true if audio can currently be heard.
+    ///
+    /// When compiling to `wasm32-unknown-unknown` and running in a web browser, audio is
+    /// blocked until the user interacts with the page, so this is false at first.
+    /// Natively, this is true once the app has been created.
+    /// This is never true if audio is not enabled (see `audio_enabled`).
+    /// See also `App::audio_unlocked`.
+    pub fn audio_available(&self) -> bool { self.audio_available }
+
+    pub(crate) fn set_audio_available(&mut self) { self.audio_available = true; }
+
+    /// Returns false if audio was disabled with `AppInfo::disable_audio`,
+    /// or if the audio device failed to open (e.g. on a machine without a sound card).
+    ///
+    /// When audio is not enabled, playing audio is a no-op and `App::audio_unlocked` is never invoked.
+    pub fn audio_enabled(&self) -> bool { self.audio.core.enabled() }
+
+    /// Returns the size and bounds of the given `sprite`,
+    /// e.g. for laying out user interfaces or computing collision boxes from art.
+    pub fn sprite_info(&self, sprite: A::Sprite) -> SpriteInfo {
+        self.sprite_infos[sprite.id_u16() as usize]
+    }
+
+    pub(crate) fn set_sprite_infos(&mut self, infos: Vec<SpriteInfo>) { self.sprite_infos = infos; }
+
+    /// Returns the collision data of the given `sprite`, to be tested with functions in `gate::collision`.
+    ///
+    /// Panics if collision data was not generated by `AssetPacker::collision_masks` in "gate_build",
+    /// or if assets have not finished loading (see `assets_loaded`).
+    pub fn sprite_collider(&self, sprite: A::Sprite) -> &Collider {
+        assert!(self.assets_loaded, "sprite collision data is not available until assets are loaded");
+        assert!(!self.sprite_colliders.is_empty(),
+                "sprite collision data was not generated, see AssetPacker::collision_masks");
+        &self.sprite_colliders[sprite.id_u16() as usize]
+    }
+
+    pub(crate) fn set_sprite_colliders(&mut self, colliders: Vec<Collider>) { self.sprite_colliders = colliders; }
+
+    /// Returns the fraction of assets that have been loaded so far, from `0.0` to `1.0`,
+    /// e.g. for drawing a progress bar on a loading screen.
+    ///
+    /// This is `1.0` once `assets_loaded` is true.
+    pub fn load_progress(&self) -> f64 { self.load_progress }
+
+    pub(crate) fn set_load_progress(&mut self, progress: f64) { self.load_progress = progress.clamp(0., 1.); }
+
+    /// Returns true once all assets have been loaded.
+    ///
+    /// If `AssetPacker::loading_sprites` was invoked in "gate_build", then the app is created
+    /// while the main sprite atlas and sounds are still loading, and this is false at first.
+    /// Until then, only the loading sprites are drawn (other sprites are drawn as empty),
+    /// `sprite_info` is zero-sized for the other sprites,
+    /// and sounds that have not loaded yet may not be heard.
+    /// Otherwise, this is always true.
+    pub fn assets_loaded(&self) -> bool { self.assets_loaded }
+
+    pub(crate) fn set_assets_loaded(&mut self) {
+        self.load_progress = 1.;
+        self.assets_loaded = true;
+    }
+
     /// Closes the app entirely.
     ///
     /// When compiling to `wasm32-unknown-unknown`, the app may be resumed after it is closed
@@ -127,10 +211,34 @@ impl<A: AppAssetId> AppContext<A> {
         result
     }
 
+    /// Reads the save data in the given `slot`, or returns `None` if nothing is saved there.
+    ///
+    /// See `write_save` for valid slot names.
+    pub fn read_save(&self, slot: &str) -> Result<Option<Vec<u8>>, SaveError> {
+        assert!(save::is_valid_slot(slot), "invalid save slot {:?}", slot);
+        self.storage.read(slot)
+    }
+
+    /// Writes save data to the given `slot`, replacing any data previously saved there.
+    ///
+    /// Slot names must be 1 to 64 characters long, using only ASCII letters, digits, `-` and `_`.
+    /// Writes are atomic: if an error is returned, the previous save data is left intact.
+    pub fn write_save(&mut self, slot: &str, data: &[u8]) -> Result<(), SaveError> {
+        assert!(save::is_valid_slot(slot), "invalid save slot {:?}", slot);
+        self.storage.write(slot, data)
+    }
+
+    /// Deletes the save data in the given `slot`, if any.
+    pub fn delete_save(&mut self, slot: &str) -> Result<(), SaveError> {
+        assert!(save::is_valid_slot(slot), "invalid save slot {:?}", slot);
+        self.storage.delete(slot)
+    }
+
     /// Gets current cookie data.
     ///
     /// NOTE: this API is likely to change change.
-    /// Returns an empty array if cookie is not set or if not running in WebAssembly mode.
+    /// Returns an empty array if cookie is not set.
+    /// The cookie is already loaded when the `App` is created.
     pub fn cookie(&self) -> &[u8] {
         &self.cookie
     }
@@ -138,18 +246,20 @@ impl<A: AppAssetId> AppContext<A> {
     /// Writes cookie data.
     ///
     /// NOTE: this API is likely to change change.
-    /// Cookie can be used as lightweight save data when built in WebAssembly mode.
-    /// Only writes persistent cookie data if built in WebAssembly mode.
-    /// To use cookies, the readCookie and writeCookie functions must be passed into gate.js.
+    /// Cookie can be used as lightweight save data, and must be less than 700 bytes.
+    /// When built in WebAssembly mode, the readCookie and writeCookie functions must be
+    /// passed into gate.js to use cookies.
+    /// Natively, the cookie is written to a file in the per-user data directory
+    /// (see `AppInfo::app_id`).
+    /// For larger save data, see `write_save`.
     pub fn set_cookie(&mut self, cookie: Vec<u8>) {
-        assert!(cookie.len() < 700);
+        assert!(cookie.len() < MAX_COOKIE_LEN);
         if cookie != self.cookie {
             self.cookie_updated = true;
             self.cookie = cookie;
         }
     }
 
-    #[cfg(target_arch = "wasm32")]
     pub(crate) fn take_cookie_updated_flag(&mut self) -> bool {
         let was_updated = self.cookie_updated;
         self.cookie_updated = false;
@@ -163,11 +273,24 @@ impl<A: AppAssetId> AppContext<A> {
 }
 
 /// Struct for audio playback.
-pub struct Audio<A: AppAssetId> { core: CoreAudio, phantom: PhantomData<A> }
+pub struct Audio<A: AppAssetId> { pub(crate) core: CoreAudio, voices: Voices, phantom: PhantomData<A> }
 
 impl<A: AppAssetId> Audio<A> {
     /// Plays the given sound effect once.
-    pub fn play_sound(&mut self, sound: A::Sound) { self.core.play_sound(sound.id_u16()); }
+    ///
+    /// The sound is subject to the limits, priority, and group declared for it in the build script.
+    /// It may be skipped if it is still cooling down or if there are no audio channels
+    /// available to play it on.
+    pub fn play_sound(&mut self, sound: A::Sound) {
+        let id = sound.id_u16();
+        let settings = A::Sound::from_u16(id).unwrap().settings();
+        self.voices.play(&mut self.core, id, settings);
+    }
+
+    /// Stops all sound effects that are playing in the given sound group.
+    pub fn stop_group(&mut self, group: A::SoundGroup) {
+        self.voices.stop_group(&mut self.core, group.id_u16());
+    }
 
     /// Plays the given music once, replacing the currently playing music, if any.
     pub fn play_music(&mut self, music: A::Music) { self.core.play_music(music.id_u16(), false); }
@@ -177,4 +300,32 @@ impl<A: AppAssetId> Audio<A> {
 
     /// Stops the currently playing music, if any.
     pub fn stop_music(&mut self) { self.core.stop_music(); }
+
+    /// Plays a buffer of raw PCM `samples` once, such as a procedurally generated sound effect.
+    ///
+    /// Samples should be in the range `-1.0` to `1.0`, interleaved by channel.
+    /// `channels` must be `1` (mono) or `2` (stereo).
+    /// A trailing partial frame of samples is ignored.
+    pub fn play_samples(&mut self, samples: &[f32], sample_rate: u32, channels: u16) {
+        assert!(channels == 1 || channels == 2, "unsupported channel count {}", channels);
+        assert!(sample_rate > 0, "sample rate must be positive");
+        let samples = &samples[..(samples.len() - samples.len() % channels as usize)];
+        self.core.play_samples(samples, sample_rate, channels);
+    }
+
+    /// Opens a stream of procedurally generated audio, replacing the currently open stream, if any.
+    ///
+    /// The `source` callback is repeatedly invoked to fill a zeroed buffer with interleaved
+    /// stereo samples in the range `-1.0` to `1.0`, at a rate of `self.stream_sample_rate()`.
+    /// Stream output is mixed on top of the music and sound effects.
+    /// When not building in WebAssembly mode, `source` is invoked on the audio thread.
+    pub fn open_stream(&mut self, source: StreamSource) { self.core.open_stream(source); }
+
+    /// Closes the currently open audio stream, if any.
+    pub fn close_stream(&mut self) { self.core.close_stream(); }
+
+    /// Returns the sample rate, in samples per second, that audio streams are played at.
+    pub fn stream_sample_rate(&self) -> u32 { self.core.stream_sample_rate() }
+
+    pub(crate) fn advance(&mut self, seconds: f64) { self.voices.advance(seconds); }
 }
@@ -15,6 +15,13 @@
 //! Contains `AppInfo` (and related structs), a struct for specifying intialization
 //! information for running an `App`.
 
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use crate::core::CoreStorage;
+use crate::renderer::TextureFilter;
+use crate::save::SaveStorage;
+
 /// A struct for specifying initialization information for running an `App`.
 ///
 /// Methods for setting fields in `AppInfo` are intended to be chained together like
@@ -39,7 +46,16 @@ pub struct AppInfo {
     pub(crate) max_dims: (f64, f64),
     pub(crate) tile_width: Option<u32>,
     pub(crate) title: &'static str,
+    pub(crate) app_id: Option<&'static str>,
+    pub(crate) save_storage: Option<Box<dyn SaveStorage>>,
+    pub(crate) audio_channels: u16,
+    pub(crate) mute_on_unfocus: bool,
+    pub(crate) audio_disabled: bool,
+    pub(crate) texture_filter: TextureFilter,
     pub(crate) print_gl_info: bool,
+    pub(crate) error_message_box: bool,
+    #[allow(dead_code)] // assets_dir is not used when targeting wasm
+    pub(crate) assets_dir: Option<PathBuf>,
 }
 
 impl AppInfo {
@@ -59,7 +75,15 @@ impl AppInfo {
             max_dims: (max_width, max_height),
             tile_width: None,
             title: "untitled app",
+            app_id: None,
+            save_storage: None,
+            audio_channels: 16,
+            mute_on_unfocus: false,
+            audio_disabled: false,
+            texture_filter: TextureFilter::Linear,
             print_gl_info: false,
+            error_message_box: false,
+            assets_dir: None,
         }
     }
 
@@ -89,6 +113,26 @@ impl AppInfo {
     /// Specifies a window title (default is "untitled app").
     pub fn title(mut self, title: &'static str) -> Self { self.title = title; self }
 
+    /// Specifies an identifier for the app, used to keep its save data separate from other apps
+    /// (default is the title).
+    ///
+    /// Natively, save data is stored in a per-user data directory named after this identifier,
+    /// so it must be non-empty and may not contain path separators or other characters
+    /// that are invalid in file names.
+    /// If no identifier is given, one is derived from the title by replacing such characters with `_`.
+    /// In a web browser, it prefixes the `localStorage` keys.
+    pub fn app_id(mut self, app_id: &'static str) -> Self {
+        assert!(is_valid_app_id(app_id), "invalid app id {:?}", app_id);
+        self.app_id = Some(app_id);
+        self
+    }
+
+    /// Replaces the default storage used for save data (see `gate::save`).
+    pub fn save_storage<S: SaveStorage + 'static>(mut self, storage: S) -> Self {
+        self.save_storage = Some(Box::new(storage));
+        self
+    }
+
     /// Specifies the intial native width and height of the window (default is `800` by `600`).
     pub fn native_dims(mut self, width: u32, height: u32) -> Self {
         assert!(width >= 10 && width <= 3000, "unrealistic window width {}", width);
@@ -97,8 +141,70 @@ impl AppInfo {
         self
     }
 
+    /// Specifies the number of audio channels available for playing sound effects (default is `16`).
+    ///
+    /// This is the maximum number of sound effects that can play at once,
+    /// including the channels reserved for sound groups.
+    pub fn audio_channels(mut self, channels: u16) -> Self {
+        assert!((1..=1000).contains(&channels), "unrealistic audio channel count {}", channels);
+        self.audio_channels = channels;
+        self
+    }
+
+    /// If invoked, all audio is muted while the window (or web page) does not have focus.
+    pub fn mute_on_unfocus(mut self) -> Self { self.mute_on_unfocus = true; self }
+
+    /// If invoked, the audio device is never opened and all audio playback is a no-op.
+    ///
+    /// Audio is also disabled automatically if the audio device fails to open,
+    /// see `AppContext::audio_enabled`.
+    pub fn disable_audio(mut self) -> Self { self.audio_disabled = true; self }
+
+    /// Specifies how textures are filtered when scaled (default is `TextureFilter::Linear`).
+    ///
+    /// Applies to render targets and to the sprite atlas,
+    /// unless a filter was specified for the atlas when it was packed.
+    pub fn texture_filter(mut self, filter: TextureFilter) -> Self { self.texture_filter = filter; self }
+
     /// If invoked, the OpenGL version info will be printed out at the start of the application.
     /// 
     /// Does not print anything when building as webassembly.
     pub fn print_gl_info(mut self) -> Self { self.print_gl_info = true; self }
+
+    /// If invoked, an error message box is shown to the user when the app fails to start,
+    /// before `gate::try_run` returns the error (or `gate::run` panics).
+    ///
+    /// Does not show anything when building as webassembly.
+    pub fn error_message_box(mut self) -> Self { self.error_message_box = true; self }
+
+    /// Specifies the directory that packed assets are loaded from natively.
+    ///
+    /// By default, this is the "assets" directory next to the executable if it exists,
+    /// and otherwise the "assets" directory in the current working directory,
+    /// which is where assets are packed by a build script when running with `cargo run`.
+    /// Not used if assets are embedded in the application with `AssetPacker::embed_assets`.
+    pub fn assets_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self { self.assets_dir = Some(dir.into()); self }
+
+    pub(crate) fn resolved_app_id(&self) -> Cow<'static, str> {
+        match self.app_id {
+            Some(app_id) => Cow::Borrowed(app_id),
+            None if is_valid_app_id(self.title) => Cow::Borrowed(self.title),
+            None => {
+                let app_id: String = self.title.chars().map(|c| if is_app_id_char(c) { c } else { '_' }).collect();
+                if is_valid_app_id(&app_id) { Cow::Owned(app_id) } else { Cow::Borrowed("untitled app") }
+            }
+        }
+    }
+
+    pub(crate) fn take_save_storage(&mut self) -> Box<dyn SaveStorage> {
+        let app_id = self.resolved_app_id();
+        self.save_storage.take().unwrap_or_else(|| Box::new(CoreStorage::new(&app_id)))
+    }
+}
+
+// excludes path separators and other characters that are not allowed in file names on some platforms
+fn is_app_id_char(c: char) -> bool { !c.is_control() && !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') }
+
+fn is_valid_app_id(app_id: &str) -> bool {
+    !app_id.trim().is_empty() && app_id != "." && app_id != ".." && app_id.chars().all(is_app_id_char)
 }
@@ -16,9 +16,14 @@
 //!
 //! The user is not expected to implement these traits themselves directly.
 //! Rather, the generated code from "gate_build" will implement these traits.
-//! Gate build will generate four enums: `SpriteId`, `MusicId`, and `SoundId`,
+//! Gate build will generate five enums: `SpriteId`, `MusicId`, `SoundId`, `SoundGroupId`, and `MapId`,
 //! that implement `IdU16`.
-//! It will also generate a collection type `AssetId` that implements `AppAssetId`.
+//! The audio enums `MusicId` and `SoundId` also implement `AudioId`,
+//! `SoundId` implements `SoundAsset`, `SoundGroupId` implements `ChannelGroup`,
+//! and `MapId` implements `MapAsset`.
+//! It will also generate a collection type `AssetId` that implements `AppAssetId`,
+//! and an `AnimId` enum for animations imported from sprite sheets,
+//! with methods to look up the `SpriteId` of each frame.
 
 /// A value that can be converted to a `u16` ID, implemented by asset enums.
 pub trait IdU16: Sized {
@@ -34,12 +39,84 @@ pub trait IdU16: Sized {
     fn from_u16(id: u16) -> Option<Self>;
 }
 
+/// An audio asset enum, which also knows the file format that each asset was packed in.
+pub trait AudioId: IdU16 {
+    /// Returns the file format that this audio asset was packed in.
+    fn format(self) -> AudioFormat;
+}
+
+/// File format of a packed audio asset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AudioFormat { Ogg, Wav, Flac }
+
+impl AudioFormat {
+    /// Returns the file extension used for this format, without a leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Flac => "flac",
+        }
+    }
+}
+
+/// A sound asset enum, which also knows the playback settings of each sound.
+pub trait SoundAsset: AudioId {
+    /// Returns the playback settings of this sound, as declared in the build script.
+    fn settings(self) -> SoundSettings;
+}
+
+/// Playback settings of a sound asset, declared in the build script using "gate_build".
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SoundSettings {
+    /// ID of the `ChannelGroup` that this sound plays in, or `None` if it is not in a group.
+    pub group: Option<u16>,
+    /// Maximum number of instances of this sound that may play at once, or `None` if unlimited.
+    pub max_instances: Option<u16>,
+    /// Minimum number of seconds between starting two instances of this sound.
+    pub cooldown: f64,
+    /// When no channels are free, a playing sound may be cut off by a sound with
+    /// greater or equal priority.
+    pub priority: u8,
+}
+
+/// A sound group enum, where each group plays its sounds on its own reserved audio channels.
+pub trait ChannelGroup: IdU16 {
+    /// Returns the number of audio channels reserved for this group.
+    fn channel_count(self) -> u16;
+}
+
+/// A map asset enum, where each map is embedded in the application as packed binary data.
+pub trait MapAsset: IdU16 {
+    /// Returns the packed data of this map, to be loaded by `gate::tiled::TiledMap`.
+    fn data(self) -> &'static [u8];
+}
+
 /// A convenience collection of all asset ID types.
 pub trait AppAssetId {
     /// Sprite asset enum
     type Sprite: IdU16;
     /// Music asset enum
-    type Music: IdU16;
+    type Music: AudioId;
     /// Sound asset enum
-    type Sound: IdU16;
+    type Sound: SoundAsset;
+    /// Sound group enum
+    type SoundGroup: ChannelGroup;
+    /// Tiled map enum
+    type Map: MapAsset;
+
+    /// Returns the IDs of the sprites that are packed in the loading atlas, in increasing order,
+    /// or an empty slice if there is no loading atlas.
+    ///
+    /// Generated by `AssetPacker::loading_sprites` in "gate_build".
+    /// If there is a loading atlas, the app is created as soon as it is loaded,
+    /// and the remaining assets are loaded while the app is running (see `AppContext::load_progress`).
+    fn loading_sprites() -> &'static [u16] { &[] }
+
+    /// Returns the packed asset files that are embedded in the application, as (filename, data) pairs,
+    /// or `None` if assets are loaded from the assets directory.
+    ///
+    /// Generated by `AssetPacker::embed_assets` in "gate_build".
+    /// Only used natively, since web builds load assets from the web page.
+    fn embedded_files() -> Option<&'static [(&'static str, &'static [u8])]> { None }
 }
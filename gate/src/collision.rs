@@ -0,0 +1,231 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains collision tests between sprites, based on the alpha channel of the sprite images.
+//!
+//! Collision data is generated by `AssetPacker::collision_masks` in "gate_build",
+//! and the `Collider` of each sprite is accessed with `AppContext::sprite_collider`.
+//! Tests take the same `Affine` transformations that are passed to the renderer when drawing the sprites,
+//! so they are performed in the same coordinates that the sprites are drawn in.
+
+use std::io::{self, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::renderer::{Affine, SpriteInfo};
+
+/// Collision data of a sprite, derived from the pixels of its image with non-zero alpha.
+///
+/// Coordinates are in source image pixels relative to the sprite anchor, with +Y meaning "up",
+/// matching `SpriteInfo`.
+#[derive(Clone, Debug)]
+pub struct Collider {
+    min: (f64, f64),
+    max: (f64, f64),
+    hull: Vec<(f64, f64)>,
+    cell_size: f64,
+    cols: u32,
+    rows: u32,
+    cells: Vec<bool>, // row-major, starting from the top-left
+}
+
+impl Collider {
+    pub(crate) fn read<R: Read>(input: &mut R, info: &SpriteInfo, cell_size: u8) -> io::Result<Collider> {
+        let (min, max) = (info.trimmed_min, info.trimmed_max);
+        let hull_len = input.read_u16::<BigEndian>()?;
+        let mut hull = Vec::with_capacity(hull_len as usize);
+        for _ in 0..hull_len {
+            let x = input.read_u16::<BigEndian>()? as f64;
+            let y = input.read_u16::<BigEndian>()? as f64;
+            hull.push((min.0 + x, max.1 - y));
+        }
+
+        let cell_size = cell_size as u32;
+        let cols = ((max.0 - min.0).round() as u32).div_ceil(cell_size);
+        let rows = ((max.1 - min.1).round() as u32).div_ceil(cell_size);
+        let mut bits = vec![0u8; (cols * rows).div_ceil(8) as usize];
+        input.read_exact(&mut bits)?;
+        let cells = (0..(cols * rows) as usize).map(|index| bits[index / 8] & (0x80 >> (index % 8)) != 0).collect();
+
+        Ok(Collider { min, max, hull, cell_size: cell_size as f64, cols, rows, cells })
+    }
+
+    /// Left-bottom corner of the tight bounding box around the solid pixels, relative to the anchor.
+    pub fn min(&self) -> (f64, f64) { self.min }
+
+    /// Right-top corner of the tight bounding box around the solid pixels, relative to the anchor.
+    pub fn max(&self) -> (f64, f64) { self.max }
+
+    /// Vertices of the convex hull around the solid pixels, relative to the anchor.
+    ///
+    /// Vertices are at pixel corners, in counter-clockwise order.
+    pub fn hull(&self) -> &[(f64, f64)] { &self.hull }
+
+    /// Returns true if the sprite drawn with the transformation `affine` is solid at `point`,
+    /// at the resolution of the bitmask.
+    pub fn contains(&self, affine: &Affine, point: (f64, f64)) -> bool {
+        affine.inverse().is_some_and(|inverse| self.cell_at(inverse.apply_f64(point)))
+    }
+
+    // returns true if the bitmask cell containing `point`, relative to the anchor, is set
+    fn cell_at(&self, point: (f64, f64)) -> bool {
+        let col = ((point.0 - self.min.0) / self.cell_size).floor();
+        let row = ((self.max.1 - point.1) / self.cell_size).floor();
+        if col < 0. || row < 0. || col >= self.cols as f64 || row >= self.rows as f64 {
+            false
+        } else {
+            self.cells[row as usize * self.cols as usize + col as usize]
+        }
+    }
+
+    // returns the centers of all set bitmask cells, relative to the anchor
+    fn set_cell_centers(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.cells.iter().enumerate().filter(|&(_, &is_set)| is_set).map(move |(index, _)| {
+            let (row, col) = (index as u32 / self.cols, index as u32 % self.cols);
+            (self.min.0 + (col as f64 + 0.5) * self.cell_size, self.max.1 - (row as f64 + 0.5) * self.cell_size)
+        })
+    }
+
+    fn bounds_corners(&self) -> [(f64, f64); 4] {
+        [self.min, (self.max.0, self.min.1), self.max, (self.min.0, self.max.1)]
+    }
+}
+
+/// Returns true if the tight bounding boxes of the sprites overlap,
+/// where `a` and `b` are drawn with the transformations `a_affine` and `b_affine`.
+///
+/// Rotated bounding boxes are tested exactly, without expanding them to be axis-aligned.
+/// Boxes that only touch along an edge do not overlap.
+pub fn bounds_overlap(a: &Collider, a_affine: &Affine, b: &Collider, b_affine: &Affine) -> bool {
+    convex_overlap(&transform(&a.bounds_corners(), a_affine), &transform(&b.bounds_corners(), b_affine))
+}
+
+/// Returns true if the convex hulls of the sprites overlap,
+/// where `a` and `b` are drawn with the transformations `a_affine` and `b_affine`.
+///
+/// Hulls that only touch along an edge do not overlap.
+pub fn hulls_overlap(a: &Collider, a_affine: &Affine, b: &Collider, b_affine: &Affine) -> bool {
+    convex_overlap(&transform(&a.hull, a_affine), &transform(&b.hull, b_affine))
+}
+
+/// Returns true if the bitmasks of the sprites overlap,
+/// where `a` and `b` are drawn with the transformations `a_affine` and `b_affine`.
+///
+/// This tests the center of each set cell of either bitmask against the other bitmask,
+/// so it is pixel-perfect when the masks have a cell size of 1 and the sprites are not scaled up.
+/// The hulls are tested first, so that sprites that are far apart are rejected quickly.
+pub fn masks_overlap(a: &Collider, a_affine: &Affine, b: &Collider, b_affine: &Affine) -> bool {
+    if !hulls_overlap(a, a_affine, b, b_affine) {
+        return false;
+    }
+    let (a_inverse, b_inverse) = match (a_affine.inverse(), b_affine.inverse()) {
+        (Some(a_inverse), Some(b_inverse)) => (a_inverse, b_inverse),
+        _ => return false,
+    };
+    let a_to_b = b_inverse.pre_transform(a_affine);
+    let b_to_a = a_inverse.pre_transform(b_affine);
+    a.set_cell_centers().any(|point| b.cell_at(a_to_b.apply_f64(point))) ||
+        b.set_cell_centers().any(|point| a.cell_at(b_to_a.apply_f64(point)))
+}
+
+fn transform(points: &[(f64, f64)], affine: &Affine) -> Vec<(f64, f64)> {
+    points.iter().map(|&point| affine.apply_f64(point)).collect()
+}
+
+// separating axis test between two convex polygons, where an empty polygon (from a blank sprite) overlaps nothing
+fn convex_overlap(a: &[(f64, f64)], b: &[(f64, f64)]) -> bool {
+    !a.is_empty() && !b.is_empty() && !has_separating_edge(a, b) && !has_separating_edge(b, a)
+}
+
+// returns true if the normal of any edge of `poly` separates it from `other`
+fn has_separating_edge(poly: &[(f64, f64)], other: &[(f64, f64)]) -> bool {
+    (0..poly.len()).any(|index| {
+        let (start, end) = (poly[index], poly[(index + 1) % poly.len()]);
+        let axis = (start.1 - end.1, end.0 - start.0);
+        let project = |points: &[(f64, f64)]| points.iter()
+            .map(|p| p.0 * axis.0 + p.1 * axis.1)
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        let (poly_lo, poly_hi) = project(poly);
+        let (other_lo, other_hi) = project(other);
+        poly_hi <= other_lo || other_hi <= poly_lo
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64) -> Vec<(f64, f64)> {
+        vec![(x, y), (x + 1., y), (x + 1., y + 1.), (x, y + 1.)]
+    }
+
+    // builds a collider with a cell size of 1 and its anchor at the bottom-left,
+    // from rows of '#' (set) and '.' (unset) cells starting from the top
+    fn collider(mask: &[&str], hull: &[(f64, f64)]) -> Collider {
+        let (rows, cols) = (mask.len() as u32, mask[0].len() as u32);
+        let cells = mask.iter().flat_map(|row| row.bytes().map(|b| b == b'#')).collect();
+        Collider { min: (0., 0.), max: (cols as f64, rows as f64), hull: hull.to_vec(), cell_size: 1., cols, rows, cells }
+    }
+
+    #[test]
+    fn separating_edge() {
+        let a = square(0., 0.);
+        assert!(convex_overlap(&a, &square(0.5, 0.5)));
+        assert!(convex_overlap(&a, &square(0.1, -0.9)));
+        assert!(!convex_overlap(&a, &square(2., 0.)));
+        assert!(has_separating_edge(&a, &square(2., 0.)));
+        assert!(!has_separating_edge(&a, &square(0.5, 0.5)));
+    }
+
+    #[test]
+    fn touching_edges_do_not_overlap() {
+        let a = square(0., 0.);
+        assert!(!convex_overlap(&a, &square(1., 0.)));
+        assert!(!convex_overlap(&a, &square(0., 1.)));
+        assert!(!convex_overlap(&a, &square(1., 1.)));
+        assert!(!convex_overlap(&a, &square(0.5, -1.)));
+        // a diamond with an edge touching a corner of the square
+        assert!(!convex_overlap(&a, &[(2., 0.), (4., 2.), (2., 4.), (0., 2.)]));
+    }
+
+    #[test]
+    fn empty_hull_does_not_overlap() {
+        assert!(!convex_overlap(&[], &square(0., 0.)));
+        assert!(!convex_overlap(&square(0., 0.), &[]));
+    }
+
+    #[test]
+    fn masks_overlap_inside_hulls() {
+        // an L shape, whose hull covers the empty top-right of its bounds up to the diagonal
+        let l_shape = collider(&[
+            "#..",
+            "#..",
+            "###",
+        ], &[(0., 0.), (3., 0.), (3., 1.), (1., 3.), (0., 3.)]);
+        let dot = collider(&["#"], &square(0., 0.));
+        let id = Affine::id();
+
+        let inside_hull = Affine::translate(1.4, 1.4);
+        assert!(hulls_overlap(&l_shape, &id, &dot, &inside_hull));
+        assert!(!masks_overlap(&l_shape, &id, &dot, &inside_hull));
+
+        let on_mask = Affine::translate(0.2, 1.5);
+        assert!(masks_overlap(&l_shape, &id, &dot, &on_mask));
+        assert!(masks_overlap(&dot, &on_mask, &l_shape, &id));
+
+        let beside_mask = Affine::translate(1., 1.);
+        assert!(hulls_overlap(&l_shape, &id, &dot, &beside_mask));
+        assert!(!masks_overlap(&l_shape, &id, &dot, &beside_mask));
+    }
+}
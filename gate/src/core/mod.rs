@@ -23,3 +23,6 @@ mod wasm;
 
 #[cfg(target_arch = "wasm32")]
 pub use self::wasm::*;
+
+// callback that fills a buffer with procedurally generated audio samples
+pub(crate) type StreamSource = Box<dyn FnMut(&mut [f32]) + Send>;
@@ -0,0 +1,86 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::borrow::Cow;
+use std::env;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::raw::{c_char, c_int, c_void};
+use std::path::PathBuf;
+
+use super::sdl_helpers::SdlErrorCode;
+use super::sdl_imports::*;
+use crate::app_info::AppInfo;
+
+// where packed asset files are loaded from
+#[derive(Clone)]
+pub enum AssetFiles {
+    Dir(PathBuf),
+    Embedded(&'static [(&'static str, &'static [u8])]),
+}
+
+impl AssetFiles {
+    pub fn new(info: &AppInfo, embedded: Option<&'static [(&'static str, &'static [u8])]>) -> AssetFiles {
+        match embedded {
+            Some(files) => AssetFiles::Embedded(files),
+            None => AssetFiles::Dir(info.assets_dir.clone().unwrap_or_else(default_dir)),
+        }
+    }
+
+    // directory of the asset files on disk, or None if they are embedded
+    #[cfg(all(feature = "hot_reload", debug_assertions))]
+    pub fn dir(&self) -> Option<&std::path::Path> {
+        match self {
+            AssetFiles::Dir(dir) => Some(dir),
+            AssetFiles::Embedded(_) => None,
+        }
+    }
+
+    pub fn read(&self, name: &str) -> io::Result<Cow<'static, [u8]>> {
+        match self {
+            AssetFiles::Dir(dir) => fs::read(dir.join(name)).map(Cow::Owned),
+            AssetFiles::Embedded(files) => embedded_file(files, name).map(Cow::Borrowed)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no embedded asset {:?}", name))),
+        }
+    }
+
+    // opens an asset file for reading by SDL functions, returning an error message if it could not be opened
+    pub unsafe fn rw(&self, name: &str) -> Result<*mut SDL_RWops, String> {
+        match self {
+            AssetFiles::Dir(dir) => {
+                let path = dir.join(name);
+                let c_path = path.to_str().and_then(|path| CString::new(path).ok())
+                    .ok_or_else(|| format!("invalid asset path {:?}", path))?;
+                SDL_RWFromFile(c_path.as_ptr(), c_str!("rb")).sdl_result()
+            },
+            AssetFiles::Embedded(files) => match embedded_file(files, name) {
+                Some(data) => SDL_RWFromConstMem(data.as_ptr() as *const c_void, data.len() as c_int).sdl_result(),
+                None => Err(format!("no embedded asset {:?}", name)),
+            },
+        }
+    }
+}
+
+fn embedded_file(files: &'static [(&'static str, &'static [u8])], name: &str) -> Option<&'static [u8]> {
+    files.iter().find(|&&(file, _)| file == name).map(|&(_, data)| data)
+}
+
+fn default_dir() -> PathBuf {
+    let exe_dir = env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.join("assets")));
+    match exe_dir {
+        Some(dir) if dir.is_dir() => dir,
+        _ => PathBuf::from("assets"),
+    }
+}
@@ -12,39 +12,103 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::ffi::CString;
-use std::os::raw::c_char;
+use std::os::raw::{c_int, c_void};
+use std::{ptr, slice};
 
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use super::asset_files::AssetFiles;
 use super::sdl_imports::*;
+use crate::asset_id::AudioFormat;
+use crate::core::StreamSource;
+
+// channels after the sound effect voices, used for playing raw samples
+const SAMPLE_CHANNELS: c_int = 8;
 
 // TODO delete audio after use...
 // TODO error checks...
 pub struct CoreAudio {
+    enabled: bool,
+    files: AssetFiles,
     music: Option<*mut Mix_Music>,
-    sounds: Vec<*mut Mix_Chunk>,
+    music_formats: Vec<AudioFormat>,
+    sound_formats: Vec<AudioFormat>,
+    sounds: Vec<*mut Mix_Chunk>, // null until loaded
+    sample_chunks: Vec<(c_int, *mut Mix_Chunk)>,
+    stream: Option<Box<AudioStream>>,
+    muted: bool,
+}
+
+struct AudioStream {
+    source: StreamSource,
+    channels: usize,
+    buffer: Vec<f32>,
 }
 
 impl CoreAudio {
-    pub(crate) unsafe fn new(sound_count: u16) -> CoreAudio {
-        let sounds: Vec<_> = (0..sound_count)
-            .map(|id| CString::new(format!("assets/sound{}.ogg", id)).unwrap())
-            .map(|p| Mix_LoadWAV_RW(SDL_RWFromFile(p.as_ptr(), c_str!("rb")), 0))
-            .collect();
-        CoreAudio { sounds, music: None }
+    // sounds are not loaded yet, see `set_sound`
+    pub(crate) unsafe fn new(files: AssetFiles, voice_count: u16, sound_formats: Vec<AudioFormat>,
+                             music_formats: Vec<AudioFormat>) -> CoreAudio {
+        let channel_count = voice_count as c_int + SAMPLE_CHANNELS;
+        assert!(Mix_AllocateChannels(channel_count) == channel_count);
+        // keeps Mix_PlayChannelTimed(-1, ...) from choosing one of the voices
+        assert!(Mix_ReserveChannels(voice_count as c_int) == voice_count as c_int);
+        let sounds = vec![ptr::null_mut(); sound_formats.len()];
+        CoreAudio {
+            enabled: true, files, sounds, music: None, music_formats, sound_formats,
+            sample_chunks: Vec::new(), stream: None, muted: false,
+        }
+    }
+
+    // audio that does nothing, used when the audio device is not opened
+    pub(crate) fn disabled(files: AssetFiles) -> CoreAudio {
+        CoreAudio {
+            enabled: false, files, sounds: Vec::new(), music: None, music_formats: Vec::new(), sound_formats: Vec::new(),
+            sample_chunks: Vec::new(), stream: None, muted: false,
+        }
     }
 
-    pub fn play_sound(&mut self, sound: u16) {
+    // files of the sounds to load and pass into `set_sound`, which are empty when audio is disabled
+    pub(crate) fn sound_files(&self) -> Vec<String> {
+        self.sound_formats.iter().enumerate()
+            .map(|(sound, format)| format!("sound{}.{}", sound, format.extension()))
+            .collect()
+    }
+
+    // sets the loaded chunk of `sound`, which is null if it failed to load
+    pub(crate) fn set_sound(&mut self, sound: u16, chunk: *mut Mix_Chunk) {
+        self.sounds[sound as usize] = chunk;
+    }
+
+    pub(crate) fn enabled(&self) -> bool { self.enabled }
+
+    pub fn play_sound(&mut self, sound: u16, voice: usize) {
+        // there is no chunk if audio is disabled, or if the sound is not loaded yet or failed to load
+        let chunk = self.sounds.get(sound as usize).copied().unwrap_or(ptr::null_mut());
+        if chunk.is_null() { return; }
         unsafe {
-            Mix_PlayChannelTimed(-1, self.sounds[sound as usize], 0, -1);
+            Mix_PlayChannelTimed(voice as c_int, chunk, 0, -1);
+        }
+    }
+
+    pub fn is_voice_playing(&self, voice: usize) -> bool {
+        self.enabled && unsafe { Mix_Playing(voice as c_int) != 0 }
+    }
+
+    pub fn stop_voice(&mut self, voice: usize) {
+        if !self.enabled { return; }
+        unsafe {
+            Mix_HaltChannel(voice as c_int);
         }
     }
 
     pub fn play_music(&mut self, music: u16, loops: bool) {
-        
+        if !self.enabled { return; }
             self.stop_music();
             let loops = if loops { -1 } else { 1 };
-            let music = CString::new(format!("assets/music{}.ogg", music)).unwrap();
-            let music = unsafe {Mix_LoadMUS(music.as_ptr())};
+            let format = self.music_formats[music as usize];
+            let src = unsafe { self.files.rw(&format!("music{}.{}", music, format.extension())) };
+            let music = src.map_or(ptr::null_mut(), |src| unsafe { Mix_LoadMUS_RW(src, 1) });
             unsafe {Mix_PlayMusic(music, loops)};
             self.music = Some(music);
         
@@ -57,4 +121,137 @@ impl CoreAudio {
             }
         }
     }
+
+    pub fn play_samples(&mut self, samples: &[f32], sample_rate: u32, channels: u16) {
+        if !self.enabled { return; }
+        self.free_finished_samples();
+        // SDL_mixer converts a loaded WAV to the output format, so wrapping the samples
+        // in a float WAV header avoids doing any resampling here
+        let wav = float_wav(samples, sample_rate, channels);
+        unsafe {
+            let chunk = Mix_LoadWAV_RW(SDL_RWFromConstMem(wav.as_ptr() as *const c_void, wav.len() as c_int), 1);
+            if chunk.is_null() { return; }
+            let channel = Mix_PlayChannelTimed(-1, chunk, 0, -1);
+            if channel < 0 {
+                Mix_FreeChunk(chunk);
+            } else {
+                self.sample_chunks.push((channel, chunk));
+            }
+        }
+    }
+
+    fn free_finished_samples(&mut self) {
+        self.sample_chunks.retain(|&(channel, chunk)| unsafe {
+            let playing = Mix_Playing(channel) != 0 && Mix_GetChunk(channel) == chunk;
+            if !playing { Mix_FreeChunk(chunk); }
+            playing
+        });
+    }
+
+    pub fn open_stream(&mut self, source: StreamSource) {
+        if !self.enabled { return; }
+        self.close_stream();
+        let (mut frequency, mut format, mut channels) = (0, 0, 0);
+        unsafe { Mix_QuerySpec(&mut frequency, &mut format, &mut channels); }
+        assert!(format == MIX_DEFAULT_FORMAT, "unexpected audio format {}", format);
+        self.stream = Some(Box::new(AudioStream { source, channels: channels as usize, buffer: Vec::new() }));
+        if !self.muted { self.attach_stream(); }
+    }
+
+    fn attach_stream(&mut self) {
+        if let Some(stream) = self.stream.as_mut() {
+            unsafe { Mix_SetPostMix(Some(mix_stream), &mut **stream as *mut AudioStream as *mut c_void); }
+        }
+    }
+
+    pub fn close_stream(&mut self) {
+        if self.stream.is_some() {
+            // Mix_SetPostMix locks the audio device, so the stream is no longer in use afterwards
+            unsafe { Mix_SetPostMix(None, ptr::null_mut()); }
+            self.stream = None;
+        }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        if !self.enabled || muted == self.muted { return; }
+        self.muted = muted;
+        let volume = if muted { 0 } else { MIX_MAX_VOLUME };
+        unsafe {
+            Mix_Volume(-1, volume);
+            Mix_VolumeMusic(volume);
+            // the stream is mixed in after channel volumes are applied, so it is detached instead
+            if muted {
+                Mix_SetPostMix(None, ptr::null_mut());
+            }
+        }
+        if !muted { self.attach_stream(); }
+    }
+
+    pub fn stream_sample_rate(&self) -> u32 {
+        if !self.enabled { return MIX_DEFAULT_FREQUENCY as u32; }
+        let (mut frequency, mut format, mut channels) = (0, 0, 0);
+        unsafe { Mix_QuerySpec(&mut frequency, &mut format, &mut channels); }
+        frequency as u32
+    }
+}
+
+pub(crate) fn mix_init_flags(formats: &[AudioFormat]) -> c_int {
+    formats.iter().map(|format| match format {
+        AudioFormat::Ogg => MIX_INIT_OGG,
+        AudioFormat::Flac => MIX_INIT_FLAC,
+        AudioFormat::Wav => 0,
+    }).fold(0, |flags, flag| flags | flag)
+}
+
+impl Drop for CoreAudio {
+    fn drop(&mut self) {
+        self.close_stream();
+    }
+}
+
+unsafe extern "C" fn mix_stream(udata: *mut c_void, stream: *mut u8, len: c_int) {
+    let stream_state = &mut *(udata as *mut AudioStream);
+    let out = slice::from_raw_parts_mut(stream as *mut i16, len as usize / 2);
+    let frames = out.len() / stream_state.channels;
+
+    let buffer = &mut stream_state.buffer;
+    buffer.clear();
+    buffer.resize(2 * frames, 0.);
+    (stream_state.source)(buffer);
+
+    for (frame, out) in out.chunks_mut(stream_state.channels).enumerate() {
+        let (left, right) = (buffer[2 * frame], buffer[2 * frame + 1]);
+        if out.len() == 1 {
+            mix_sample(&mut out[0], 0.5 * (left + right));
+        } else {
+            mix_sample(&mut out[0], left);
+            mix_sample(&mut out[1], right);
+        }
+    }
+}
+
+fn mix_sample(out: &mut i16, sample: f32) {
+    let mixed = *out as f32 + sample * i16::MAX as f32;
+    *out = mixed.max(i16::MIN as f32).min(i16::MAX as f32) as i16;
+}
+
+fn float_wav(samples: &[f32], sample_rate: u32, channels: u16) -> Vec<u8> {
+    let data_len = 4 * samples.len() as u32;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.write_u32::<LittleEndian>(36 + data_len).unwrap();
+    out.extend_from_slice(b"WAVEfmt ");
+    out.write_u32::<LittleEndian>(16).unwrap();
+    out.write_u16::<LittleEndian>(3).unwrap(); // IEEE float format
+    out.write_u16::<LittleEndian>(channels).unwrap();
+    out.write_u32::<LittleEndian>(sample_rate).unwrap();
+    out.write_u32::<LittleEndian>(4 * sample_rate * channels as u32).unwrap();
+    out.write_u16::<LittleEndian>(4 * channels).unwrap();
+    out.write_u16::<LittleEndian>(32).unwrap();
+    out.extend_from_slice(b"data");
+    out.write_u32::<LittleEndian>(data_len).unwrap();
+    for &sample in samples {
+        out.write_f32::<LittleEndian>(sample).unwrap();
+    }
+    out
 }
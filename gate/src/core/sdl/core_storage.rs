@@ -0,0 +1,89 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::ffi::{CStr, CString};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
+
+use super::sdl_imports::*;
+use crate::save::{SaveError, SaveStorage};
+
+// stores each save slot in its own file under the per-user data directory
+pub struct CoreStorage {
+    dir: Option<PathBuf>,
+}
+
+impl CoreStorage {
+    pub(crate) fn new(app_id: &str) -> CoreStorage {
+        let app_id = CString::new(app_id).expect("invalid app id");
+        let dir = unsafe {
+            let path = SDL_GetPrefPath(c_str!(""), app_id.as_ptr());
+            if path.is_null() {
+                None
+            } else {
+                let dir = PathBuf::from(CStr::from_ptr(path).to_string_lossy().into_owned());
+                SDL_free(path as *mut c_void);
+                Some(dir)
+            }
+        };
+        CoreStorage { dir }
+    }
+
+    fn path(&self, file_name: &str) -> Result<PathBuf, SaveError> {
+        self.dir.as_ref().map(|dir| dir.join(file_name)).ok_or(SaveError::Unavailable)
+    }
+
+    pub(crate) fn read_file(&self, file_name: &str) -> Result<Option<Vec<u8>>, SaveError> {
+        match fs::read(self.path(file_name)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub(crate) fn write_file(&self, file_name: &str, data: &[u8]) -> Result<(), SaveError> {
+        // writes to a temporary file first, so a failed write never leaves a partial file behind
+        let path = self.path(file_name)?;
+        let temp_path = self.path(&format!("{}.tmp", file_name))?;
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    pub(crate) fn delete_file(&self, file_name: &str) -> Result<(), SaveError> {
+        match fs::remove_file(self.path(file_name)?) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl SaveStorage for CoreStorage {
+    fn read(&self, slot: &str) -> Result<Option<Vec<u8>>, SaveError> {
+        self.read_file(&format!("{}.sav", slot))
+    }
+
+    fn write(&mut self, slot: &str, data: &[u8]) -> Result<(), SaveError> {
+        self.write_file(&format!("{}.sav", slot), data)
+    }
+
+    fn delete(&mut self, slot: &str) -> Result<(), SaveError> {
+        self.delete_file(&format!("{}.sav", slot))
+    }
+}
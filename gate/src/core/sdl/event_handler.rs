@@ -24,11 +24,12 @@ use crate::renderer::Renderer;
 
 pub struct EventHandler {
     held_keys: HashSet<KeyCode>,
+    mute_on_unfocus: bool,
 }
 
 impl EventHandler {
-    pub fn new() -> EventHandler {
-        EventHandler { held_keys: HashSet::new() }
+    pub fn new(mute_on_unfocus: bool) -> EventHandler {
+        EventHandler { held_keys: HashSet::new(), mute_on_unfocus }
     }
 
     pub unsafe fn process_events<AS: AppAssetId, AP: App<AS>>(&mut self, app: &mut AP, ctx: &mut AppContext<AS>,
@@ -78,12 +79,25 @@ impl EventHandler {
                         }
                     }
                 },
+                SDL_WINDOWEVENT => {
+                    match event.window.event {
+                        SDL_WINDOWEVENT_FOCUS_GAINED => self.set_focused(ctx, true),
+                        SDL_WINDOWEVENT_FOCUS_LOST => self.set_focused(ctx, false),
+                        _ => {},
+                    }
+                },
                 _ => {},
             }
             if ctx.take_close_request() { return false; }
         }
         true
     }
+
+    fn set_focused<AS: AppAssetId>(&self, ctx: &mut AppContext<AS>, focused: bool) {
+        if self.mute_on_unfocus {
+            ctx.audio.core.set_muted(!focused);
+        }
+    }
 }
 
 #[allow(non_upper_case_globals)]
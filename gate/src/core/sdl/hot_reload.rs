@@ -0,0 +1,117 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime};
+
+use gate_build::hot_reload::SpriteSources;
+
+use super::asset_files::AssetFiles;
+use super::sdl_imports::SDL_Renderer;
+use crate::AppContext;
+use crate::app_info::AppInfo;
+use crate::asset_id::AppAssetId;
+use crate::renderer::Renderer;
+use crate::renderer::atlas::Atlas;
+
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+const RELOAD_NAME: &str = "sprites_reload";
+
+// watches the sprite source files recorded by `AssetPacker::hot_reload`,
+// re-packing and swapping the sprite atlas when they change
+pub struct HotReload {
+    sources: SpriteSources,
+    files: AssetFiles,
+    modified: SystemTime,
+    last_check: Instant,
+}
+
+impl HotReload {
+    pub fn new(assets_dir: &Path) -> Option<HotReload> {
+        let sources = match SpriteSources::read(assets_dir) {
+            Ok(Some(sources)) => sources,
+            Ok(None) => {
+                eprintln!("sprite hot reloading is disabled, since AssetPacker::hot_reload was not invoked");
+                return None;
+            },
+            Err(err) => {
+                eprintln!("failed to read sprite sources for hot reloading: {}", err);
+                return None;
+            },
+        };
+        let modified = sources.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        let files = AssetFiles::Dir(assets_dir.to_path_buf());
+        Some(HotReload { sources, files, modified, last_check: Instant::now() })
+    }
+
+    pub unsafe fn poll<AS: AppAssetId>(&mut self, info: &AppInfo, sdl_renderer: *mut SDL_Renderer,
+                                       renderer: &mut Renderer<AS>, ctx: &mut AppContext<AS>) {
+        // the loading atlas is not reloaded, since it is replaced once assets are loaded
+        if !ctx.assets_loaded() || self.last_check.elapsed() < CHECK_INTERVAL { return; }
+        self.last_check = Instant::now();
+        // errors are usually caused by files that are being saved, so they are checked again later
+        let modified = match self.sources.modified() {
+            Ok(modified) => modified,
+            Err(_) => return,
+        };
+        if modified == self.modified { return; }
+        self.modified = modified;
+
+        let out = self.files.dir().unwrap().join(RELOAD_NAME);
+        let names = match panic::catch_unwind(AssertUnwindSafe(|| self.sources.repack(&out))) {
+            Ok(names) => names,
+            Err(_) => {
+                eprintln!("failed to re-pack sprites, keeping the previous sprites");
+                return;
+            },
+        };
+        let atlas = match self.files.read(&format!("{}.atlas", RELOAD_NAME)).and_then(|data| Atlas::new(&*data)) {
+            Ok(atlas) => atlas,
+            Err(err) => {
+                eprintln!("failed to read re-packed sprite atlas: {}", err);
+                return;
+            },
+        };
+        let ids = remap_ids(self.sources.names(), &names);
+        let sprites_tex = self.files.rw(&format!("{}.png", RELOAD_NAME))
+            .and_then(|src| super::load_sprites_tex(sdl_renderer, src, &atlas, info.texture_filter));
+        let sprites_tex = match sprites_tex {
+            Ok(sprites_tex) => sprites_tex,
+            Err(message) => {
+                eprintln!("failed to load re-packed sprite texture: {}", message);
+                return;
+            },
+        };
+        renderer.reload_sprites(atlas, &ids, sprites_tex);
+        ctx.set_sprite_infos(renderer.sprite_infos().to_vec());
+        ctx.set_sprite_colliders(renderer.sprite_colliders().to_vec());
+        eprintln!("reloaded sprites");
+    }
+}
+
+// maps the ID of each sprite the app was compiled with to its ID in the re-packed atlas, if it still exists
+fn remap_ids(old_names: &[String], new_names: &[String]) -> Vec<Option<u16>> {
+    let new_ids: HashMap<&str, u16> = new_names.iter().enumerate().map(|(id, name)| (name.as_str(), id as u16)).collect();
+    let old_set: HashSet<&str> = old_names.iter().map(|name| name.as_str()).collect();
+    let removed: Vec<_> = old_names.iter().filter(|name| !new_ids.contains_key(name.as_str())).collect();
+    let added: Vec<_> = new_names.iter().filter(|name| !old_set.contains(name.as_str())).collect();
+    if !removed.is_empty() || !added.is_empty() {
+        eprintln!("warning: the set of sprite IDs has changed, rebuild the app to use the new IDs");
+        if !added.is_empty() { eprintln!("  added sprites (not yet available): {:?}", added); }
+        if !removed.is_empty() { eprintln!("  removed sprites (drawn as empty): {:?}", removed); }
+    }
+    old_names.iter().map(|name| new_ids.get(name.as_str()).copied()).collect()
+}
@@ -0,0 +1,119 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::ptr;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use super::asset_files::AssetFiles;
+use super::sdl_helpers::SdlErrorCode;
+use super::sdl_imports::*;
+use crate::{AppContext, Error};
+use crate::app_info::AppInfo;
+use crate::asset_id::AppAssetId;
+use crate::renderer::Renderer;
+use crate::renderer::atlas::Atlas;
+
+// an asset decoded by the loader thread, to be handed over to the renderer or audio on the main thread
+enum Decoded {
+    Sprites(Result<(Atlas, *mut SDL_Surface), Error>),
+    Sound(u16, *mut Mix_Chunk),
+}
+
+// the decoded surface and chunks are no longer touched by the loader thread once they are sent
+unsafe impl Send for Decoded {}
+
+// loads the main sprite atlas (if the app started with the loading atlas) and the sounds,
+// decoding them on a separate thread so that the loading screen keeps running smoothly,
+// and handing them over between frames
+pub struct Loader {
+    decoded: Receiver<Decoded>,
+    sprites_loaded: bool,
+    sounds_loaded: u16,
+    sound_files: Vec<String>,
+}
+
+impl Loader {
+    // starts decoding the assets, where `sound_files` are the files of the sounds to load
+    pub fn new(files: AssetFiles, sprites_loaded: bool, sound_files: Vec<String>) -> Loader {
+        let (sender, decoded) = mpsc::channel();
+        let thread_sound_files = sound_files.clone();
+        thread::spawn(move || unsafe {
+            // sending only fails once the app has exited, so the thread stops without cleaning up
+            if !sprites_loaded && sender.send(Decoded::Sprites(decode_sprites(&files))).is_err() { return; }
+            for (sound, file) in thread_sound_files.iter().enumerate() {
+                let chunk = files.rw(file).map_or(ptr::null_mut(), |src| Mix_LoadWAV_RW(src, 1));
+                if sender.send(Decoded::Sound(sound as u16, chunk)).is_err() { return; }
+            }
+        });
+        Loader { decoded, sprites_loaded, sounds_loaded: 0, sound_files }
+    }
+
+    // hands over the assets that have been decoded so far, or waits for all of them if `wait` is true
+    pub unsafe fn load<AS: AppAssetId>(&mut self, info: &AppInfo, sdl_renderer: *mut SDL_Renderer,
+                                       renderer: &mut Renderer<AS>, ctx: &mut AppContext<AS>,
+                                       wait: bool) -> Result<(), Error> {
+        if ctx.assets_loaded() { return Ok(()); }
+        let sound_count = self.sound_files.len() as u16;
+        loop {
+            if self.sprites_loaded && self.sounds_loaded == sound_count {
+                ctx.set_assets_loaded();
+                return Ok(());
+            }
+            let decoded = if wait {
+                self.decoded.recv().map_err(|_| TryRecvError::Disconnected)
+            } else {
+                self.decoded.try_recv()
+            };
+            match decoded {
+                Ok(Decoded::Sprites(sprites)) => {
+                    let (atlas, surface) = sprites?;
+                    let sprites_tex = super::sprites_tex_from_surface(sdl_renderer, surface, &atlas, info.texture_filter)
+                        .map_err(|message| Error::Asset { file: "sprites.png".to_owned(), message })?;
+                    renderer.replace_sprites(atlas, sprites_tex);
+                    ctx.set_sprite_infos(renderer.sprite_infos().to_vec());
+                    ctx.set_sprite_colliders(renderer.sprite_colliders().to_vec());
+                    self.sprites_loaded = true;
+                },
+                Ok(Decoded::Sound(sound, chunk)) => {
+                    ctx.audio.core.set_sound(sound, chunk);
+                    self.sounds_loaded += 1;
+                },
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => {
+                    // the thread only stops early if it panics, while loading the next asset in order
+                    let file = if self.sprites_loaded {
+                        self.sound_files[self.sounds_loaded as usize].clone()
+                    } else {
+                        "sprites.png".to_owned()
+                    };
+                    return Err(Error::Asset { file, message: "asset loader thread stopped unexpectedly".to_owned() });
+                },
+            }
+            let loaded = self.sprites_loaded as u16 + self.sounds_loaded;
+            ctx.set_load_progress(loaded as f64 / (1 + sound_count) as f64);
+        }
+    }
+}
+
+// reads the main sprite atlas and decodes its image, leaving the texture upload to the main thread
+unsafe fn decode_sprites(files: &AssetFiles) -> Result<(Atlas, *mut SDL_Surface), Error> {
+    let asset_error = |file: &str, message: String| Error::Asset { file: file.to_owned(), message };
+    let atlas = files.read("sprites.atlas").and_then(|data| Atlas::new(&*data))
+        .map_err(|err| asset_error("sprites.atlas", err.to_string()))?;
+    let surface = files.rw("sprites.png").and_then(|src| IMG_Load_RW(src, 1).sdl_result())
+        .map_err(|message| asset_error("sprites.png", message))?;
+    Ok((atlas, surface))
+}
@@ -13,17 +13,22 @@
 // limitations under the License.
 
 mod app_clock;
+mod asset_files;
 mod core_audio;
+mod core_storage;
 mod event_handler;
+#[cfg(all(feature = "hot_reload", debug_assertions))]
+mod hot_reload;
+mod loader;
 pub(crate) mod sdl_helpers;
 pub(crate) mod sdl_imports;
 
 pub use self::core_audio::CoreAudio;
+pub use self::core_storage::CoreStorage;
 
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
-use std::fs::File;
-use std::io::BufReader;
+use std::{mem, ptr};
 
 use sdl_helpers::*;
 use sdl_imports::*;
@@ -31,15 +36,17 @@ use sdl_imports::*;
 use gl;
 use gl::types::*;
 
-use crate::{AppContext, App};
+use crate::{AppContext, App, Error};
 use crate::app_info::AppInfo;
-use crate::renderer::Renderer;
-use crate::renderer::core_renderer::CoreRenderer;
+use crate::renderer::{Renderer, TextureFilter};
+use crate::renderer::core_renderer::{CoreRenderer, set_texture_filter};
 use crate::renderer::render_buffer::RenderBuffer;
 use crate::renderer::atlas::Atlas;
-use crate::asset_id::{AppAssetId, IdU16};
+use crate::asset_id::{AppAssetId, AudioFormat, AudioId, IdU16};
 use self::app_clock::AppClock;
+use self::asset_files::AssetFiles;
 use self::event_handler::EventHandler;
+use self::loader::Loader;
 
 /// Macro to be placed in the `main.rs` file for a Gate app.
 ///
@@ -50,26 +57,31 @@ macro_rules! gate_header {
     () => {};
 }
 
-pub fn run<AS, AP, F>(info: AppInfo, app: F) where
+pub fn run<AS, AP, F>(mut info: AppInfo, app: F) -> Result<(), Error> where
     AS: AppAssetId,
     AP: App<AS>,
     F: FnOnce(&mut AppContext<AS>) -> AP
 {
     unsafe {
         SDL_SetHint(SDL_HINT_RENDER_DRIVER, c_str!("opengles2"));
-        SDL_Init(SDL_INIT_VIDEO | SDL_INIT_AUDIO | SDL_INIT_TIMER | SDL_INIT_EVENTS).sdl_check();
-        sdl_assert(Mix_Init(MIX_INIT_OGG) == MIX_INIT_OGG);
-
-        Mix_OpenAudio(MIX_DEFAULT_FREQUENCY, MIX_DEFAULT_FORMAT, MIX_DEFAULT_CHANNELS, 1024).sdl_check();
-        assert!(Mix_AllocateChannels(16) == 16);
-
-        let mut event_handler = EventHandler::new();
-
-        SDL_GL_SetAttribute(SDL_GL_CONTEXT_PROFILE_MASK, SDL_GL_CONTEXT_PROFILE_ES).sdl_check();
-        SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, 2).sdl_check();
-        SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, 0).sdl_check();
-
-        let title = CString::new(info.title).expect("invalid title");
+        SDL_Init(SDL_INIT_VIDEO | SDL_INIT_TIMER | SDL_INIT_EVENTS).sdl_result().map_err(Error::Init)?;
+        let sound_formats = audio_formats::<AS::Sound>();
+        let music_formats = audio_formats::<AS::Music>();
+        let audio_enabled = !info.audio_disabled && match init_audio(&sound_formats, &music_formats) {
+            Ok(()) => true,
+            Err(message) => {
+                eprintln!("failed to open audio device, continuing without audio: {}", message);
+                false
+            },
+        };
+
+        let mut event_handler = EventHandler::new(info.mute_on_unfocus);
+
+        SDL_GL_SetAttribute(SDL_GL_CONTEXT_PROFILE_MASK, SDL_GL_CONTEXT_PROFILE_ES).sdl_result().map_err(Error::Graphics)?;
+        SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, 2).sdl_result().map_err(Error::Graphics)?;
+        SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, 0).sdl_result().map_err(Error::Graphics)?;
+
+        let title = CString::new(info.title).map_err(|_| Error::Window(format!("invalid title {:?}", info.title)))?;
         let window = SDL_CreateWindow(
             title.as_ptr(),
             SDL_WINDOWPOS_CENTERED_MASK,
@@ -77,25 +89,63 @@ pub fn run<AS, AP, F>(info: AppInfo, app: F) where
             info.window_pixels.0 as c_int,
             info.window_pixels.1 as c_int,
             SDL_WINDOW_RESIZABLE | SDL_WINDOW_OPENGL,
-        ).sdl_check();
+        ).sdl_result().map_err(Error::Window)?;
 
-        let sdl_renderer = SDL_CreateRenderer(window, -1, SDL_RENDERER_ACCELERATED | SDL_RENDERER_PRESENTVSYNC).sdl_check();
+        let sdl_renderer = SDL_CreateRenderer(window, -1, SDL_RENDERER_ACCELERATED | SDL_RENDERER_PRESENTVSYNC)
+            .sdl_result().map_err(Error::Graphics)?;
+        check_renderer_driver(sdl_renderer)?;
 
         init_gl();
 
-        let mut renderer = build_renderer(&info, sdl_renderer);
+        // with a loading atlas, the app is created before the main sprite atlas and sounds are loaded
+        let loading = !AS::loading_sprites().is_empty();
+        let files = AssetFiles::new(&info, AS::embedded_files());
+        let mut renderer = build_renderer(&info, &files, sdl_renderer, loading)?;
 
         gl_error_check();
 
-        let mut ctx = AppContext::new(CoreAudio::new(AS::Sound::count()), renderer.app_dims(), renderer.native_px());
+        let audio = if audio_enabled {
+            CoreAudio::new(files.clone(), info.audio_channels, sound_formats, music_formats)
+        } else {
+            CoreAudio::disabled(files.clone())
+        };
+        let storage = info.take_save_storage();
+        let mut ctx = AppContext::new(audio, info.audio_channels, storage, renderer.app_dims(), renderer.native_px());
+        ctx.set_sprite_infos(renderer.sprite_infos().to_vec());
+        ctx.set_sprite_colliders(renderer.sprite_colliders().to_vec());
+
+        let mut loader = Loader::new(files.clone(), !loading, ctx.audio.core.sound_files());
+        if !loading {
+            loader.load(&info, sdl_renderer, &mut renderer, &mut ctx, true)?;
+        }
+
+        let cookie_storage = CoreStorage::new(&info.resolved_app_id());
+        load_cookie(&cookie_storage, &mut ctx);
 
         if info.print_gl_info { print_gl_info(); }
 
         let mut app = app(&mut ctx);
 
+        // audio is never blocked natively, so it is unlocked as soon as the app exists
+        if audio_enabled {
+            ctx.set_audio_available();
+            app.audio_unlocked(&mut ctx);
+        }
+
         let mut clock = AppClock::new();
 
+        #[cfg(all(feature = "hot_reload", debug_assertions))]
+        let mut hot_reload = files.dir().and_then(hot_reload::HotReload::new);
+
         loop {
+            write_cookie(&cookie_storage, &mut ctx);
+            loader.load(&info, sdl_renderer, &mut renderer, &mut ctx, false)?;
+
+            #[cfg(all(feature = "hot_reload", debug_assertions))]
+            if let Some(hot_reload) = hot_reload.as_mut() {
+                hot_reload.poll(&info, sdl_renderer, &mut renderer, &mut ctx);
+            }
+
             gl::ClearColor(0., 0., 0., 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT);
 
@@ -106,12 +156,13 @@ pub fn run<AS, AP, F>(info: AppInfo, app: F) where
                 renderer.set_screen_dims((screen_dims.0 as u32, screen_dims.1 as u32));
                 ctx.set_dims(renderer.app_dims(), renderer.native_px());
                 app.render(&mut renderer, &ctx);
-                renderer.flush();
+                renderer.end_frame();
             }
             SDL_RenderPresent(sdl_renderer);
             gl_error_check();
 
             let elapsed = clock.step();
+            ctx.audio.advance(elapsed);
 
             match (ctx.is_fullscreen(), ctx.desires_fullscreen()) {
                 (false, true) => {
@@ -130,25 +181,130 @@ pub fn run<AS, AP, F>(info: AppInfo, app: F) where
             app.advance(elapsed.min(crate::MAX_TIMESTEP), &mut ctx);
             if ctx.take_close_request() { break; }
         }
+        write_cookie(&cookie_storage, &mut ctx);
+    }
+    Ok(())
+}
+
+const COOKIE_FILE: &str = "cookie.bin";
+
+fn load_cookie<AS: AppAssetId>(storage: &CoreStorage, ctx: &mut AppContext<AS>) {
+    match storage.read_file(COOKIE_FILE) {
+        Ok(Some(cookie)) if cookie.len() < crate::app_context::MAX_COOKIE_LEN => {
+            ctx.set_cookie(cookie);
+            ctx.take_cookie_updated_flag();
+        },
+        Ok(_) => {},
+        Err(err) => eprintln!("failed to load cookie: {}", err),
     }
 }
 
-unsafe fn build_renderer<AS: AppAssetId>(info: &AppInfo, sdl_renderer: *mut SDL_Renderer) -> Renderer<AS> {
-    let sprites_atlas = Atlas::new(BufReader::new(File::open("assets/sprites.atlas").unwrap())).unwrap();
+fn write_cookie<AS: AppAssetId>(storage: &CoreStorage, ctx: &mut AppContext<AS>) {
+    if ctx.take_cookie_updated_flag() {
+        if let Err(err) = storage.write_file(COOKIE_FILE, ctx.cookie()) {
+            eprintln!("failed to write cookie: {}", err);
+        }
+    }
+}
+
+unsafe fn build_renderer<AS: AppAssetId>(info: &AppInfo, files: &AssetFiles, sdl_renderer: *mut SDL_Renderer,
+                                         loading: bool) -> Result<Renderer<AS>, Error> {
+    let (sprites_atlas, sprites_tex) = load_sprites::<AS>(info, files, sdl_renderer, loading)?;
     let render_buffer = RenderBuffer::new(&info, info.window_pixels, sprites_atlas);
 
-    let sprites_tex = IMG_LoadTexture(sdl_renderer, c_str!("assets/sprites.png")).sdl_check();
+    let core_renderer = CoreRenderer::new(sprites_tex, info.texture_filter).map_err(Error::Graphics)?;
+
+    Ok(Renderer::<AS>::new(render_buffer, core_renderer))
+}
+
+// loads the main sprite atlas and its texture, or the loading atlas if `loading` is true
+unsafe fn load_sprites<AS: AppAssetId>(info: &AppInfo, files: &AssetFiles, sdl_renderer: *mut SDL_Renderer,
+                                       loading: bool) -> Result<(Atlas, *mut SDL_Texture), Error> {
+    let name = if loading { "loading_sprites" } else { "sprites" };
+    let (atlas_file, image_file) = (format!("{}.atlas", name), format!("{}.png", name));
+    let asset_error = |file: &str, message: String| Error::Asset { file: file.to_owned(), message };
+    let mut atlas = files.read(&atlas_file).and_then(|data| Atlas::new(&*data));
+    if loading {
+        atlas = atlas.and_then(|atlas| atlas.for_loading(AS::loading_sprites(), AS::Sprite::count()));
+    }
+    let atlas = atlas.map_err(|err| asset_error(&atlas_file, err.to_string()))?;
+    let sprites_tex = files.rw(&image_file)
+        .and_then(|src| load_sprites_tex(sdl_renderer, src, &atlas, info.texture_filter))
+        .map_err(|message| asset_error(&image_file, message))?;
+    Ok((atlas, sprites_tex))
+}
+
+// SDL falls back to other render drivers when OpenGL ES 2.0 is not supported, which Gate cannot draw with
+unsafe fn check_renderer_driver(sdl_renderer: *mut SDL_Renderer) -> Result<(), Error> {
+    let mut renderer_info: SDL_RendererInfo = mem::zeroed();
+    SDL_GetRendererInfo(sdl_renderer, &mut renderer_info).sdl_result().map_err(Error::Graphics)?;
+    let name = CStr::from_ptr(renderer_info.name).to_string_lossy();
+    if name == "opengles2" {
+        Ok(())
+    } else {
+        Err(Error::Graphics(format!("OpenGL ES 2.0 is not available, got {:?} render driver instead", name)))
+    }
+}
+
+// loads the sprite atlas texture from `src`, which is freed afterwards, returning the SDL error message on failure
+unsafe fn load_sprites_tex(sdl_renderer: *mut SDL_Renderer, src: *mut SDL_RWops, atlas: &Atlas,
+                           default_filter: TextureFilter) -> Result<*mut SDL_Texture, String> {
+    let surface = IMG_Load_RW(src, 1).sdl_result()?;
+    sprites_tex_from_surface(sdl_renderer, surface, atlas, default_filter)
+}
+
+// creates the sprite atlas texture from a decoded image `surface`, which is freed afterwards
+unsafe fn sprites_tex_from_surface(sdl_renderer: *mut SDL_Renderer, surface: *mut SDL_Surface, atlas: &Atlas,
+                                   default_filter: TextureFilter) -> Result<*mut SDL_Texture, String> {
+    let sprites_tex = SDL_CreateTextureFromSurface(sdl_renderer, surface);
+    SDL_FreeSurface(surface);
+    let sprites_tex = sprites_tex.sdl_result()?;
+    let sprites_dims = (atlas.dims.0 as u32, atlas.dims.1 as u32);
 
     let (mut tex_w, mut tex_h) = (0., 0.);
-    SDL_GL_BindTexture(sprites_tex, &mut tex_w, &mut tex_h).sdl_check();
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
-    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
-    SDL_GL_UnbindTexture(sprites_tex).sdl_check();
+    let result = SDL_GL_BindTexture(sprites_tex, &mut tex_w, &mut tex_h).sdl_result().and_then(|()| {
+        set_texture_filter(atlas.filter(default_filter), sprites_dims);
+        SDL_GL_UnbindTexture(sprites_tex).sdl_result()
+    });
+    match result {
+        Ok(()) => Ok(sprites_tex),
+        Err(message) => {
+            SDL_DestroyTexture(sprites_tex);
+            Err(message)
+        },
+    }
+}
+
+pub fn show_error_message_box(title: &str, message: &str) {
+    let title = CString::new(title).unwrap_or_default();
+    let message = CString::new(message).unwrap_or_default();
+    unsafe {
+        // the message has already been returned as an error, so a failure to show it is ignored
+        SDL_ShowSimpleMessageBox(SDL_MESSAGEBOX_ERROR, title.as_ptr(), message.as_ptr(), ptr::null_mut());
+    }
+}
 
-    // TODO need to ensure Nearest-neighbor sampling is used?
-    let core_renderer = CoreRenderer::new(sprites_tex);
+unsafe fn init_audio(sound_formats: &[AudioFormat], music_formats: &[AudioFormat]) -> Result<(), String> {
+    SDL_InitSubSystem(SDL_INIT_AUDIO).sdl_result()?;
+    let result = open_audio(sound_formats, music_formats);
+    if result.is_err() {
+        // shuts audio back down, so that an app continuing without audio leaves the audio device alone
+        Mix_Quit();
+        SDL_QuitSubSystem(SDL_INIT_AUDIO);
+    }
+    result
+}
+
+unsafe fn open_audio(sound_formats: &[AudioFormat], music_formats: &[AudioFormat]) -> Result<(), String> {
+    let mix_flags = core_audio::mix_init_flags(sound_formats) | core_audio::mix_init_flags(music_formats);
+    if Mix_Init(mix_flags) != mix_flags {
+        return Err(format!("failed to load audio decoders: {}", sdl_error_message()));
+    }
+    Mix_OpenAudio(MIX_DEFAULT_FREQUENCY, MIX_DEFAULT_FORMAT, MIX_DEFAULT_CHANNELS, 1024).sdl_result()
+}
 
-    Renderer::<AS>::new(render_buffer, core_renderer)
+fn audio_formats<A: AudioId>() -> Vec<AudioFormat> {
+    (0..A::count()).map(|id| A::from_u16(id).unwrap().format()).collect()
 }
 
 unsafe fn init_gl() {
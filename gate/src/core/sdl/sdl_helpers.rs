@@ -17,29 +17,31 @@ use std::os::raw::c_int;
 use super::sdl_imports::*;
 
 // TODO make sure to call this on all relevant sdl return values
-pub trait SdlErrorCode {
+pub trait SdlErrorCode: Sized {
     type Out;
-    unsafe fn sdl_check(self) -> Self::Out;
+
+    // returns the SDL error message on failure
+    unsafe fn sdl_result(self) -> Result<Self::Out, String>;
+
+    unsafe fn sdl_check(self) -> Self::Out {
+        self.sdl_result().unwrap_or_else(|message| panic!("SDL error: {}", message))
+    }
 }
 
 impl SdlErrorCode for c_int {
     type Out = ();
-    unsafe fn sdl_check(self) {
-        sdl_assert(self == 0);
+    unsafe fn sdl_result(self) -> Result<(), String> {
+        if self == 0 { Ok(()) } else { Err(sdl_error_message()) }
     }
 }
 
 impl<T> SdlErrorCode for *mut T {
     type Out = Self;
-    unsafe fn sdl_check(self) -> Self {
-        sdl_assert(!self.is_null());
-        self
+    unsafe fn sdl_result(self) -> Result<Self, String> {
+        if self.is_null() { Err(sdl_error_message()) } else { Ok(self) }
     }
 }
 
-pub unsafe fn sdl_assert(condition: bool) {
-    if !condition {
-        let message = SDL_GetError();
-        panic!("SDL error: {}", CStr::from_ptr(message).to_str().unwrap());
-    }
+pub unsafe fn sdl_error_message() -> String {
+    CStr::from_ptr(SDL_GetError()).to_string_lossy().into_owned()
 }
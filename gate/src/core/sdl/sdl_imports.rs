@@ -24,8 +24,14 @@ pub use sdl::{
     SDL_BUTTON_MIDDLE,
     SDL_BUTTON_RIGHT,
     SDL_CreateRenderer,
+    SDL_CreateTextureFromSurface,
     SDL_CreateWindow,
+    SDL_DestroyTexture,
+    SDL_free,
+    SDL_FreeSurface,
     SDL_GetError,
+    SDL_GetRendererInfo,
+    SDL_GetPrefPath,
     SDL_GetTicks,
     SDL_GetWindowSize,
     SDL_GL_BindTexture,
@@ -34,16 +40,23 @@ pub use sdl::{
     SDL_GL_UnbindTexture,
     SDL_GLattr::{SDL_GL_CONTEXT_PROFILE_MASK, SDL_GL_CONTEXT_MAJOR_VERSION, SDL_GL_CONTEXT_MINOR_VERSION},
     SDL_Init,
+    SDL_InitSubSystem,
     SDL_INIT_AUDIO,
     SDL_INIT_EVENTS,
     SDL_INIT_TIMER,
     SDL_INIT_VIDEO,
     SDL_PollEvent,
+    SDL_QuitSubSystem,
     SDL_Renderer,
+    SDL_RendererInfo,
     SDL_RenderPresent,
+    SDL_RWFromConstMem,
     SDL_RWFromFile,
+    SDL_RWops,
     SDL_SetHint,
     SDL_SetWindowFullscreen,
+    SDL_ShowSimpleMessageBox,
+    SDL_Surface,
     SDL_Texture,
 };
 
@@ -51,12 +64,16 @@ pub const SDL_GL_CONTEXT_PROFILE_ES: c_int = sdl::SDL_GLprofile::SDL_GL_CONTEXT_
 pub const SDL_HINT_RENDER_DRIVER: *const c_char = sdl::SDL_HINT_RENDER_DRIVER as *const u8 as *const c_char;
 pub const SDL_KEYDOWN: u32 = sdl::SDL_EventType::SDL_KEYDOWN as u32;
 pub const SDL_KEYUP: u32 = sdl::SDL_EventType::SDL_KEYUP as u32;
+pub const SDL_MESSAGEBOX_ERROR: u32 = sdl::SDL_MessageBoxFlags::SDL_MESSAGEBOX_ERROR as u32;
 pub const SDL_MOUSEBUTTONDOWN: u32 = sdl::SDL_EventType::SDL_MOUSEBUTTONDOWN as u32;
 pub const SDL_MOUSEBUTTONUP: u32 = sdl::SDL_EventType::SDL_MOUSEBUTTONUP as u32;
 pub const SDL_MOUSEMOTION: u32 = sdl::SDL_EventType::SDL_MOUSEMOTION as u32;
 pub const SDL_QUIT: u32 = sdl::SDL_EventType::SDL_QUIT as u32;
 pub const SDL_RENDERER_ACCELERATED: u32 = sdl::SDL_RendererFlags::SDL_RENDERER_ACCELERATED as u32;
 pub const SDL_RENDERER_PRESENTVSYNC: u32 = sdl::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32;
+pub const SDL_WINDOWEVENT: u32 = sdl::SDL_EventType::SDL_WINDOWEVENT as u32;
+pub const SDL_WINDOWEVENT_FOCUS_GAINED: u8 = sdl::SDL_WindowEventID::SDL_WINDOWEVENT_FOCUS_GAINED as u8;
+pub const SDL_WINDOWEVENT_FOCUS_LOST: u8 = sdl::SDL_WindowEventID::SDL_WINDOWEVENT_FOCUS_LOST as u8;
 pub const SDL_WINDOW_OPENGL: u32 = sdl::SDL_WindowFlags::SDL_WINDOW_OPENGL as u32;
 pub const SDL_WINDOW_RESIZABLE: u32 = sdl::SDL_WindowFlags::SDL_WINDOW_RESIZABLE as u32;
 pub const SDL_WINDOW_FULLSCREEN_DESKTOP: u32 = sdl::SDL_WindowFlags::SDL_WINDOW_FULLSCREEN_DESKTOP as u32;
@@ -110,21 +127,33 @@ pub const SDLK_DELETE: i32 = sdl::SDLK_DELETE as i32;
 pub use mix::{
     Mix_AllocateChannels,
     Mix_Chunk,
+    Mix_FreeChunk,
     Mix_FreeMusic,
+    Mix_GetChunk,
+    Mix_HaltChannel,
     Mix_Init,
-    Mix_LoadMUS,
+    Mix_LoadMUS_RW,
     Mix_LoadWAV_RW,
     Mix_Music,
     Mix_OpenAudio,
     Mix_PlayChannelTimed,
     Mix_PlayMusic,
+    Mix_Playing,
+    Mix_QuerySpec,
+    Mix_Quit,
+    Mix_ReserveChannels,
+    Mix_SetPostMix,
+    Mix_Volume,
+    Mix_VolumeMusic,
 };
 
 pub const MIX_DEFAULT_CHANNELS: c_int = mix::MIX_DEFAULT_CHANNELS as c_int;
 pub const MIX_DEFAULT_FORMAT: u16 = mix::MIX_DEFAULT_FORMAT as u16;
 pub const MIX_DEFAULT_FREQUENCY: c_int = mix::MIX_DEFAULT_FREQUENCY as c_int;
+pub const MIX_INIT_FLAC: c_int = mix::MIX_InitFlags_MIX_INIT_FLAC as c_int;
 pub const MIX_INIT_OGG: c_int = mix::MIX_InitFlags_MIX_INIT_OGG as c_int;
+pub const MIX_MAX_VOLUME: c_int = mix::MIX_MAX_VOLUME as c_int;
 
 pub use image::{
-    IMG_LoadTexture,
+    IMG_Load_RW,
 };
@@ -0,0 +1,78 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::io;
+use std::os::raw::c_void;
+
+use super::wasm_imports::*;
+use crate::save::{SaveError, SaveStorage};
+
+// return codes of the gateWasmStorage* imports
+const STORAGE_EMPTY: i32 = -1;
+const STORAGE_UNAVAILABLE: i32 = -2;
+const STORAGE_FAILED: i32 = -3;
+
+// stores each save slot as a base64 string in localStorage, keyed by the app id
+pub struct CoreStorage {
+    prefix: String,
+}
+
+impl CoreStorage {
+    pub(crate) fn new(app_id: &str) -> CoreStorage {
+        CoreStorage { prefix: format!("{}/", app_id) }
+    }
+
+    fn key(&self, slot: &str) -> String {
+        format!("{}{}", self.prefix, slot)
+    }
+}
+
+impl SaveStorage for CoreStorage {
+    fn read(&self, slot: &str) -> Result<Option<Vec<u8>>, SaveError> {
+        let key = self.key(slot);
+        let size = unsafe { gateWasmStorageRead(key.len(), key.as_ptr() as *const c_void) };
+        match size {
+            STORAGE_EMPTY => Ok(None),
+            size if size >= 0 => {
+                let mut data = vec![0; size as usize];
+                unsafe { gateWasmStorageFill(data.as_mut_ptr() as *mut c_void); }
+                Ok(Some(data))
+            },
+            code => Err(storage_error(code)),
+        }
+    }
+
+    fn write(&mut self, slot: &str, data: &[u8]) -> Result<(), SaveError> {
+        let key = self.key(slot);
+        let code = unsafe {
+            gateWasmStorageWrite(key.len(), key.as_ptr() as *const c_void, data.len(), data.as_ptr() as *const c_void)
+        };
+        if code == 0 { Ok(()) } else { Err(storage_error(code)) }
+    }
+
+    fn delete(&mut self, slot: &str) -> Result<(), SaveError> {
+        let key = self.key(slot);
+        let code = unsafe { gateWasmStorageDelete(key.len(), key.as_ptr() as *const c_void) };
+        if code == 0 { Ok(()) } else { Err(storage_error(code)) }
+    }
+}
+
+fn storage_error(code: i32) -> SaveError {
+    match code {
+        STORAGE_UNAVAILABLE => SaveError::Unavailable,
+        STORAGE_FAILED => SaveError::Io(io::Error::new(io::ErrorKind::Other, "browser storage operation failed")),
+        _ => panic!("unexpected storage result {}", code),
+    }
+}
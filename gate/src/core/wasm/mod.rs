@@ -14,6 +14,9 @@
 
 pub mod wasm_imports;
 pub mod wasm_exports;
+mod core_storage;
+
+pub use self::core_storage::CoreStorage;
 
 use std::collections::HashSet;
 use std::cell::{self, RefCell};
@@ -21,8 +24,8 @@ use std::mem;
 use std::io::Cursor;
 use std::os::raw::{c_int, c_void};
 
-use crate::{App, AppContext};
-use crate::asset_id::{AppAssetId, IdU16};
+use crate::{App, AppContext, Error};
+use crate::asset_id::{AppAssetId, AudioFormat, AudioId, IdU16};
 use crate::renderer::Renderer;
 use crate::app_info::AppInfo;
 use crate::input::KeyCode;
@@ -30,16 +33,38 @@ use crate::renderer::atlas::Atlas;
 use crate::renderer::render_buffer::RenderBuffer;
 use crate::renderer::core_renderer::CoreRenderer;
 use self::wasm_imports::*;
+use super::StreamSource;
 
-pub struct CoreAudio;
+pub struct CoreAudio {
+    enabled: bool,
+    stream: Option<StreamSource>,
+    stream_buffer: Vec<f32>,
+}
 
 impl CoreAudio {
-    pub fn play_sound(&mut self, id: u16) {
+    fn new(enabled: bool) -> CoreAudio {
+        CoreAudio { enabled, stream: None, stream_buffer: Vec::new() }
+    }
+
+    pub(crate) fn enabled(&self) -> bool { self.enabled }
+
+    pub fn play_sound(&mut self, id: u16, voice: usize) {
+        if !self.enabled { return; }
         unsafe {
-            gateWasmPlaySound(id as c_int);
+            gateWasmPlaySound(id as c_int, voice as c_int);
+        }
+    }
+    pub fn is_voice_playing(&self, voice: usize) -> bool {
+        self.enabled && unsafe { gateWasmIsVoicePlaying(voice as c_int) != 0 }
+    }
+    pub fn stop_voice(&mut self, voice: usize) {
+        if !self.enabled { return; }
+        unsafe {
+            gateWasmStopVoice(voice as c_int);
         }
     }
     pub fn play_music(&mut self, id: u16, loops: bool) {
+        if !self.enabled { return; }
         unsafe {
             if loops {
                 gateWasmLoopMusic(id as c_int);
@@ -49,10 +74,50 @@ impl CoreAudio {
         }
     }
     pub fn stop_music(&mut self) {
+        if !self.enabled { return; }
         unsafe {
             gateWasmStopMusic();
         }
     }
+    pub fn play_samples(&mut self, samples: &[f32], sample_rate: u32, channels: u16) {
+        if !self.enabled { return; }
+        unsafe {
+            gateWasmPlaySamples(samples.len(), samples.as_ptr() as *const c_void, sample_rate as c_int, channels as c_int);
+        }
+    }
+    pub fn open_stream(&mut self, source: StreamSource) {
+        if !self.enabled { return; }
+        self.stream = Some(source);
+        unsafe {
+            gateWasmOpenStream();
+        }
+    }
+    pub fn close_stream(&mut self) {
+        if self.stream.take().is_some() {
+            unsafe {
+                gateWasmCloseStream();
+            }
+        }
+    }
+    pub fn set_muted(&mut self, muted: bool) {
+        if !self.enabled { return; }
+        unsafe {
+            gateWasmSetMuted(if muted { 1 } else { 0 });
+        }
+    }
+    pub fn stream_sample_rate(&self) -> u32 {
+        // a common default, since no audio context is created when audio is disabled
+        if !self.enabled { return 44100; }
+        unsafe { gateWasmAudioSampleRate() as u32 }
+    }
+    fn fill_stream(&mut self, frame_count: usize) -> &mut Vec<f32> {
+        self.stream_buffer.clear();
+        self.stream_buffer.resize(2 * frame_count, 0.);
+        if let Some(stream) = self.stream.as_mut() {
+            stream(&mut self.stream_buffer);
+        }
+        &mut self.stream_buffer
+    }
 }
 
 trait TraitAppRunner {
@@ -63,8 +128,16 @@ trait TraitAppRunner {
     fn input(&mut self, key: KeyCode, down: bool) -> bool;
     fn music_count(&self) -> u16;
     fn sound_count(&self) -> u16;
+    fn music_format(&self, id: u16) -> AudioFormat;
+    fn sound_format(&self, id: u16) -> AudioFormat;
     fn on_restart(&mut self);
     fn cookie_buffer(&mut self, size: usize) -> &mut Vec<u8>;
+    fn fill_stream(&mut self, frame_count: usize) -> &mut Vec<f32>;
+    fn audio_unlocked(&mut self) -> bool;
+    fn focus_changed(&mut self, focused: bool);
+    fn has_loading_sprites(&self) -> bool;
+    fn set_load_progress(&mut self, progress: f64);
+    fn assets_loaded(&mut self);
 }
 
 struct StaticAppRunner { r: RefCell<Option<Box<dyn TraitAppRunner>>> }
@@ -135,6 +208,15 @@ impl<AS: AppAssetId, AP: App<AS>> AppRunner<AS, AP> {
         }
     }
 
+    fn read_sprite_atlas(&self) -> Atlas {
+        let mut atlas_buf: Vec<u8>;
+        unsafe {
+            atlas_buf = vec![0; gateWasmSpriteAtlasBinSize()];
+            gateWasmSpriteAtlasBinFill(mem::transmute(&mut atlas_buf[0]));
+        }
+        Atlas::new(Cursor::new(atlas_buf)).unwrap()
+    }
+
     fn update_cookie(&mut self) {
         if self.ctx.take_cookie_updated_flag() {
             let cookie = self.ctx.cookie_buffer();
@@ -147,20 +229,25 @@ impl<AS: AppAssetId, AP: App<AS>> TraitAppRunner for AppRunner<AS, AP> {
     fn init(&mut self) {
         assert!(self.renderer.is_none());
 
-        let mut atlas_buf: Vec<u8>;
-        unsafe {
-            atlas_buf = vec![0; gateWasmSpriteAtlasBinSize()];
-            gateWasmSpriteAtlasBinFill(mem::transmute(&mut atlas_buf[0]));
+        // JavaScript provides the loading atlas first if there is one, and the main atlas otherwise
+        let loading = self.has_loading_sprites();
+        let mut sprite_atlas = self.read_sprite_atlas();
+        if loading {
+            sprite_atlas = sprite_atlas.for_loading(AS::loading_sprites(), AS::Sprite::count()).unwrap();
+        } else {
+            self.ctx.set_assets_loaded();
         }
-        let sprite_atlas = Atlas::new(Cursor::new(atlas_buf)).unwrap();
+        let sprites_filter = sprite_atlas.filter(self.info.texture_filter);
 
         let render_buffer = RenderBuffer::new(&self.info, self.info.window_pixels, sprite_atlas);
-        let core_renderer = CoreRenderer::new();
+        let core_renderer = CoreRenderer::new(sprites_filter, self.info.texture_filter);
         self.renderer = Some(Renderer::<AS>::new(render_buffer, core_renderer));
 
         {
             let renderer = self.renderer.as_ref().unwrap();
             self.ctx.set_dims(renderer.app_dims(), renderer.native_px());
+            self.ctx.set_sprite_infos(renderer.sprite_infos().to_vec());
+            self.ctx.set_sprite_colliders(renderer.sprite_colliders().to_vec());
             self.app.init(&mut self.ctx);
         }
         self.update_cookie();
@@ -176,6 +263,7 @@ impl<AS: AppAssetId, AP: App<AS>> TraitAppRunner for AppRunner<AS, AP> {
     fn update_and_draw(&mut self, time_sec: f64) -> bool {
         self.update_is_fullscreen();
         let elapsed = self.last_time_sec.map(|x| time_sec - x).unwrap_or(0.0).max(0.0).min(0.1);
+        self.ctx.audio.advance(elapsed);
         if elapsed > 0.0 {
             self.app.unwrap().advance(elapsed.min(crate::MAX_TIMESTEP), &mut self.ctx);
         }
@@ -185,7 +273,7 @@ impl<AS: AppAssetId, AP: App<AS>> TraitAppRunner for AppRunner<AS, AP> {
         let close_requested = self.ctx.take_close_request();
         if !close_requested {
             self.app.unwrap().render(self.renderer.as_mut().unwrap(), &self.ctx);
-            self.renderer.as_mut().unwrap().flush();
+            self.renderer.as_mut().unwrap().end_frame();
         }
         !close_requested
     }
@@ -214,8 +302,11 @@ impl<AS: AppAssetId, AP: App<AS>> TraitAppRunner for AppRunner<AS, AP> {
         }
     }
 
-    fn music_count(&self) -> u16 { AS::Music::count() }
-    fn sound_count(&self) -> u16 { AS::Sound::count() }
+    // no audio is loaded when audio is disabled
+    fn music_count(&self) -> u16 { if self.info.audio_disabled { 0 } else { AS::Music::count() } }
+    fn sound_count(&self) -> u16 { if self.info.audio_disabled { 0 } else { AS::Sound::count() } }
+    fn music_format(&self, id: u16) -> AudioFormat { AS::Music::from_u16(id).unwrap().format() }
+    fn sound_format(&self, id: u16) -> AudioFormat { AS::Sound::from_u16(id).unwrap().format() }
 
     fn on_restart(&mut self) {
         self.update_is_fullscreen();
@@ -233,19 +324,59 @@ impl<AS: AppAssetId, AP: App<AS>> TraitAppRunner for AppRunner<AS, AP> {
         assert!(buffer.len() == size);
         buffer
     }
+
+    fn fill_stream(&mut self, frame_count: usize) -> &mut Vec<f32> {
+        self.ctx.audio.core.fill_stream(frame_count)
+    }
+
+    fn audio_unlocked(&mut self) -> bool {
+        if self.ctx.audio_available() || !self.ctx.audio_enabled() { return true; }
+        self.ctx.set_audio_available();
+        self.app.unwrap().audio_unlocked(&mut self.ctx);
+        self.update_cookie();
+        !self.ctx.take_close_request()
+    }
+
+    fn focus_changed(&mut self, focused: bool) {
+        if self.info.mute_on_unfocus {
+            self.ctx.audio.core.set_muted(!focused);
+        }
+    }
+
+    fn has_loading_sprites(&self) -> bool { !AS::loading_sprites().is_empty() }
+
+    fn set_load_progress(&mut self, progress: f64) {
+        if !self.ctx.assets_loaded() { self.ctx.set_load_progress(progress); }
+    }
+
+    fn assets_loaded(&mut self) {
+        assert!(!self.ctx.assets_loaded());
+        let sprite_atlas = self.read_sprite_atlas();
+        let renderer = self.renderer.as_mut().unwrap();
+        renderer.replace_sprites(sprite_atlas, self.info.texture_filter);
+        self.ctx.set_sprite_infos(renderer.sprite_infos().to_vec());
+        self.ctx.set_sprite_colliders(renderer.sprite_colliders().to_vec());
+        self.ctx.set_assets_loaded();
+    }
 }
 
-pub fn run<AS, AP, F>(info: AppInfo, app: F) where
+pub fn run<AS, AP, F>(mut info: AppInfo, app: F) -> Result<(), Error> where
     AS: 'static + AppAssetId,
     AP: 'static + App<AS>,
     F: 'static + FnOnce(&mut AppContext<AS>) -> AP
 {
+    let storage = info.take_save_storage();
+    let ctx = AppContext::new(CoreAudio::new(!info.audio_disabled), info.audio_channels, storage, (0., 0.), 1.);
     *APP_RUNNER.r.borrow_mut() = Some(Box::new(AppRunner {
         app: AppContainer::Uninit(Box::new(app)),
         info,
-        ctx: AppContext::new(CoreAudio { }, (0., 0.), 1.),
+        ctx,
         renderer: None,
         last_time_sec: None,
         held_keys: HashSet::new(),
     }));
+    Ok(())
 }
+
+// the app is started by JavaScript after `run` returns, so there are no startup errors to show
+pub fn show_error_message_box(_title: &str, _message: &str) {}
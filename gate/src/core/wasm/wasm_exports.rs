@@ -72,6 +72,14 @@ pub fn gateWasmSoundCount() -> c_int {
     app_runner_borrow().sound_count() as c_int
 }
 
+pub fn gateWasmMusicFormat(id: c_int) -> c_int {
+    app_runner_borrow().music_format(id as u16) as c_int
+}
+
+pub fn gateWasmSoundFormat(id: c_int) -> c_int {
+    app_runner_borrow().sound_format(id as u16) as c_int
+}
+
 pub fn gateWasmSpriteVertSrc() -> *const c_char {
     shaders::VS_SPRITE_SRC
 }
@@ -80,6 +88,18 @@ pub fn gateWasmSpriteFragSrc() -> *const c_char {
     shaders::FS_SPRITE_SRC
 }
 
+pub fn gateWasmMeshVertSrc() -> *const c_char {
+    shaders::VS_MESH_SRC
+}
+
+pub fn gateWasmMeshFragSrc() -> *const c_char {
+    shaders::FS_MESH_SRC
+}
+
+pub fn gateWasmTileVertSrc() -> *const c_char {
+    shaders::VS_TILE_SRC
+}
+
 pub fn gateWasmOnRestart() {
     app_runner_borrow_mut().on_restart();
 }
@@ -88,6 +108,31 @@ pub fn gateWasmCookieDataPtr(size: usize) -> *mut c_void {
     app_runner_borrow_mut().cookie_buffer(size).as_mut_ptr() as *mut c_void
 }
 
+pub fn gateWasmFillStream(frame_count: usize) -> *const f32 {
+    app_runner_borrow_mut().fill_stream(frame_count).as_ptr()
+}
+
+pub fn gateWasmAudioUnlocked() -> c_int {
+    let continuing = app_runner_borrow_mut().audio_unlocked();
+    if continuing { 1 } else { 0 }
+}
+
+pub fn gateWasmFocusEvent(focused: bool) {
+    app_runner_borrow_mut().focus_changed(focused);
+}
+
+pub fn gateWasmHasLoadingSprites() -> c_int {
+    if app_runner_borrow().has_loading_sprites() { 1 } else { 0 }
+}
+
+pub fn gateWasmLoadProgress(progress: f64) {
+    app_runner_borrow_mut().set_load_progress(progress);
+}
+
+pub fn gateWasmAssetsLoaded() {
+    app_runner_borrow_mut().assets_loaded();
+}
+
 /// Macro to be placed in the `main.rs` file for a Gate app.
 ///
 /// Currently, the only use this macro has is to export WASM functions for the app
@@ -121,18 +166,51 @@ macro_rules! gate_header {
             #[no_mangle] pub unsafe extern "C" fn gateWasmSoundCount() -> c_int {
                 ::gate::wasm_exports::gateWasmSoundCount()
             }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmMusicFormat(id: c_int) -> c_int {
+                ::gate::wasm_exports::gateWasmMusicFormat(id)
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmSoundFormat(id: c_int) -> c_int {
+                ::gate::wasm_exports::gateWasmSoundFormat(id)
+            }
             #[no_mangle] pub unsafe extern "C" fn gateWasmSpriteVertSrc() -> *const c_char {
                 ::gate::wasm_exports::gateWasmSpriteVertSrc()
             }
             #[no_mangle] pub unsafe extern "C" fn gateWasmSpriteFragSrc() -> *const c_char {
                 ::gate::wasm_exports::gateWasmSpriteFragSrc()
             }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmMeshVertSrc() -> *const c_char {
+                ::gate::wasm_exports::gateWasmMeshVertSrc()
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmMeshFragSrc() -> *const c_char {
+                ::gate::wasm_exports::gateWasmMeshFragSrc()
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmTileVertSrc() -> *const c_char {
+                ::gate::wasm_exports::gateWasmTileVertSrc()
+            }
             #[no_mangle] pub unsafe extern "C" fn gateWasmOnRestart() {
                 ::gate::wasm_exports::gateWasmOnRestart()
             }
             #[no_mangle] pub unsafe extern "C" fn gateWasmCookieDataPtr(size: usize) -> *mut c_void {
                 ::gate::wasm_exports::gateWasmCookieDataPtr(size)
             }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmFillStream(frame_count: usize) -> *const f32 {
+                ::gate::wasm_exports::gateWasmFillStream(frame_count)
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmAudioUnlocked() -> c_int {
+                ::gate::wasm_exports::gateWasmAudioUnlocked()
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmFocusEvent(focused: bool) {
+                ::gate::wasm_exports::gateWasmFocusEvent(focused)
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmHasLoadingSprites() -> c_int {
+                ::gate::wasm_exports::gateWasmHasLoadingSprites()
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmLoadProgress(progress: f64) {
+                ::gate::wasm_exports::gateWasmLoadProgress(progress)
+            }
+            #[no_mangle] pub unsafe extern "C" fn gateWasmAssetsLoaded() {
+                ::gate::wasm_exports::gateWasmAssetsLoaded()
+            }
         }
     };
 }
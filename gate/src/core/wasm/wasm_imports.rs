@@ -21,21 +21,43 @@ extern {
     pub fn gateWasmSetScissor(x: c_int, y: c_int, w: c_int, h: c_int);
 
     pub fn gateWasmClear(r: f32, g: f32, b: f32);
-    pub fn gateWasmDrawSprites(size: usize, data: *const c_void);
-
-    pub fn gateWasmPlaySound(id: c_int);
+    pub fn gateWasmDrawSprites(shader: c_int, blend: c_int, size: usize, data: *const c_void);
+    pub fn gateWasmDrawMesh(blend: c_int, size: usize, data: *const c_void);
+    pub fn gateWasmCreateTileChunk(size: usize, data: *const c_void) -> c_int;
+    pub fn gateWasmDeleteTileChunk(id: c_int);
+    pub fn gateWasmDrawTileChunk(id: c_int, blend: c_int, vertex_count: c_int, params: *const f32);
+    pub fn gateWasmDrawTargetSprites(id: c_int, shader: c_int, blend: c_int, size: usize, data: *const c_void);
+    pub fn gateWasmCreateTarget(w: c_int, h: c_int, filter: c_int) -> c_int;
+    pub fn gateWasmDeleteTarget(id: c_int);
+    pub fn gateWasmBindTarget(id: c_int, viewport_w: c_int, viewport_h: c_int);
+    pub fn gateWasmCreateShader(src_len: usize, src: *const c_void) -> c_int;
+    pub fn gateWasmSetUniform(shader: c_int, name_len: usize, name: *const c_void, kind: c_int,
+                              x: f32, y: f32, z: f32, w: f32);
+
+    pub fn gateWasmPlaySound(id: c_int, voice: c_int);
+    pub fn gateWasmIsVoicePlaying(voice: c_int) -> c_int;
+    pub fn gateWasmStopVoice(voice: c_int);
     pub fn gateWasmPlayMusic(id: c_int);
     pub fn gateWasmLoopMusic(id: c_int);
     pub fn gateWasmStopMusic();
+    pub fn gateWasmPlaySamples(len: usize, data: *const c_void, sample_rate: c_int, channels: c_int);
+    pub fn gateWasmOpenStream();
+    pub fn gateWasmCloseStream();
+    pub fn gateWasmAudioSampleRate() -> c_int;
+    pub fn gateWasmSetMuted(muted: c_int);
 
     pub fn gateWasmSpriteAtlasBinSize() -> usize;
     pub fn gateWasmSpriteAtlasBinFill(buffer: *mut c_void);
-    pub fn gateWasmTiledAtlasBinSize() -> usize;
-    pub fn gateWasmTiledAtlasBinFill(buffer: *mut c_void);
+    pub fn gateWasmSetSpriteFilter(filter: c_int);
 
     pub fn gateWasmRequestFullscreen();
     pub fn gateWasmCancelFullscreen();
     pub fn gateWasmIsFullscreen() -> c_int;
 
     pub fn gateWasmWriteCookie(size: usize, data: *const c_void);
+
+    pub fn gateWasmStorageRead(key_len: usize, key: *const c_void) -> c_int;
+    pub fn gateWasmStorageFill(buffer: *mut c_void);
+    pub fn gateWasmStorageWrite(key_len: usize, key: *const c_void, size: usize, data: *const c_void) -> c_int;
+    pub fn gateWasmStorageDelete(key_len: usize, key: *const c_void) -> c_int;
 }
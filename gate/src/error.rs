@@ -0,0 +1,47 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::error;
+use std::fmt;
+
+/// An error that prevents an app from starting, returned by `gate::try_run`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// SDL could not be initialized, with the error message from SDL.
+    Init(String),
+    /// The window could not be created, with the error message from SDL.
+    Window(String),
+    /// The graphics device does not support rendering with OpenGL ES 2.0, or failed to set it up.
+    Graphics(String),
+    /// A packed asset file is missing or invalid.
+    Asset {
+        /// Name of the asset file, e.g. "sprites.atlas".
+        file: String,
+        /// Description of the problem.
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Init(message) => write!(f, "failed to initialize: {}", message),
+            Error::Window(message) => write!(f, "failed to create window: {}", message),
+            Error::Graphics(message) => write!(f, "graphics are not supported: {}", message),
+            Error::Asset { file, message } => write!(f, "failed to load asset {:?}: {}", file, message),
+        }
+    }
+}
+
+impl error::Error for Error {}
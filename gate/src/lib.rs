@@ -32,6 +32,22 @@
 //! This will generate texture atlases and enums to reference assets.
 //! See the "gate_build" crate for more details.
 //!
+//! # Loading screens
+//!
+//! By default, all assets are loaded before the app is created.
+//! If `AssetPacker::loading_sprites` is invoked in "gate_build", then a small atlas of the given sprites
+//! is loaded first, and the app is created and rendered while the main sprite atlas and sounds load in the background.
+//! The app can draw its own loading screen with these sprites,
+//! using `AppContext::load_progress` and `AppContext::assets_loaded`.
+//!
+//! # Hot reloading
+//!
+//! When the "hot_reload" feature is enabled, a debug build running natively watches the sprite source files
+//! recorded by `AssetPacker::hot_reload` in "gate_build",
+//! re-packing the sprite atlas and swapping it in when they change, without recompiling.
+//! Existing sprite IDs keep drawing their updated images,
+//! but sprites that are added or removed require a rebuild, and a warning is printed when that happens.
+//!
 //! # Example usage
 //!
 //! For a full example, see <https://github.com/SergiusIW/gate/tree/master/example>.
@@ -44,7 +60,6 @@
 //! * Loading assets on the fly
 //! * Adding XBox controller input
 //! * Generating enums/handles for user-specific assets, and loading those assets
-//! * Handling game save data (currently only cookies in WebAssembly mode, nothing on desktop)
 //! * Playing looping music that has a one-time intro, without any hiccups in the music
 //!   (not sure how I'm going to do this, but it's important to me;
 //!   game libraries often seem to overlook this fundamental feature)
@@ -54,11 +69,16 @@
 
 #[macro_use] mod macros;
 pub mod asset_id;
+pub mod collision;
 pub mod renderer;
+pub mod save;
+pub mod tiled;
 mod app_info;
 mod app_context;
+mod error;
 mod input;
 mod core;
+mod voices;
 
 #[cfg(target_arch = "wasm32")]
 pub use crate::core::{wasm_imports, wasm_exports};
@@ -66,6 +86,7 @@ pub use crate::core::{wasm_imports, wasm_exports};
 pub use crate::app_context::{AppContext, Audio};
 pub use crate::input::KeyCode;
 pub use crate::app_info::AppInfo;
+pub use crate::error::Error;
 
 use crate::asset_id::AppAssetId;
 use crate::renderer::Renderer;
@@ -76,17 +97,41 @@ const MAX_TIMESTEP: f64 = 1. / 15.;
 ///
 /// Will panic if this method is called more than once.
 /// The `AppInfo` is used to specify intiailization parameters for the application.
+/// Panics if the app fails to start, see `try_run` to handle these errors instead.
 pub fn run<AS, AP, F>(info: AppInfo, app: F) where
     AS: 'static + AppAssetId,
     AP: 'static + App<AS>,
     F: 'static + FnOnce(&mut AppContext<AS>) -> AP
+{
+    if let Err(err) = try_run(info, app) {
+        panic!("{}", err);
+    }
+}
+
+/// Same as `run`, but returns an `Error` if the app fails to start,
+/// e.g. if the assets are missing or the graphics device is not supported.
+///
+/// If `AppInfo::error_message_box` was invoked, then the error is also shown to the user in a message box.
+/// If the app has a loading screen, assets that fail to load after the app has started
+/// also cause this method to return an error.
+/// When compiling to `wasm32-unknown-unknown`, the app starts after this method returns,
+/// so startup errors cause a panic instead.
+pub fn try_run<AS, AP, F>(info: AppInfo, app: F) -> Result<(), Error> where
+    AS: 'static + AppAssetId,
+    AP: 'static + App<AS>,
+    F: 'static + FnOnce(&mut AppContext<AS>) -> AP
 {
     use std::sync::atomic::{AtomicBool, Ordering};
     static APP_CREATED: AtomicBool = AtomicBool::new(false);
     let previously_created = APP_CREATED.swap(true, Ordering::Relaxed);
     assert!(!previously_created, "Cannot run more than one App.");
 
-    core::run(info, app);
+    let (title, error_message_box) = (info.title, info.error_message_box);
+    let result = core::run(info, app);
+    if let Err(err) = &result {
+        if error_message_box { core::show_error_message_box(title, &err.to_string()); }
+    }
+    result
 }
 
 /// Trait that a user can implement to specify application behavior, passed into `gate::run(...)`.
@@ -100,6 +145,14 @@ pub trait App<A: AppAssetId> {
     /// Invoked when a key or mouse button is released, default behavior is a no-op.
     fn key_up(&mut self, _key: KeyCode, _ctx: &mut AppContext<A>) {}
 
+    /// Invoked once audio becomes available, default behavior is a no-op.
+    ///
+    /// In a web browser this happens after the user first interacts with the page.
+    /// Natively, this is invoked right after the app is created.
+    /// Audio played before this point may not be heard.
+    /// Never invoked if audio is not enabled, see `AppContext::audio_enabled`.
+    fn audio_unlocked(&mut self, _ctx: &mut AppContext<A>) {}
+
     /// Render the app in its current state.
     fn render(&mut self, renderer: &mut Renderer<A>, ctx: &AppContext<A>);
 }
@@ -17,12 +17,24 @@ use std::collections::HashMap;
 
 use byteorder::BigEndian;
 
+use crate::collision::Collider;
+use super::renderer::{SpriteInfo, TextureFilter};
+
 const PAD: u16 = 1;
 
+const EMPTY_IMAGE: ImageCoords = ImageCoords { lt: (0., 0.), rb: (0., 0.), anchor: (0., 0.) };
+
+#[cfg(all(feature = "hot_reload", debug_assertions, not(target_arch = "wasm32")))]
+const EMPTY_SLICE: NineSlice = NineSlice { lt: (0., 0.), rb: (0., 0.), border: 0. };
+
 pub struct Atlas {
     #[allow(dead_code)] // dims might not used when targeting wasm
-    pub(super) dims: (f32, f32),
+    pub(crate) dims: (f32, f32),
     pub(super) images: HashMap<u16, ImageCoords>,
+    pub(super) slices: HashMap<u16, NineSlice>,
+    pub(crate) infos: Vec<SpriteInfo>,
+    pub(crate) colliders: Vec<Collider>, // empty if collision data was not generated
+    filter: Option<TextureFilter>,
 }
 
 impl Atlas {
@@ -31,18 +43,126 @@ impl Atlas {
 
         let dims = (input.read_u16::<BigEndian>()? as f32, input.read_u16::<BigEndian>()? as f32);
         let handle_count = input.read_u16::<BigEndian>()?;
+        let filter = match input.read_u8()? {
+            0 => None,
+            value => Some(TextureFilter::from_u8(value - 1).ok_or(io::ErrorKind::InvalidData)?),
+        };
 
         let mut images = HashMap::with_capacity(handle_count as usize);
+        let mut slices = HashMap::new();
+        let mut infos = Vec::with_capacity(handle_count as usize);
         for id in 0..handle_count {
             let image = ImageCoords {
                 lt: ((input.read_u16::<BigEndian>()? - PAD) as f32, (input.read_u16::<BigEndian>()? - PAD) as f32),
                 rb: ((input.read_u16::<BigEndian>()? + PAD) as f32, (input.read_u16::<BigEndian>()? + PAD) as f32),
                 anchor: (0.5 * input.read_i16::<BigEndian>()? as f32, 0.5 * input.read_i16::<BigEndian>()? as f32),
             };
+            let border = input.read_u16::<BigEndian>()?;
+            if border > 0 {
+                let pad = PAD as f32;
+                slices.insert(id, NineSlice {
+                    lt: (image.lt.0 + pad, image.lt.1 + pad),
+                    rb: (image.rb.0 - pad, image.rb.1 - pad),
+                    border: border as f32,
+                });
+            }
+            let raw_dims = (input.read_u16::<BigEndian>()? as f32, input.read_u16::<BigEndian>()? as f32);
+            let trim = (input.read_u16::<BigEndian>()? as f32, input.read_u16::<BigEndian>()? as f32);
+            infos.push(sprite_info(&image, raw_dims, trim));
             images.insert(id, image);
         }
 
-        Ok(Atlas { dims, images })
+        let mut colliders = Vec::new();
+        let collision_cell_size = input.read_u8()?;
+        if collision_cell_size > 0 {
+            for info in &infos {
+                colliders.push(Collider::read(&mut input, info, collision_cell_size)?);
+            }
+        }
+
+        Ok(Atlas { dims, images, slices, infos, colliders, filter })
+    }
+
+    pub fn filter(&self, default: TextureFilter) -> TextureFilter {
+        self.filter.unwrap_or(default)
+    }
+
+    // true for sprites that are drawn as empty, such as sprites outside of a loading atlas
+    pub(super) fn is_empty(&self, sprite_id: u16) -> bool {
+        let image = &self.images[&sprite_id];
+        image.lt == image.rb
+    }
+
+    // re-keys a loading atlas by the sprite IDs it was packed for (see `AppAssetId::loading_sprites`),
+    // drawing the other sprites (including nine-slice sprites) as empty with zero-sized sprite info
+    pub fn for_loading(self, ids: &[u16], sprite_count: u16) -> io::Result<Atlas> {
+        if ids.len() != self.infos.len() || ids.iter().any(|&id| id >= sprite_count) {
+            let message = "loading atlas does not match the app's loading sprites";
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+        let empty_info = SpriteInfo { dims: (0., 0.), anchor: (0., 0.), trimmed_min: (0., 0.), trimmed_max: (0., 0.) };
+        let mut images: HashMap<_, _> = (0..sprite_count).map(|id| (id, EMPTY_IMAGE)).collect();
+        let mut slices = HashMap::new();
+        let mut infos = vec![empty_info; sprite_count as usize];
+        for (index, &id) in ids.iter().enumerate() {
+            let index = index as u16;
+            images.insert(id, self.images[&index]);
+            if let Some(&slice) = self.slices.get(&index) { slices.insert(id, slice); }
+            infos[id as usize] = self.infos[index as usize];
+        }
+        Ok(Atlas { dims: self.dims, images, slices, infos, colliders: Vec::new(), filter: self.filter })
+    }
+
+    // re-keys a re-packed atlas by the sprite IDs of the `old` atlas, where `ids` maps each old ID to a new ID,
+    // keeping the old sprite info for sprites that were removed and drawing them as empty
+    // (including removed nine-slice sprites)
+    #[cfg(all(feature = "hot_reload", debug_assertions, not(target_arch = "wasm32")))]
+    pub fn remapped(mut self, ids: &[Option<u16>], old: &Atlas) -> Atlas {
+        let mut images = HashMap::with_capacity(ids.len());
+        let mut slices = HashMap::new();
+        let mut infos = Vec::with_capacity(ids.len());
+        let mut colliders = Vec::new();
+        for (old_id, &id) in ids.iter().enumerate() {
+            let old_id = old_id as u16;
+            match id {
+                Some(id) => {
+                    images.insert(old_id, self.images[&id]);
+                    if let Some(&slice) = self.slices.get(&id) { slices.insert(old_id, slice); }
+                    infos.push(self.infos[id as usize]);
+                },
+                None => {
+                    images.insert(old_id, EMPTY_IMAGE);
+                    if old.slices.contains_key(&old_id) { slices.insert(old_id, EMPTY_SLICE); }
+                    infos.push(old.infos[old_id as usize]);
+                },
+            }
+            if !self.colliders.is_empty() {
+                let collider = match id {
+                    Some(id) => self.colliders[id as usize].clone(),
+                    None => old.colliders[old_id as usize].clone(),
+                };
+                colliders.push(collider);
+            }
+        }
+        self.images = images;
+        self.slices = slices;
+        self.infos = infos;
+        self.colliders = colliders;
+        self
+    }
+}
+
+// trim is the offset of the trimmed image from the top-left of the untrimmed image
+fn sprite_info(image: &ImageCoords, raw_dims: (f32, f32), trim: (f32, f32)) -> SpriteInfo {
+    let pad = PAD as f32;
+    let (left, top) = (image.lt.0 + pad, image.lt.1 + pad);
+    let (right, bottom) = (image.rb.0 - pad, image.rb.1 - pad);
+    let anchor = image.anchor;
+    SpriteInfo {
+        dims: (raw_dims.0 as f64, raw_dims.1 as f64),
+        anchor: ((anchor.0 - left + trim.0) as f64, (anchor.1 - top + trim.1) as f64),
+        trimmed_min: ((left - anchor.0) as f64, (anchor.1 - bottom) as f64),
+        trimmed_max: ((right - anchor.0) as f64, (anchor.1 - top) as f64),
     }
 }
 
@@ -53,3 +173,16 @@ pub(super) struct ImageCoords {
     pub rb: (f32, f32), // right, bottom
     pub anchor: (f32, f32), // anchor X, Y
 }
+
+// an untrimmed sprite that is divided into nine parts by its border, without padding
+#[derive(Copy, Clone)]
+pub(super) struct NineSlice {
+    pub lt: (f32, f32),
+    pub rb: (f32, f32),
+    pub border: f32,
+}
+
+impl NineSlice {
+    // true for the placeholder of a nine-slice sprite that is drawn as empty
+    pub fn is_empty(&self) -> bool { self.lt == self.rb }
+}
@@ -0,0 +1,210 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::AppContext;
+use crate::asset_id::AppAssetId;
+
+use super::geom::Affine;
+
+/// A camera for viewing a 2D world that is larger than the screen.
+///
+/// The camera is centered on a world position, and may be zoomed, rotated, shaken,
+/// and kept within world bounds.
+/// Pre-multiply the transformations of things drawn in the world with `self.affine(ctx)`.
+///
+/// # Example
+///
+/// ```rust
+/// use gate::renderer::Camera2D;
+///
+/// let mut camera = Camera2D::new(100., 40.);
+/// camera.set_zoom(2.);
+/// camera.set_bounds(0., 0., 640., 360.);
+/// camera.shake(3., 0.25);
+/// ```
+#[derive(Clone)]
+pub struct Camera2D {
+    pos: (f64, f64),
+    zoom: f64,
+    rotation: f64,
+    bounds: Option<((f64, f64), (f64, f64))>,
+    shake: Shake,
+}
+
+#[derive(Clone)]
+struct Shake {
+    magnitude: f64,
+    duration: f64,
+    remaining: f64,
+    offset: (f64, f64),
+    rng_state: u32,
+}
+
+impl Camera2D {
+    /// Returns a new camera centered on the world position `(x, y)`, with no zoom or rotation.
+    pub fn new(x: f64, y: f64) -> Camera2D {
+        Camera2D {
+            pos: (x, y),
+            zoom: 1.,
+            rotation: 0.,
+            bounds: None,
+            shake: Shake { magnitude: 0., duration: 0., remaining: 0., offset: (0., 0.), rng_state: 0x9E37_79B9 },
+        }
+    }
+
+    /// Returns the world position that the camera is centered on, before clamping or shaking.
+    pub fn pos(&self) -> (f64, f64) { self.pos }
+
+    /// Centers the camera on the world position `(x, y)`.
+    pub fn set_pos(&mut self, x: f64, y: f64) { self.pos = (x, y); }
+
+    /// Returns the zoom factor (default is `1.0`).
+    pub fn zoom(&self) -> f64 { self.zoom }
+
+    /// Sets the zoom factor, where values greater than `1.0` make the world appear larger.
+    ///
+    /// Integer zoom factors keep pixel art crisp.
+    pub fn set_zoom(&mut self, zoom: f64) {
+        assert!(zoom > 0. && zoom.is_finite(), "invalid zoom {}", zoom);
+        self.zoom = zoom;
+    }
+
+    /// Returns the rotation in radians (default is `0.0`).
+    pub fn rotation(&self) -> f64 { self.rotation }
+
+    /// Rotates the camera counter-clockwise by `angle` radians,
+    /// so the world appears to rotate clockwise.
+    pub fn set_rotation(&mut self, angle: f64) { self.rotation = angle; }
+
+    /// Restricts the camera so that it only views the world rectangle within the given bounds.
+    ///
+    /// If the bounds are smaller than the visible area along an axis,
+    /// the camera is centered on the bounds along that axis.
+    pub fn set_bounds(&mut self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) {
+        assert!(min_x <= max_x && min_y <= max_y, "invalid camera bounds");
+        self.bounds = Some(((min_x, min_y), (max_x, max_y)));
+    }
+
+    /// Removes the bounds set by `set_bounds`.
+    pub fn clear_bounds(&mut self) { self.bounds = None; }
+
+    /// Starts shaking the camera by up to `magnitude` world units, fading out over `seconds`.
+    ///
+    /// Replaces the current shake, unless the current shake is stronger.
+    pub fn shake(&mut self, magnitude: f64, seconds: f64) {
+        assert!(magnitude >= 0. && seconds > 0., "invalid camera shake");
+        let shake = &mut self.shake;
+        if magnitude >= shake.magnitude * shake.remaining / shake.duration.max(1e-9) {
+            shake.magnitude = magnitude;
+            shake.duration = seconds;
+            shake.remaining = seconds;
+        }
+    }
+
+    /// Advances the camera shake by the given amount of `seconds`.
+    ///
+    /// Typically invoked from `App::advance`.
+    pub fn advance(&mut self, seconds: f64) {
+        let shake = &mut self.shake;
+        shake.remaining = (shake.remaining - seconds).max(0.);
+        if shake.remaining > 0. {
+            let strength = shake.magnitude * shake.remaining / shake.duration;
+            shake.offset = (strength * shake.next_random(), strength * shake.next_random());
+        } else {
+            shake.offset = (0., 0.);
+        }
+    }
+
+    /// Returns the transformation from world coordinates to app coordinates.
+    ///
+    /// When the camera is not rotated, the transformation is aligned to native pixels
+    /// (see `AppContext::native_px_align`), so pixel art stays crisp while panning.
+    pub fn affine<A: AppAssetId>(&self, ctx: &AppContext<A>) -> Affine {
+        let (x, y) = self.view_center(ctx);
+        let dims = ctx.dims();
+        Affine::translate(0.5 * dims.0, 0.5 * dims.1)
+            .pre_rotate(-self.rotation)
+            .pre_scale(self.zoom)
+            .pre_translate(-x, -y)
+    }
+
+    /// Converts a position in app coordinates, such as `AppContext::cursor()`, into world coordinates.
+    pub fn to_world<A: AppAssetId>(&self, ctx: &AppContext<A>, pos: (f64, f64)) -> (f64, f64) {
+        let (x, y) = self.view_center(ctx);
+        let dims = ctx.dims();
+        let offset = ((pos.0 - 0.5 * dims.0) / self.zoom, (pos.1 - 0.5 * dims.1) / self.zoom);
+        let (sin, cos) = self.rotation.sin_cos();
+        (x + cos * offset.0 - sin * offset.1, y + sin * offset.0 + cos * offset.1)
+    }
+
+    /// Returns the mouse cursor position in world coordinates.
+    pub fn cursor<A: AppAssetId>(&self, ctx: &AppContext<A>) -> (f64, f64) {
+        self.to_world(ctx, ctx.cursor())
+    }
+
+    /// Returns the world rectangle `(min_x, min_y, max_x, max_y)` containing everything visible,
+    /// useful for culling.
+    pub fn visible_rect<A: AppAssetId>(&self, ctx: &AppContext<A>) -> (f64, f64, f64, f64) {
+        let (x, y) = self.view_center(ctx);
+        let (half_w, half_h) = self.half_extents(ctx.dims());
+        (x - half_w, y - half_h, x + half_w, y + half_h)
+    }
+
+    // half width and height of the axis-aligned world rectangle containing the view
+    fn half_extents(&self, dims: (f64, f64)) -> (f64, f64) {
+        let (half_w, half_h) = (0.5 * dims.0 / self.zoom, 0.5 * dims.1 / self.zoom);
+        let (sin, cos) = (self.rotation.sin().abs(), self.rotation.cos().abs());
+        (cos * half_w + sin * half_h, sin * half_w + cos * half_h)
+    }
+
+    // world position at the center of the screen, after clamping, shaking, and pixel alignment
+    fn view_center<A: AppAssetId>(&self, ctx: &AppContext<A>) -> (f64, f64) {
+        let dims = ctx.dims();
+        let (mut x, mut y) = self.pos;
+        if let Some((min, max)) = self.bounds {
+            let (half_w, half_h) = self.half_extents(dims);
+            x = clamp_axis(x, min.0, max.0, half_w);
+            y = clamp_axis(y, min.1, max.1, half_h);
+        }
+        x += self.shake.offset.0;
+        y += self.shake.offset.1;
+        if self.rotation == 0. {
+            let offset = ctx.native_px_align(0.5 * dims.0 - x * self.zoom, 0.5 * dims.1 - y * self.zoom);
+            x = (0.5 * dims.0 - offset.0) / self.zoom;
+            y = (0.5 * dims.1 - offset.1) / self.zoom;
+        }
+        (x, y)
+    }
+}
+
+fn clamp_axis(pos: f64, min: f64, max: f64, half_extent: f64) -> f64 {
+    if max - min <= 2. * half_extent {
+        0.5 * (min + max)
+    } else {
+        pos.max(min + half_extent).min(max - half_extent)
+    }
+}
+
+impl Shake {
+    // xorshift random number in the range -1 to 1
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+        x as f64 / u32::MAX as f64 * 2. - 1.
+    }
+}
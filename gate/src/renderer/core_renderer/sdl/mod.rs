@@ -15,7 +15,10 @@
 mod shader_util;
 mod sprite_program;
 
-use std::mem;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::{mem, ptr};
 
 use crate::core::sdl_helpers::*;
 use crate::core::sdl_imports::*;
@@ -23,23 +26,83 @@ use crate::core::sdl_imports::*;
 use gl::types::*;
 use gl;
 
-use crate::renderer::render_buffer::RenderBuffer;
+use crate::renderer::{BlendMode, TextureFilter, Uniform};
+use crate::renderer::render_buffer::{Mode, RenderBuffer};
+use crate::renderer::shaders;
 use self::sprite_program::SpriteProgram;
 
 pub struct CoreRenderer {
     vbo: GLuint,
     sprite_program: SpriteProgram,
+    mesh_program: SpriteProgram,
+    tile_program: SpriteProgram,
+    tile_uniforms: TileUniforms,
+    tile_chunks: Vec<Option<TileChunk>>,
     sprites_tex: *mut SDL_Texture, // TODO invoke sys::SDL_DestroyTexture on panic? does it matter?
+    screen_fbo: GLuint,
+    bound_fbo: GLuint,
+    targets: Vec<Option<Target>>,
+    shaders: Vec<CustomShader>,
+    target_filter: TextureFilter,
+}
+
+struct CustomShader {
+    program: SpriteProgram,
+    uniforms: HashMap<String, GLint>,
+}
+
+struct TileUniforms {
+    transform_row_0: GLint,
+    transform_row_1: GLint,
+    native_scale: GLint,
+}
+
+struct TileChunk {
+    buffer: GLuint,
+    vao: GLuint,
+}
+
+struct Target {
+    fbo: GLuint,
+    tex: GLuint,
+    dims: (u32, u32),
 }
 
 impl CoreRenderer {
-    pub fn new(sprites_tex: *mut SDL_Texture) -> CoreRenderer {
+    // returns an error message if the shaders fail to compile
+    pub fn new(sprites_tex: *mut SDL_Texture, target_filter: TextureFilter) -> Result<CoreRenderer, String> {
         let mut vbo = 0;
+        let mut screen_fbo = 0;
         unsafe {
             gl::GenBuffers(1, &mut vbo);
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+            // the screen framebuffer is not necessarily 0 on all platforms
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut screen_fbo);
         }
-        CoreRenderer { vbo, sprites_tex, sprite_program: SpriteProgram::new() }
+        let screen_fbo = screen_fbo as GLuint;
+        let tile_program = SpriteProgram::tile()?;
+        let tile_uniforms = unsafe {
+            TileUniforms {
+                transform_row_0: gl::GetUniformLocation(tile_program.handle, c_str!("transform_row_0")),
+                transform_row_1: gl::GetUniformLocation(tile_program.handle, c_str!("transform_row_1")),
+                native_scale: gl::GetUniformLocation(tile_program.handle, c_str!("native_scale")),
+            }
+        };
+        Ok(CoreRenderer {
+            vbo, sprites_tex, sprite_program: SpriteProgram::new()?, mesh_program: SpriteProgram::mesh()?,
+            tile_program, tile_uniforms, tile_chunks: Vec::new(),
+            screen_fbo, bound_fbo: screen_fbo, targets: Vec::new(), shaders: Vec::new(),
+            target_filter: target_filter.for_target(),
+        })
+    }
+
+    pub(in crate::renderer) fn replace_sprites_tex(&mut self, sprites_tex: *mut SDL_Texture) {
+        unsafe { SDL_DestroyTexture(self.sprites_tex); }
+        self.sprites_tex = sprites_tex;
+    }
+
+    fn target(&self, id: usize) -> &Target {
+        self.targets[id].as_ref().expect("render target was deleted")
     }
 }
 
@@ -59,41 +122,234 @@ impl CoreRenderer {
         }
     }
 
-    pub(in crate::renderer) fn draw_sprites(&mut self, r: &mut RenderBuffer) {
+    pub(in crate::renderer) fn create_target(&mut self, dims: (u32, u32)) -> usize {
+        let mut target = Target { fbo: 0, tex: 0, dims };
+        unsafe {
+            gl::GenTextures(1, &mut target.tex);
+            gl::BindTexture(gl::TEXTURE_2D, target.tex);
+            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as GLint, dims.0 as GLsizei, dims.1 as GLsizei, 0,
+                           gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
+            set_texture_filter(self.target_filter, dims);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+
+            gl::GenFramebuffers(1, &mut target.fbo);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, target.fbo);
+            gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, target.tex, 0);
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            assert!(status == gl::FRAMEBUFFER_COMPLETE, "incomplete render target framebuffer, status {}", status);
+            gl::ClearColor(0., 0., 0., 0.);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.bound_fbo);
+        }
+        match self.targets.iter().position(|t| t.is_none()) {
+            Some(id) => { self.targets[id] = Some(target); id },
+            None => { self.targets.push(Some(target)); self.targets.len() - 1 },
+        }
+    }
+
+    pub(in crate::renderer) fn delete_target(&mut self, id: usize) {
+        let target = self.targets[id].take().expect("render target was deleted");
         unsafe {
+            gl::DeleteFramebuffers(1, &target.fbo);
+            gl::DeleteTextures(1, &target.tex);
+        }
+    }
+
+    pub(in crate::renderer) fn create_tile_chunk(&mut self, vertices: &[f32]) -> usize {
+        let mut chunk = TileChunk { buffer: 0, vao: 0 };
+        unsafe {
+            gl::GenBuffers(1, &mut chunk.buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, chunk.buffer);
+            gl::BufferData(gl::ARRAY_BUFFER,
+                           mem::size_of_val(vertices) as GLsizeiptr,
+                           vertices.as_ptr() as *const _,
+                           gl::STATIC_DRAW);
+            chunk.vao = self.tile_program.make_vao();
             gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
-            gl::Enable(gl::SCISSOR_TEST);
-            gl::UseProgram(self.sprite_program.handle);
+        }
+        match self.tile_chunks.iter().position(|c| c.is_none()) {
+            Some(id) => { self.tile_chunks[id] = Some(chunk); id },
+            None => { self.tile_chunks.push(Some(chunk)); self.tile_chunks.len() - 1 },
+        }
+    }
+
+    pub(in crate::renderer) fn delete_tile_chunk(&mut self, id: usize) {
+        let chunk = self.tile_chunks[id].take().expect("tile chunk was deleted");
+        unsafe {
+            gl::DeleteVertexArrays(1, &chunk.vao);
+            gl::DeleteBuffers(1, &chunk.buffer);
+        }
+    }
 
+    pub(in crate::renderer) fn draw_tile_chunk(&mut self, r: &RenderBuffer, id: usize, vertex_count: usize,
+                                               blend: BlendMode, transform: &[f32; 6], native_scale: (f32, f32)) {
+        let chunk = self.tile_chunks[id].as_ref().expect("tile chunk was deleted");
+        let program = &self.tile_program;
+        let uniforms = &self.tile_uniforms;
+        unsafe {
             gl::ActiveTexture(gl::TEXTURE0);
             let (mut width, mut height) = (0., 0.);
             SDL_GL_BindTexture(self.sprites_tex, &mut width, &mut height).sdl_check();
-            gl::Uniform1i(self.sprite_program.uniform_tex, 0); // binds to GL_TEXTURE0
-            gl::Uniform2f(self.sprite_program.uniform_inv_tex_dims,
-                          1. / r.sprite_atlas.dims.0, 1. / r.sprite_atlas.dims.1);
-
-            gl::BindVertexArray(self.sprite_program.vao);
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::UseProgram(program.handle);
+            let (src_factor, dst_factor) = blend_factors(blend);
+            gl::BlendFunc(src_factor, dst_factor);
 
-            gl::BufferData(gl::ARRAY_BUFFER,
-                           (mem::size_of::<GLfloat>() * r.vbo_data.len()) as GLsizeiptr,
-                           mem::transmute(&r.vbo_data[0]),
-                           gl::STREAM_DRAW);
+            gl::Uniform1i(program.uniform_tex, 0); // binds to GL_TEXTURE0
+            gl::Uniform2f(program.uniform_inv_tex_dims, 1. / r.sprite_atlas.dims.0, 1. / r.sprite_atlas.dims.1);
+            gl::Uniform3f(uniforms.transform_row_0, transform[0], transform[1], transform[2]);
+            gl::Uniform3f(uniforms.transform_row_1, transform[3], transform[4], transform[5]);
+            gl::Uniform2f(uniforms.native_scale, native_scale.0, native_scale.1);
 
-            gl::DrawArrays(gl::TRIANGLES, 0, r.vbo_data.len() as GLint / 7);
+            gl::BindVertexArray(chunk.vao);
+            gl::DrawArrays(gl::TRIANGLES, 0, vertex_count as GLint);
 
             gl::BindVertexArray(0);
-            SDL_GL_UnbindTexture(self.sprites_tex).sdl_check();
             gl::UseProgram(0);
             gl::Disable(gl::SCISSOR_TEST);
+            SDL_GL_UnbindTexture(self.sprites_tex).sdl_check();
         }
+    }
+
+    pub(in crate::renderer) fn bind_target(&mut self, id: Option<usize>, viewport_dims: (u32, u32)) {
+        self.bound_fbo = id.map(|id| self.target(id).fbo).unwrap_or(self.screen_fbo);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.bound_fbo);
+            gl::Viewport(0, 0, viewport_dims.0 as GLsizei, viewport_dims.1 as GLsizei);
+        }
+    }
+
+    pub(in crate::renderer) fn create_shader(&mut self, fragment_src: &str) -> usize {
+        let src = CString::new(format!("{}\n{}", shaders::FS_CUSTOM_PRELUDE, fragment_src)).expect("invalid shader source");
+        let program = SpriteProgram::with_fragment_shader(src.as_ptr())
+            .unwrap_or_else(|message| panic!("{}", message));
+        self.shaders.push(CustomShader { program, uniforms: HashMap::new() });
+        self.shaders.len() - 1
+    }
+
+    pub(in crate::renderer) fn set_uniform(&mut self, shader: usize, name: &str, value: Uniform) {
+        let shader = &mut self.shaders[shader];
+        let handle = shader.program.handle;
+        let location = *shader.uniforms.entry(name.to_owned()).or_insert_with(|| {
+            let name = CString::new(name).expect("invalid uniform name");
+            unsafe { gl::GetUniformLocation(handle, name.as_ptr()) }
+        });
+        if location < 0 { return; }
+        unsafe {
+            gl::UseProgram(handle);
+            match value {
+                Uniform::Float(x) => gl::Uniform1f(location, x),
+                Uniform::Vec2(x, y) => gl::Uniform2f(location, x, y),
+                Uniform::Vec3(x, y, z) => gl::Uniform3f(location, x, y, z),
+                Uniform::Vec4(x, y, z, w) => gl::Uniform4f(location, x, y, z, w),
+                Uniform::Int(x) => gl::Uniform1i(location, x),
+            }
+            gl::UseProgram(0);
+        }
+    }
+
+    pub(in crate::renderer) fn draw_sprites(&mut self, r: &mut RenderBuffer) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            let (mut width, mut height) = (0., 0.);
+            SDL_GL_BindTexture(self.sprites_tex, &mut width, &mut height).sdl_check();
+            let tex_dims = r.sprite_atlas.dims;
+            self.draw_textured(r, tex_dims);
+            SDL_GL_UnbindTexture(self.sprites_tex).sdl_check();
+        }
+    }
+
+    pub(in crate::renderer) fn draw_mesh(&mut self, r: &mut RenderBuffer) {
+        // the mesh program uses the same texture as sprites
+        self.draw_sprites(r);
+    }
+
+    pub(in crate::renderer) fn draw_target(&mut self, r: &mut RenderBuffer, id: usize) {
+        let target = self.target(id);
+        let (tex, dims) = (target.tex, (target.dims.0 as f32, target.dims.1 as f32));
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, tex);
+            self.draw_textured(r, dims);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+        }
+    }
+
+    // draws sprite vertices sampling from the texture bound to GL_TEXTURE0
+    unsafe fn draw_textured(&mut self, r: &mut RenderBuffer, tex_dims: (f32, f32)) {
+        let program = match (r.mode, r.style.shader) {
+            (Mode::Mesh, _) => &self.mesh_program,
+            (_, Some(id)) => &self.shaders[id].program,
+            (_, None) => &self.sprite_program,
+        };
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+        gl::Enable(gl::SCISSOR_TEST);
+        gl::UseProgram(program.handle);
+        let (src_factor, dst_factor) = blend_factors(r.style.blend);
+        gl::BlendFunc(src_factor, dst_factor);
+
+        gl::Uniform1i(program.uniform_tex, 0); // binds to GL_TEXTURE0
+        gl::Uniform2f(program.uniform_inv_tex_dims, 1. / tex_dims.0, 1. / tex_dims.1);
+
+        gl::BindVertexArray(program.vao);
+
+        gl::BufferData(gl::ARRAY_BUFFER,
+                       (mem::size_of::<GLfloat>() * r.vbo_data.len()) as GLsizeiptr,
+                       mem::transmute(&r.vbo_data[0]),
+                       gl::STREAM_DRAW);
+
+        gl::DrawArrays(gl::TRIANGLES, 0, (r.vbo_data.len() / program.vertex_len) as GLint);
+
+        gl::BindVertexArray(0);
+        gl::UseProgram(0);
+        gl::Disable(gl::SCISSOR_TEST);
         r.vbo_data.clear();
     }
 }
 
+// sets the filter of the texture bound to GL_TEXTURE_2D, generating mipmaps if needed
+pub unsafe fn set_texture_filter(filter: TextureFilter, dims: (u32, u32)) {
+    let filter = if filter == TextureFilter::Mipmap && !(dims.0.is_power_of_two() && dims.1.is_power_of_two()) {
+        TextureFilter::Linear // OpenGL ES 2 only supports mipmaps for power-of-two textures
+    } else {
+        filter
+    };
+    let (mag_filter, min_filter) = match filter {
+        TextureFilter::Linear => (gl::LINEAR, gl::LINEAR),
+        TextureFilter::Nearest => (gl::NEAREST, gl::NEAREST),
+        TextureFilter::Mipmap => (gl::LINEAR, gl::LINEAR_MIPMAP_LINEAR),
+    };
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter as GLint);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter as GLint);
+    if filter == TextureFilter::Mipmap {
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+    }
+}
+
+// blend factors assume premultiplied alpha, as used by the sprite atlas and render targets
+fn blend_factors(blend: BlendMode) -> (GLenum, GLenum) {
+    match blend {
+        BlendMode::Alpha => (gl::ONE, gl::ONE_MINUS_SRC_ALPHA),
+        BlendMode::Additive => (gl::ONE, gl::ONE),
+        BlendMode::Multiply => (gl::DST_COLOR, gl::ONE_MINUS_SRC_ALPHA),
+        BlendMode::Screen => (gl::ONE, gl::ONE_MINUS_SRC_COLOR),
+    }
+}
+
 impl Drop for CoreRenderer {
     fn drop(&mut self) {
         unsafe {
             gl::DeleteBuffers(1, &self.vbo);
+            for chunk in self.tile_chunks.drain(..).flatten() {
+                gl::DeleteVertexArrays(1, &chunk.vao);
+                gl::DeleteBuffers(1, &chunk.buffer);
+            }
+            for target in self.targets.drain(..).flatten() {
+                gl::DeleteFramebuffers(1, &target.fbo);
+                gl::DeleteTextures(1, &target.tex);
+            }
         }
     }
 }
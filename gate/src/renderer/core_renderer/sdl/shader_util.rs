@@ -13,12 +13,13 @@
 // limitations under the License.
 
 use std::os::raw::c_char;
-use std::{ptr, str};
+use std::ptr;
 
 use gl::types::*;
 use gl;
 
-pub fn compile_shader(src: *const c_char, ty: GLenum) -> GLuint {
+// compiles a shader, returning the info log on failure
+pub fn compile_shader(src: *const c_char, ty: GLenum) -> Result<GLuint, String> {
     let shader = unsafe {gl::CreateShader(ty)};
     unsafe {
 
@@ -34,13 +35,15 @@ pub fn compile_shader(src: *const c_char, ty: GLenum) -> GLuint {
             let mut buf = Vec::with_capacity(len as usize - 1);
             buf.set_len(len as usize - 1); // subtract 1 to skip the trailing null character
             gl::GetShaderInfoLog(shader, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
-            panic!("{}", str::from_utf8(&buf).ok().expect("glGetShaderInfoLog returned invalid utf8"));
+            gl::DeleteShader(shader);
+            return Err(format!("failed to compile shader: {}", String::from_utf8_lossy(&buf)));
         }
     }
-        shader
+    Ok(shader)
 }
 
-pub fn link_program(vs: GLuint, fs: GLuint) -> GLuint {
+// links a program from compiled shaders, returning the info log on failure
+pub fn link_program(vs: GLuint, fs: GLuint) -> Result<GLuint, String> {
     let program = unsafe {gl::CreateProgram()};
     unsafe {
         gl::AttachShader(program, vs);
@@ -56,8 +59,9 @@ pub fn link_program(vs: GLuint, fs: GLuint) -> GLuint {
             let mut buf = Vec::with_capacity(len as usize - 1);
             buf.set_len(len as usize - 1); // subtract 1 to skip the trailing null character
             gl::GetProgramInfoLog(program, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
-            panic!("{}", str::from_utf8(&buf).ok().expect("glGetProgramInfoLog returned invalid utf8"));
+            gl::DeleteProgram(program);
+            return Err(format!("failed to link shader program: {}", String::from_utf8_lossy(&buf)));
         }
     }
-    program
+    Ok(program)
 }
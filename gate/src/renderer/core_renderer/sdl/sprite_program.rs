@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{ptr, str, mem};
+use std::{str, mem};
 use std::os::raw::{c_void, c_char};
 
 use gl::types::*;
@@ -28,50 +28,91 @@ pub struct SpriteProgram {
     fs: GLuint,
     pub uniform_tex: GLint,
     pub uniform_inv_tex_dims: GLint,
+    pub vertex_len: usize, // number of floats per vertex
+    attribs: &'static [Attrib],
 }
 
+// attribute name, size, normalized flag, and offset in floats
+type Attrib = (*const c_char, GLint, GLboolean, usize);
+
+const SPRITE_ATTRIBS: [Attrib; 4] = [
+    (c_str!("vert"), 2, gl::FALSE, 0),
+    (c_str!("vs_inv_tex_sample_dims"), 2, gl::TRUE, 2),
+    (c_str!("vs_tex_vert_rb"), 2, gl::TRUE, 4),
+    (c_str!("vs_flash_ratio"), 1, gl::TRUE, 6),
+];
+
+const MESH_ATTRIBS: [Attrib; 4] = [
+    (c_str!("vert"), 2, gl::FALSE, 0),
+    (c_str!("vs_tex_coords"), 2, gl::FALSE, 2),
+    (c_str!("vs_tex_bounds"), 4, gl::FALSE, 4),
+    (c_str!("vs_color"), 4, gl::FALSE, 8),
+];
+
+const TILE_ATTRIBS: [Attrib; 2] = [
+    (c_str!("vert"), 2, gl::FALSE, 0),
+    (c_str!("vs_tex_coords"), 2, gl::FALSE, 2),
+];
+
 impl SpriteProgram {
-    pub fn new() -> SpriteProgram {
-        let vs = shader_util::compile_shader(shaders::VS_SPRITE_SRC, gl::VERTEX_SHADER);
-        let fs = shader_util::compile_shader(shaders::FS_SPRITE_SRC, gl::FRAGMENT_SHADER);
-        let handle = shader_util::link_program(vs, fs);
-        let vao = SpriteProgram::make_vao(handle);
-        unsafe {
+    pub fn new() -> Result<SpriteProgram, String> {
+        SpriteProgram::with_fragment_shader(shaders::FS_SPRITE_SRC)
+    }
+
+    pub fn with_fragment_shader(fs_src: *const c_char) -> Result<SpriteProgram, String> {
+        SpriteProgram::build(shaders::VS_SPRITE_SRC, fs_src, &SPRITE_ATTRIBS, 7)
+    }
+
+    // program for drawing triangle meshes in "mesh mode", which has its own vertex layout
+    pub fn mesh() -> Result<SpriteProgram, String> {
+        SpriteProgram::build(shaders::VS_MESH_SRC, shaders::FS_MESH_SRC, &MESH_ATTRIBS, 12)
+    }
+
+    // program for drawing cached tile map chunks, using the sprite fragment shader
+    pub fn tile() -> Result<SpriteProgram, String> {
+        SpriteProgram::build(shaders::VS_TILE_SRC, shaders::FS_SPRITE_SRC, &TILE_ATTRIBS, 4)
+    }
+
+    fn build(vs_src: *const c_char, fs_src: *const c_char, attribs: &'static [Attrib], vertex_len: usize)
+             -> Result<SpriteProgram, String> {
+        let vs = shader_util::compile_shader(vs_src, gl::VERTEX_SHADER)?;
+        let fs = shader_util::compile_shader(fs_src, gl::FRAGMENT_SHADER).inspect_err(|_| unsafe {
+            gl::DeleteShader(vs);
+        })?;
+        let handle = shader_util::link_program(vs, fs).inspect_err(|_| unsafe {
+            gl::DeleteShader(vs);
+            gl::DeleteShader(fs);
+        })?;
+        let mut program = unsafe {
             SpriteProgram {
-                handle, vao, vs, fs,
+                handle, vao: 0, vs, fs,
                 uniform_tex: gl::GetUniformLocation(handle, c_str!("tex")),
                 uniform_inv_tex_dims: gl::GetUniformLocation(handle, c_str!("inv_tex_dims")),
+                vertex_len, attribs,
             }
-        }
+        };
+        program.vao = program.make_vao();
+        Ok(program)
     }
 
-    fn make_vao(program_handle: GLuint) -> GLuint {
+    // makes a vertex array object for this program that reads from the buffer bound to GL_ARRAY_BUFFER
+    pub fn make_vao(&self) -> GLuint {
         let mut vao = 0;
         unsafe {
-            let attrib_vert = gl::GetAttribLocation(program_handle, c_str!("vert"));
-            let attrib_vs_inv_tex_sample_dims = gl::GetAttribLocation(program_handle, c_str!("vs_inv_tex_sample_dims"));
-            let attrib_vs_tex_vert_rb = gl::GetAttribLocation(program_handle, c_str!("vs_tex_vert_rb"));
-            let attrib_vs_flash_ratio = gl::GetAttribLocation(program_handle, c_str!("vs_flash_ratio"));
-
             gl::GenVertexArrays(1, &mut vao);
             gl::BindVertexArray(vao);
 
             // TODO be consistent with the gl::TRUE/FALSE values in gl::VertexAttribPointer...
 
-            gl::EnableVertexAttribArray(attrib_vert as GLuint);
-            gl::VertexAttribPointer(attrib_vert as GLuint, 2, gl::FLOAT, gl::FALSE, 7*mem::size_of::<GLfloat>() as i32, ptr::null());
-
-            gl::EnableVertexAttribArray(attrib_vs_inv_tex_sample_dims as GLuint);
-            gl::VertexAttribPointer(attrib_vs_inv_tex_sample_dims as GLuint, 2, gl::FLOAT, gl::TRUE, 7*mem::size_of::<GLfloat>() as i32,
-                                    (2 * mem::size_of::<GLfloat>()) as *const c_void);
-
-            gl::EnableVertexAttribArray(attrib_vs_tex_vert_rb as GLuint);
-            gl::VertexAttribPointer(attrib_vs_tex_vert_rb as GLuint, 2, gl::FLOAT, gl::TRUE, 7*mem::size_of::<GLfloat>() as i32,
-                                    (4 * mem::size_of::<GLfloat>()) as *const c_void);
-
-            gl::EnableVertexAttribArray(attrib_vs_flash_ratio as GLuint);
-            gl::VertexAttribPointer(attrib_vs_flash_ratio as GLuint, 1, gl::FLOAT, gl::TRUE, 7*mem::size_of::<GLfloat>() as i32,
-                                    (6 * mem::size_of::<GLfloat>()) as *const c_void);
+            for &(name, size, normalized, offset) in self.attribs {
+                let attrib = gl::GetAttribLocation(self.handle, name);
+                // attributes may be optimized out when a custom fragment shader ignores their varyings
+                if attrib < 0 { continue; }
+                gl::EnableVertexAttribArray(attrib as GLuint);
+                gl::VertexAttribPointer(attrib as GLuint, size, gl::FLOAT, normalized,
+                                        (self.vertex_len * mem::size_of::<GLfloat>()) as i32,
+                                        (offset * mem::size_of::<GLfloat>()) as *const c_void);
+            }
 
             gl::BindVertexArray(0);
         }
@@ -13,16 +13,25 @@
 // limitations under the License.
 
 use std::mem;
-use std::os::raw::c_int;
+use std::os::raw::{c_int, c_void};
 
+use crate::renderer::{BlendMode, TextureFilter, Uniform};
 use crate::renderer::render_buffer::RenderBuffer;
+use crate::renderer::shaders;
 use crate::wasm_imports::*;
 
-pub struct CoreRenderer { }
+pub struct CoreRenderer {
+    target_filter: TextureFilter,
+}
 
 impl CoreRenderer {
-    pub fn new() -> CoreRenderer {
-        CoreRenderer { }
+    pub fn new(sprites_filter: TextureFilter, target_filter: TextureFilter) -> CoreRenderer {
+        unsafe { gateWasmSetSpriteFilter(filter_id(sprites_filter)); }
+        CoreRenderer { target_filter: target_filter.for_target() }
+    }
+
+    pub(in crate::renderer) fn set_sprites_filter(&mut self, filter: TextureFilter) {
+        unsafe { gateWasmSetSpriteFilter(filter_id(filter)); }
     }
 }
 
@@ -39,9 +48,102 @@ impl CoreRenderer {
         }
     }
 
+    pub(in crate::renderer) fn create_target(&mut self, dims: (u32, u32)) -> usize {
+        unsafe { gateWasmCreateTarget(dims.0 as c_int, dims.1 as c_int, filter_id(self.target_filter)) as usize }
+    }
+
+    pub(in crate::renderer) fn delete_target(&mut self, id: usize) {
+        unsafe {
+            gateWasmDeleteTarget(id as c_int);
+        }
+    }
+
+    pub(in crate::renderer) fn create_tile_chunk(&mut self, vertices: &[f32]) -> usize {
+        unsafe {
+            gateWasmCreateTileChunk(mem::size_of_val(vertices), vertices.as_ptr() as *const c_void) as usize
+        }
+    }
+
+    pub(in crate::renderer) fn delete_tile_chunk(&mut self, id: usize) {
+        unsafe {
+            gateWasmDeleteTileChunk(id as c_int);
+        }
+    }
+
+    pub(in crate::renderer) fn draw_tile_chunk(&mut self, _r: &RenderBuffer, id: usize, vertex_count: usize,
+                                               blend: BlendMode, transform: &[f32; 6], native_scale: (f32, f32)) {
+        let params = [
+            transform[0], transform[1], transform[2], transform[3], transform[4], transform[5],
+            native_scale.0, native_scale.1,
+        ];
+        unsafe {
+            gateWasmDrawTileChunk(id as c_int, blend_id(blend), vertex_count as c_int, params.as_ptr());
+        }
+    }
+
+    pub(in crate::renderer) fn bind_target(&mut self, id: Option<usize>, viewport_dims: (u32, u32)) {
+        let id = id.map(|id| id as c_int).unwrap_or(-1);
+        unsafe {
+            gateWasmBindTarget(id, viewport_dims.0 as c_int, viewport_dims.1 as c_int);
+        }
+    }
+
+    pub(in crate::renderer) fn create_shader(&mut self, fragment_src: &str) -> usize {
+        let src = format!("{}\n{}", shaders::FS_CUSTOM_PRELUDE, fragment_src);
+        unsafe { gateWasmCreateShader(src.len(), src.as_ptr() as *const c_void) as usize }
+    }
+
+    pub(in crate::renderer) fn set_uniform(&mut self, shader: usize, name: &str, value: Uniform) {
+        // the kind codes are interpreted by gateWasmSetUniform in gate.js
+        let (kind, x, y, z, w) = match value {
+            Uniform::Float(x) => (0, x, 0., 0., 0.),
+            Uniform::Vec2(x, y) => (1, x, y, 0., 0.),
+            Uniform::Vec3(x, y, z) => (2, x, y, z, 0.),
+            Uniform::Vec4(x, y, z, w) => (3, x, y, z, w),
+            Uniform::Int(x) => (4, x as f32, 0., 0., 0.),
+        };
+        unsafe {
+            gateWasmSetUniform(shader as c_int, name.len(), name.as_ptr() as *const c_void, kind, x, y, z, w);
+        }
+    }
+
     pub(in crate::renderer) fn draw_sprites(&mut self, r: &mut RenderBuffer) {
         unsafe {
-            gateWasmDrawSprites(mem::size_of::<f32>() * r.vbo_data.len(), mem::transmute(&r.vbo_data[0]));
+            gateWasmDrawSprites(shader_id(r), blend_id(r.style.blend), mem::size_of::<f32>() * r.vbo_data.len(), mem::transmute(&r.vbo_data[0]));
+        }
+    }
+
+    pub(in crate::renderer) fn draw_mesh(&mut self, r: &mut RenderBuffer) {
+        unsafe {
+            gateWasmDrawMesh(blend_id(r.style.blend), mem::size_of::<f32>() * r.vbo_data.len(), mem::transmute(&r.vbo_data[0]));
         }
     }
+
+    pub(in crate::renderer) fn draw_target(&mut self, r: &mut RenderBuffer, id: usize) {
+        unsafe {
+            gateWasmDrawTargetSprites(id as c_int, shader_id(r), blend_id(r.style.blend), mem::size_of::<f32>() * r.vbo_data.len(),
+                                      mem::transmute(&r.vbo_data[0]));
+        }
+    }
+}
+
+fn shader_id(r: &RenderBuffer) -> c_int {
+    r.style.shader.map(|id| id as c_int).unwrap_or(-1)
+}
+
+fn filter_id(filter: TextureFilter) -> c_int {
+    match filter {
+        TextureFilter::Linear => 0,
+        TextureFilter::Nearest => 1,
+        TextureFilter::Mipmap => 2,
+    }
+}
+
+fn blend_id(blend: BlendMode) -> c_int {
+    match blend {
+        BlendMode::Alpha => 0,
+        BlendMode::Additive => 1,
+        BlendMode::Multiply => 2,
+        BlendMode::Screen => 3,
+    }
 }
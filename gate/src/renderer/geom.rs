@@ -66,6 +66,12 @@ impl Mat2 {
 
     pub fn col_0(&self) -> Vec2 { Vec2::new(self.a, self.c) }
     pub fn col_1(&self) -> Vec2 { Vec2::new(self.b, self.d) }
+
+    fn inverse(&self) -> Option<Mat2> {
+        let det = self.a * self.d - self.b * self.c;
+        if det.abs() < 1e-12 { return None; }
+        Some(Mat2 { a: self.d / det, b: -self.b / det, c: -self.c / det, d: self.a / det })
+    }
 }
 
 impl Mul<Vec2> for Mat2 {
@@ -223,5 +229,17 @@ impl Affine {
         (result.x as f32, result.y as f32)
     }
 
+    pub(crate) fn apply_f64(&self, input: (f64, f64)) -> (f64, f64) {
+        let result = self.apply(Vec2::new(input.0, input.1));
+        (result.x, result.y)
+    }
+
     pub(crate) fn mat(&self) -> &Mat2 { &self.mat }
+
+    pub(crate) fn offset(&self) -> Vec2 { self.offset }
+
+    // returns the inverse transformation, or None if the transformation is degenerate
+    pub(crate) fn inverse(&self) -> Option<Affine> {
+        self.mat.inverse().map(|mat| Affine { mat, offset: -1. * (mat * self.offset) })
+    }
 }
@@ -17,13 +17,17 @@
 //! Rendering uses OpenGL shaders designed specifically for 2D pixel art,
 //! looking crisp at any scale or rotation.
 
+mod camera;
 mod geom;
 pub(crate) mod atlas;
 pub(crate) mod render_buffer;
 mod renderer;
+mod tile_map;
 mod vbo_packer;
 pub(crate) mod core_renderer;
 pub(crate) mod shaders;
 
 pub use self::renderer::*;
 pub use self::geom::Affine;
+pub use self::camera::Camera2D;
+pub use self::tile_map::TileMap;
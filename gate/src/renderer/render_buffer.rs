@@ -16,11 +16,20 @@ use super::core_renderer::CoreRenderer;
 use super::vbo_packer;
 
 use crate::app_info::AppInfo;
-use super::atlas::Atlas;
+use super::atlas::{Atlas, ImageCoords};
 use super::geom::Affine;
+use super::renderer::{BlendMode, MeshVertex, SliceFill};
 
 #[derive(PartialEq, Copy, Clone)]
-pub(super) enum Mode { Sprite }
+pub(super) enum Mode { Sprite, Target(usize), Mesh }
+
+// per-draw settings that require a flush when changed
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub(super) struct Style { pub shader: Option<usize>, pub blend: BlendMode }
+
+impl Style {
+    pub fn new(shader: Option<usize>, blend: BlendMode) -> Style { Style { shader, blend } }
+}
 
 pub(super) struct RenderDims {
     pub min_dims: (f64, f64),
@@ -53,6 +62,12 @@ impl RenderDims {
         RenderDims { min_dims, max_dims, tile_width, native_dims, pixel_scalar, native_pre_pad, used_native_dims, dims }
     }
 
+    // dims for rendering to an offscreen target, where one app pixel is one target pixel
+    pub fn for_target(dims: (u32, u32)) -> RenderDims {
+        let dims_f64 = (dims.0 as f64, dims.1 as f64);
+        RenderDims::new(dims_f64, dims_f64, None, dims)
+    }
+
     pub fn set_native_dims(&mut self, native_dims: (u32, u32)) {
         *self = RenderDims::new(self.min_dims, self.max_dims, self.tile_width, native_dims);
     }
@@ -69,8 +84,10 @@ impl RenderDims {
 pub struct RenderBuffer {
     pub(super) sprite_atlas: Atlas,
     pub(super) mode: Mode,
+    pub(super) style: Style,
     pub(super) vbo_data: Vec<f32>,
     pub(super) dims: RenderDims,
+    pub(super) flip_y: bool,
 }
 
 impl RenderBuffer {
@@ -78,15 +95,18 @@ impl RenderBuffer {
         RenderBuffer {
             sprite_atlas,
             mode: Mode::Sprite,
+            style: Style::new(None, BlendMode::Alpha),
             vbo_data: Vec::new(),
             dims: RenderDims::new(info.min_dims, info.max_dims, info.tile_width, native_dims),
+            flip_y: false,
         }
     }
 
-    fn change_mode(&mut self, r: &mut CoreRenderer, mode: Mode) {
-        if mode != self.mode {
+    fn change_mode(&mut self, r: &mut CoreRenderer, mode: Mode, style: Style) {
+        if mode != self.mode || style != self.style {
             self.flush(r);
             self.mode = mode;
+            self.style = style;
         }
     }
 
@@ -94,13 +114,44 @@ impl RenderBuffer {
         if !self.vbo_data.is_empty() {
             match self.mode {
                 Mode::Sprite => r.draw_sprites(self),
+                Mode::Target(id) => r.draw_target(self, id),
+                Mode::Mesh => r.draw_mesh(self),
             }
             self.vbo_data.clear();
         }
     }
 
-    pub(super) fn append_sprite(&mut self, r: &mut CoreRenderer, affine: &Affine, sprite_id: u16, flash_ratio: f64) {
-        self.change_mode(r, Mode::Sprite);
-        vbo_packer::append_sprite(self, affine, sprite_id, flash_ratio);
+    pub(super) fn append_sprite(&mut self, r: &mut CoreRenderer, affine: &Affine, sprite_id: u16, flash_ratio: f64,
+                                style: Style) {
+        self.change_mode(r, Mode::Sprite, style);
+        let img_coords = self.sprite_atlas.images[&sprite_id];
+        vbo_packer::append_sprite(self, affine, img_coords, flash_ratio);
+    }
+
+    pub(super) fn append_target(&mut self, r: &mut CoreRenderer, affine: &Affine, target_id: usize,
+                                dims: (u32, u32), flash_ratio: f64, style: Style) {
+        self.change_mode(r, Mode::Target(target_id), style);
+        let dims = (dims.0 as f32, dims.1 as f32);
+        let img_coords = ImageCoords { lt: (0., 0.), rb: dims, anchor: (0.5 * dims.0, 0.5 * dims.1) };
+        vbo_packer::append_sprite(self, affine, img_coords, flash_ratio);
+    }
+
+    pub(super) fn append_nine_slice(&mut self, r: &mut CoreRenderer, affine: &Affine, sprite_id: u16,
+                                    dims: (f64, f64), fill: SliceFill, style: Style) {
+        self.change_mode(r, Mode::Sprite, style);
+        let slice = match self.sprite_atlas.slices.get(&sprite_id) {
+            Some(&slice) => slice,
+            // the loading atlas has no nine-slices for the sprites it draws as empty
+            None if self.sprite_atlas.is_empty(sprite_id) => return,
+            None => panic!("sprite is not a nine-slice sprite"),
+        };
+        vbo_packer::append_nine_slice(self, affine, slice, dims, fill == SliceFill::Tile);
+    }
+
+    pub(super) fn append_mesh(&mut self, r: &mut CoreRenderer, affine: &Affine, sprite_id: u16,
+                              vertices: &[MeshVertex], blend: BlendMode) {
+        self.change_mode(r, Mode::Mesh, Style::new(None, blend));
+        let img_coords = self.sprite_atlas.images[&sprite_id];
+        vbo_packer::append_mesh(self, affine, img_coords, vertices);
     }
 }
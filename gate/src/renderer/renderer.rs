@@ -17,13 +17,23 @@
 //! Rendering uses OpenGL shaders designed specifically for 2D pixel art,
 //! looking crisp at any scale or rotation.
 
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::mem;
+use std::ops::Range;
 
 use crate::asset_id::{AppAssetId, IdU16};
+use crate::collision::Collider;
 
-use super::geom::Affine;
-use super::render_buffer::RenderBuffer;
+use super::atlas::Atlas;
+use super::geom::{Affine, Vec2};
+use super::render_buffer::{RenderBuffer, RenderDims, Style};
 use super::core_renderer::CoreRenderer;
+use super::tile_map::{TileMap, CHUNK_TILES};
+use super::vbo_packer;
+
+// number of frames that a cached tile map chunk is kept without being drawn
+const TILE_CHUNK_LIFETIME: u64 = 120;
 
 /// Contains methods for rendering visuals to screen.
 ///
@@ -36,13 +46,164 @@ use super::core_renderer::CoreRenderer;
 /// can be expensive, since it involves flushing graphics data and switching shaders,
 /// so try to minimize these switches.
 ///
-/// Note: due to recent refactorings, there is currently only one render mode, the "sprite mode".
-/// This will likely change in the near future.
-pub struct Renderer<A: AppAssetId> { b: RenderBuffer, c: CoreRenderer, phantom: PhantomData<A> }
+/// The available modes are "flash mode" (`flash_mode`) for sprites with an optional
+/// fade-to-white value, "shader mode" (`shader_mode`) for sprites drawn with a custom shader,
+/// "mesh mode" (`mesh_mode`) for arbitrary triangles textured by sprites,
+/// and "tile mode" (`tile_mode`) for drawing a `TileMap`.
+/// Any mode can use a `BlendMode` other than standard alpha blending via `with_blend`.
+pub struct Renderer<A: AppAssetId> {
+    b: RenderBuffer,
+    c: CoreRenderer,
+    target_stack: Vec<usize>,
+    tile_chunks: HashMap<TileChunkKey, TileChunk>,
+    frame: u64,
+    phantom: PhantomData<A>,
+}
+
+// tile map id, layer, and chunk index
+type TileChunkKey = (u64, usize, usize);
+
+struct TileChunk {
+    buffer: Option<usize>, // None if the chunk is empty
+    vertex_count: usize,
+    version: u64,
+    last_frame: u64,
+}
+
+/// An offscreen image that can be rendered to, and then drawn like a sprite.
+///
+/// Created using `Renderer::create_target`.
+pub struct RenderTarget { id: usize, dims: (u32, u32) }
+
+impl RenderTarget {
+    /// Returns the (width, height) of the target in pixels.
+    pub fn dims(&self) -> (u32, u32) { self.dims }
+}
+
+/// A custom fragment shader, created using `Renderer::create_shader`.
+#[derive(Copy, Clone)]
+pub struct Shader { id: usize }
+
+/// Value of a uniform variable in a custom `Shader`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(f32, f32),
+    Vec3(f32, f32, f32),
+    Vec4(f32, f32, f32, f32),
+    Int(i32),
+}
+
+/// How the edges and center of a nine-slice sprite fill the area they are resized to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SliceFill {
+    /// Stretches the edges and center to fit.
+    Stretch,
+    /// Repeats the edges and center at their original scale, cutting off the last repetition to fit.
+    Tile,
+}
+
+/// A vertex of a triangle mesh, drawn in "mesh mode".
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MeshVertex {
+    /// Position of the vertex, before applying the affine transformation of the draw call.
+    pub pos: (f64, f64),
+    /// Point of the sprite image at the vertex, in source image pixels relative to the sprite anchor,
+    /// with +Y meaning "up".
+    ///
+    /// A mesh with `tex` equal to `pos` for each vertex looks the same as drawing the sprite normally.
+    pub tex: (f64, f64),
+    /// Color to multiply the sprite image by, in rgba (red-green-blue-alpha) format.
+    pub color: (u8, u8, u8, u8),
+}
+
+impl MeshVertex {
+    /// Returns a new `MeshVertex` with the given fields.
+    pub fn new(pos: (f64, f64), tex: (f64, f64), color: (u8, u8, u8, u8)) -> MeshVertex {
+        MeshVertex { pos, tex, color }
+    }
+}
+
+/// Size and bounds of a sprite image, as returned by `AppContext::sprite_info`.
+///
+/// Measurements are in source image pixels, which are the units of the affine transformation of a draw call.
+/// Bounds are relative to the sprite anchor, which is drawn at the origin of the affine transformation,
+/// with +Y meaning "up".
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SpriteInfo {
+    /// Width and height of the original image, before transparent pixels around it were trimmed.
+    pub dims: (f64, f64),
+    /// Position of the anchor relative to the top-left corner of the original image, with +Y meaning "down".
+    pub anchor: (f64, f64),
+    /// Left-bottom corner of the trimmed image, relative to the anchor.
+    pub trimmed_min: (f64, f64),
+    /// Right-top corner of the trimmed image, relative to the anchor.
+    pub trimmed_max: (f64, f64),
+}
+
+impl SpriteInfo {
+    /// Returns the left-bottom corner of the original image, relative to the anchor.
+    pub fn untrimmed_min(&self) -> (f64, f64) {
+        (-self.anchor.0, self.anchor.1 - self.dims.1)
+    }
+
+    /// Returns the right-top corner of the original image, relative to the anchor.
+    pub fn untrimmed_max(&self) -> (f64, f64) {
+        (self.dims.0 - self.anchor.0, self.anchor.1)
+    }
+}
+
+/// How textures are sampled when drawn at a different scale than their source pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Linear interpolation, combined with the pixel art sampling of the sprite shader.
+    Linear,
+    /// Nearest-neighbor sampling, for blocky pixels without any smoothing.
+    Nearest,
+    /// Linear interpolation with mipmaps, for smooth scaling of zoomed-out views.
+    ///
+    /// Mipmaps require a power-of-two texture size, otherwise `Linear` is used instead.
+    Mipmap,
+}
+
+impl TextureFilter {
+    pub(crate) fn from_u8(value: u8) -> Option<TextureFilter> {
+        match value {
+            0 => Some(TextureFilter::Linear),
+            1 => Some(TextureFilter::Nearest),
+            2 => Some(TextureFilter::Mipmap),
+            _ => None,
+        }
+    }
+
+    // the filter to use for render targets, which do not maintain mipmaps
+    pub(crate) fn for_target(self) -> TextureFilter {
+        match self {
+            TextureFilter::Mipmap => TextureFilter::Linear,
+            filter => filter,
+        }
+    }
+}
+
+/// How drawn images are combined with the colors already rendered beneath them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Standard alpha blending, drawing images over what is beneath them.
+    Alpha,
+    /// Adds image colors to what is beneath them, for glows and particles.
+    Additive,
+    /// Multiplies image colors with what is beneath them, for shadows and lighting.
+    Multiply,
+    /// Brightens what is beneath by the inverse product of colors, a softer alternative to `Additive`.
+    Screen,
+}
 
 impl<A: AppAssetId> Renderer<A> {
     pub(crate) fn new(buffer: RenderBuffer, core_renderer: CoreRenderer) -> Renderer<A> {
-        let mut result = Renderer { b: buffer, c: core_renderer, phantom: PhantomData };
+        let mut result = Renderer {
+            b: buffer, c: core_renderer, target_stack: Vec::new(), tile_chunks: HashMap::new(), frame: 0,
+            phantom: PhantomData,
+        };
         result.set_scissor();
         result
     }
@@ -55,19 +216,200 @@ impl<A: AppAssetId> Renderer<A> {
 
     /// Enters "flash mode", for rendering images with an optional fade-to-white value.
     pub fn flash_mode(&mut self) -> FlashRenderer<A> {
-        FlashRenderer { r: self }
+        FlashRenderer { r: self, blend: BlendMode::Alpha }
+    }
+
+    /// Enters "mesh mode", for rendering triangles textured by sprites with per-vertex colors.
+    pub fn mesh_mode(&mut self) -> MeshRenderer<'_, A> {
+        MeshRenderer { r: self, blend: BlendMode::Alpha }
+    }
+
+    /// Enters "tile mode", for rendering a `TileMap`.
+    pub fn tile_mode(&mut self) -> TileRenderer<'_, A> {
+        TileRenderer { r: self, blend: BlendMode::Alpha }
+    }
+
+    /// Creates an offscreen render target with the given `width` and `height` in pixels.
+    ///
+    /// The target is initially transparent.
+    pub fn create_target(&mut self, width: u32, height: u32) -> RenderTarget {
+        assert!((1..=4096).contains(&width), "unrealistic target width {}", width);
+        assert!((1..=4096).contains(&height), "unrealistic target height {}", height);
+        RenderTarget { id: self.c.create_target((width, height)), dims: (width, height) }
+    }
+
+    /// Deletes a render target, freeing its graphics memory.
+    pub fn delete_target(&mut self, target: RenderTarget) {
+        assert!(!self.target_stack.contains(&target.id), "cannot delete a target that is being rendered to");
+        self.b.flush(&mut self.c);
+        self.c.delete_target(target.id);
+    }
+
+    /// Renders to `target` instead of the screen for the duration of the `render` closure.
+    ///
+    /// While rendering to the target, the origin is the bottom-left of the target,
+    /// and one renderer unit is one target pixel.
+    /// The previous contents of the target are kept unless it is cleared.
+    pub fn with_target<F: FnOnce(&mut Renderer<A>)>(&mut self, target: &RenderTarget, render: F) {
+        assert!(!self.target_stack.contains(&target.id), "cannot render to a target from within itself");
+        self.b.flush(&mut self.c);
+        self.target_stack.push(target.id);
+        let prev_dims = mem::replace(&mut self.b.dims, RenderDims::for_target(target.dims));
+        let prev_flip_y = mem::replace(&mut self.b.flip_y, true);
+        self.c.bind_target(Some(target.id), target.dims);
+        self.set_scissor();
+
+        render(self);
+
+        self.b.flush(&mut self.c);
+        self.target_stack.pop();
+        self.b.dims = prev_dims;
+        self.b.flip_y = prev_flip_y;
+        self.c.bind_target(self.target_stack.last().cloned(), self.b.dims.native_dims);
+        self.set_scissor();
+    }
+
+    /// Compiles a custom GLSL ES 1.0 (`#version 100`) fragment shader, panicking on a compile error.
+    ///
+    /// The shader source is prepended with declarations of the same inputs that the built-in sprite
+    /// shader uses, along with some helper functions, and must define `void main()`:
+    ///
+    /// * `uniform sampler2D tex` and `uniform vec2 inv_tex_dims`, the texture being drawn
+    ///   and the inverse of its dimensions
+    /// * `vec2 gate_tex_coords()`, the coordinates to sample for the current fragment in texture pixels,
+    ///   chosen to keep pixel art crisp
+    /// * `vec4 gate_sample(vec2 coords)`, samples the texture at coordinates in texture pixels
+    /// * `vec4 gate_flash(vec4 color)`, applies the fade-to-white value of the draw call
+    ///
+    /// For example, a shader that inverts colors could use the source
+    /// `"void main() { vec4 c = gate_sample(gate_tex_coords()); gl_FragColor = vec4(c.a - c.rgb, c.a); }"`.
+    /// Shaders are typically created once when first rendering.
+    pub fn create_shader(&mut self, fragment_src: &str) -> Shader {
+        Shader { id: self.c.create_shader(fragment_src) }
+    }
+
+    /// Sets a uniform variable declared in a custom `shader`.
+    ///
+    /// Uniforms that are not used by the shader are ignored, since they may have been optimized out.
+    pub fn set_uniform(&mut self, shader: &Shader, name: &str, value: Uniform) {
+        if self.b.style.shader == Some(shader.id) {
+            self.b.flush(&mut self.c);
+        }
+        self.c.set_uniform(shader.id, name, value);
+    }
+
+    /// Enters "shader mode", for rendering images using a custom `shader`.
+    pub fn shader_mode(&mut self, shader: &Shader) -> ShaderRenderer<'_, A> {
+        ShaderRenderer { r: self, shader: shader.id, blend: BlendMode::Alpha }
+    }
+
+    /// Draws `target` stretched over the entire screen using `shader`, for full-screen effects.
+    ///
+    /// Typically the scene is first rendered to `target` using `with_target`.
+    /// If invoked while rendering to another target, covers that target instead.
+    pub fn post_process(&mut self, target: &RenderTarget, shader: &Shader) {
+        let dims = self.b.dims.dims;
+        let affine = Affine::translate(0.5 * dims.0, 0.5 * dims.1)
+            .pre_scale_axes(dims.0 / target.dims.0 as f64, dims.1 / target.dims.1 as f64);
+        self.shader_mode(shader).draw_target(&affine, target);
     }
 
     pub(crate) fn app_dims(&self) -> (f64, f64) { self.b.dims.dims }
 
     pub(crate) fn native_px(&self) -> f64 { 1. / self.b.dims.pixel_scalar }
 
+    pub(crate) fn sprite_infos(&self) -> &[SpriteInfo] { &self.b.sprite_atlas.infos }
+
+    pub(crate) fn sprite_colliders(&self) -> &[Collider] { &self.b.sprite_atlas.colliders }
+
+    // swaps in a re-packed sprite atlas and its texture, see `Atlas::remapped`
+    #[cfg(all(feature = "hot_reload", debug_assertions, not(target_arch = "wasm32")))]
+    pub(crate) fn reload_sprites(&mut self, atlas: Atlas, ids: &[Option<u16>],
+                                 sprites_tex: *mut crate::core::sdl_imports::SDL_Texture) {
+        let atlas = atlas.remapped(ids, &self.b.sprite_atlas);
+        self.replace_sprites(atlas, sprites_tex);
+    }
+
+    // swaps in a new sprite atlas and its texture, e.g. once the main atlas has replaced the loading atlas
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn replace_sprites(&mut self, atlas: Atlas, sprites_tex: *mut crate::core::sdl_imports::SDL_Texture) {
+        self.clear_sprite_atlas();
+        self.b.sprite_atlas = atlas;
+        self.c.replace_sprites_tex(sprites_tex);
+    }
+
+    // swaps in a new sprite atlas, after its texture has been swapped in by JavaScript
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn replace_sprites(&mut self, atlas: Atlas, default_filter: TextureFilter) {
+        self.clear_sprite_atlas();
+        self.c.set_sprites_filter(atlas.filter(default_filter));
+        self.b.sprite_atlas = atlas;
+    }
+
+    // draws everything that uses the current sprite atlas, and deletes tile chunks made from it
+    fn clear_sprite_atlas(&mut self) {
+        self.b.flush(&mut self.c);
+        for (_, chunk) in self.tile_chunks.drain() {
+            if let Some(buffer) = chunk.buffer {
+                self.c.delete_tile_chunk(buffer);
+            }
+        }
+    }
+
     pub(crate) fn to_app_pos(&self, raw_x: i32, raw_y: i32) -> (f64, f64) {
         self.b.dims.to_app_pos(raw_x, raw_y)
     }
 
-    pub(crate) fn flush(&mut self) {
+    pub(crate) fn end_frame(&mut self) {
         self.b.flush(&mut self.c);
+        self.frame += 1;
+        let (frame, c) = (self.frame, &mut self.c);
+        self.tile_chunks.retain(|_, chunk| {
+            let keep = chunk.last_frame + TILE_CHUNK_LIFETIME >= frame;
+            if let (false, Some(buffer)) = (keep, chunk.buffer) {
+                c.delete_tile_chunk(buffer);
+            }
+            keep
+        });
+    }
+
+    fn draw_tile_layer(&mut self, affine: &Affine, map: &TileMap<A>, layer: usize, blend: BlendMode) {
+        assert!(layer < map.layer_count(), "tile map has no layer {}", layer);
+        self.b.flush(&mut self.c);
+        let (cols, rows) = match visible_chunks(map, affine, self.b.dims.dims) {
+            Some(chunks) => chunks,
+            None => return,
+        };
+        let clip = vbo_packer::clip_affine(&self.b, affine);
+        let (col_0, col_1, offset) = (clip.mat().col_0(), clip.mat().col_1(), clip.offset());
+        let transform = [
+            col_0.x as f32, col_1.x as f32, offset.x as f32,
+            col_0.y as f32, col_1.y as f32, offset.y as f32,
+        ];
+        let native = affine.post_scale(self.b.dims.pixel_scalar);
+        let native_scale = (native.mat().col_0().len() as f32, native.mat().col_1().len() as f32);
+
+        for chunk_row in rows {
+            for chunk_col in cols.clone() {
+                let index = map.chunk_index(chunk_col, chunk_row);
+                let key = (map.id(), layer, index);
+                let version = map.chunk_version(layer, index);
+                if !matches!(self.tile_chunks.get(&key), Some(chunk) if chunk.version == version) {
+                    if let Some(buffer) = self.tile_chunks.remove(&key).and_then(|chunk| chunk.buffer) {
+                        self.c.delete_tile_chunk(buffer);
+                    }
+                    let vertices = vbo_packer::tile_chunk_vertices(&self.b.sprite_atlas, map, layer, chunk_col, chunk_row);
+                    let buffer = if vertices.is_empty() { None } else { Some(self.c.create_tile_chunk(&vertices)) };
+                    let chunk = TileChunk { buffer, vertex_count: vertices.len() / 4, version, last_frame: 0 };
+                    self.tile_chunks.insert(key, chunk);
+                }
+                let chunk = self.tile_chunks.get_mut(&key).unwrap();
+                chunk.last_frame = self.frame;
+                if let Some(buffer) = chunk.buffer {
+                    self.c.draw_tile_chunk(&self.b, buffer, chunk.vertex_count, blend, &transform, native_scale);
+                }
+            }
+        }
     }
 
     pub(crate) fn set_screen_dims(&mut self, dims: (u32, u32)) {
@@ -91,9 +433,16 @@ impl<A: AppAssetId> Renderer<A> {
 /// A mode for rendering images with an optional fade-to-white value.
 pub struct FlashRenderer<'a, A: AppAssetId + 'a> {
     r: &'a mut Renderer<A>,
+    blend: BlendMode,
 }
 
 impl<'a, A: AppAssetId + 'a> FlashRenderer<'a, A> {
+    /// Uses the given `blend` mode for subsequent draws, instead of the default `BlendMode::Alpha`.
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
     /// Draws the given `sprite` using the given `affine` transformation from the origin.
     pub fn draw(&mut self, affine: &Affine, sprite: A::Sprite) {
         self.draw_flash(affine, sprite, 0.);
@@ -104,6 +453,168 @@ impl<'a, A: AppAssetId + 'a> FlashRenderer<'a, A> {
     /// `flash_ratio`, capped between `0.0` and `1.0`, controls how much blending occurs with the
     /// color white (`0.0` means use the image unaltered, `1.0` means use white completely).
     pub fn draw_flash(&mut self, affine: &Affine, sprite: A::Sprite, flash_ratio: f64) {
-        self.r.b.append_sprite(&mut self.r.c, affine, sprite.id_u16(), flash_ratio);
+        self.r.b.append_sprite(&mut self.r.c, affine, sprite.id_u16(), flash_ratio, Style::new(None, self.blend));
+    }
+
+    /// Draws the given nine-slice `sprite` resized to `dims` (width, height),
+    /// using the given `affine` transformation from the origin.
+    ///
+    /// The resized sprite is centered on the origin.
+    /// Corners are drawn at their original scale, while the edges and center are filled according to `fill`.
+    /// If `dims` is smaller than the corners, the corners are scaled down.
+    /// Nine-slice sprites are packed from images with the "_9s#" suffix (see `gate_build::AssetPacker::sprites`).
+    pub fn draw_nine_slice(&mut self, affine: &Affine, sprite: A::Sprite, dims: (f64, f64), fill: SliceFill) {
+        assert!(dims.0 >= 0. && dims.1 >= 0., "invalid nine-slice dims {:?}", dims);
+        self.r.b.append_nine_slice(&mut self.r.c, affine, sprite.id_u16(), dims, fill, Style::new(None, self.blend));
+    }
+
+    /// Draws the contents of the given render `target` using the given `affine` transformation from the origin.
+    ///
+    /// The target is centered on the origin, with one target pixel per app pixel.
+    pub fn draw_target(&mut self, affine: &Affine, target: &RenderTarget) {
+        assert!(!self.r.target_stack.contains(&target.id), "cannot draw a target from within itself");
+        self.r.b.append_target(&mut self.r.c, affine, target.id, target.dims, 0., Style::new(None, self.blend));
+    }
+}
+
+/// A mode for rendering images using a custom `Shader`.
+pub struct ShaderRenderer<'a, A: AppAssetId + 'a> {
+    r: &'a mut Renderer<A>,
+    shader: usize,
+    blend: BlendMode,
+}
+
+impl<'a, A: AppAssetId + 'a> ShaderRenderer<'a, A> {
+    /// Uses the given `blend` mode for subsequent draws, instead of the default `BlendMode::Alpha`.
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Draws the given `sprite` using the given `affine` transformation from the origin.
+    pub fn draw(&mut self, affine: &Affine, sprite: A::Sprite) {
+        self.draw_flash(affine, sprite, 0.);
+    }
+
+    /// Draws the given `sprite` using the given `affine` transformation from the origin,
+    /// passing a `flash_ratio` to the shader (see `FlashRenderer::draw_flash`).
+    pub fn draw_flash(&mut self, affine: &Affine, sprite: A::Sprite, flash_ratio: f64) {
+        self.r.b.append_sprite(&mut self.r.c, affine, sprite.id_u16(), flash_ratio, Style::new(Some(self.shader), self.blend));
+    }
+
+    /// Draws the contents of the given render `target` using the given `affine` transformation from the origin.
+    ///
+    /// The target is centered on the origin, with one target pixel per app pixel.
+    pub fn draw_target(&mut self, affine: &Affine, target: &RenderTarget) {
+        assert!(!self.r.target_stack.contains(&target.id), "cannot draw a target from within itself");
+        self.r.b.append_target(&mut self.r.c, affine, target.id, target.dims, 0., Style::new(Some(self.shader), self.blend));
+    }
+}
+
+/// A mode for rendering triangles textured by sprites, with a color for each vertex.
+///
+/// Sprite images are sampled smoothly rather than with the pixel art sampling of the other modes.
+/// Points outside of the trimmed sprite image are transparent.
+pub struct MeshRenderer<'a, A: AppAssetId + 'a> {
+    r: &'a mut Renderer<A>,
+    blend: BlendMode,
+}
+
+impl<'a, A: AppAssetId + 'a> MeshRenderer<'a, A> {
+    /// Uses the given `blend` mode for subsequent draws, instead of the default `BlendMode::Alpha`.
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Draws triangles textured by `sprite` using the given `affine` transformation from the origin.
+    ///
+    /// Each consecutive group of three `vertices` is a triangle.
+    pub fn draw_triangles(&mut self, affine: &Affine, sprite: A::Sprite, vertices: &[MeshVertex]) {
+        assert_eq!(vertices.len() % 3, 0, "vertex count {} is not a multiple of 3", vertices.len());
+        self.r.b.append_mesh(&mut self.r.c, affine, sprite.id_u16(), vertices, self.blend);
+    }
+
+    /// Draws a quadrilateral textured by `sprite` using the given `affine` transformation from the origin.
+    ///
+    /// The `vertices` must be in order going around the quadrilateral, which must be convex.
+    pub fn draw_quad(&mut self, affine: &Affine, sprite: A::Sprite, vertices: &[MeshVertex; 4]) {
+        self.append_quad(affine, sprite.id_u16(), vertices);
+    }
+
+    /// Draws the given `sprite` using the given `affine` transformation from the origin,
+    /// with colors blended between the four corners of the sprite image.
+    ///
+    /// The `colors` are for the left-top, right-top, right-bottom, and left-bottom corners, in rgba format.
+    /// Transparent pixels around the edges of the image are trimmed before choosing the corners.
+    pub fn draw_gradient(&mut self, affine: &Affine, sprite: A::Sprite, colors: [(u8, u8, u8, u8); 4]) {
+        let id = sprite.id_u16();
+        let coords = self.r.b.sprite_atlas.images[&id];
+        let (left, top) = ((coords.lt.0 - coords.anchor.0) as f64, (coords.anchor.1 - coords.lt.1) as f64);
+        let (right, bottom) = ((coords.rb.0 - coords.anchor.0) as f64, (coords.anchor.1 - coords.rb.1) as f64);
+        let vertices = [
+            MeshVertex::new((left, top), (left, top), colors[0]),
+            MeshVertex::new((right, top), (right, top), colors[1]),
+            MeshVertex::new((right, bottom), (right, bottom), colors[2]),
+            MeshVertex::new((left, bottom), (left, bottom), colors[3]),
+        ];
+        self.append_quad(affine, id, &vertices);
+    }
+
+    fn append_quad(&mut self, affine: &Affine, sprite_id: u16, vertices: &[MeshVertex; 4]) {
+        let triangles = [vertices[0], vertices[1], vertices[2], vertices[0], vertices[2], vertices[3]];
+        self.r.b.append_mesh(&mut self.r.c, affine, sprite_id, &triangles, self.blend);
+    }
+}
+
+// returns the ranges of chunk columns and rows of `map` that may be visible on screen
+fn visible_chunks<A: AppAssetId>(map: &TileMap<A>, affine: &Affine, dims: (f64, f64))
+                                 -> Option<(Range<u32>, Range<u32>)> {
+    let inverse = affine.inverse()?;
+    let corners = [(0., 0.), (dims.0, 0.), (0., dims.1), (dims.0, dims.1)];
+    let corners: Vec<_> = corners.iter().map(|&(x, y)| inverse.apply(Vec2::new(x, y))).collect();
+    // include a margin of one tile for sprites that extend beyond their tile
+    let margin = map.tile_size();
+    let chunk_size = map.tile_size() * CHUNK_TILES as f64;
+    let chunk_dims = map.chunk_dims();
+    let range = |min: f64, max: f64, count: u32| {
+        let start = ((min - margin) / chunk_size).floor().max(0.).min(count as f64) as u32;
+        let end = ((max + margin) / chunk_size).ceil().max(0.).min(count as f64) as u32;
+        start..end
+    };
+    let min_x = corners.iter().map(|c| c.x).fold(f64::INFINITY, f64::min);
+    let max_x = corners.iter().map(|c| c.x).fold(f64::NEG_INFINITY, f64::max);
+    let min_y = corners.iter().map(|c| c.y).fold(f64::INFINITY, f64::min);
+    let max_y = corners.iter().map(|c| c.y).fold(f64::NEG_INFINITY, f64::max);
+    Some((range(min_x, max_x, chunk_dims.0), range(min_y, max_y, chunk_dims.1)))
+}
+
+/// A mode for rendering `TileMap`s.
+pub struct TileRenderer<'a, A: AppAssetId + 'a> {
+    r: &'a mut Renderer<A>,
+    blend: BlendMode,
+}
+
+impl<'a, A: AppAssetId + 'a> TileRenderer<'a, A> {
+    /// Uses the given `blend` mode for subsequent draws, instead of the default `BlendMode::Alpha`.
+    pub fn with_blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Draws all layers of `map` in order, using the given `affine` transformation
+    /// from the left-bottom corner of the map.
+    ///
+    /// Only the chunks of the map that are visible are drawn.
+    pub fn draw(&mut self, affine: &Affine, map: &TileMap<A>) {
+        for layer in 0..map.layer_count() {
+            self.draw_layer(affine, map, layer);
+        }
+    }
+
+    /// Draws a single `layer` of `map`, using the given `affine` transformation
+    /// from the left-bottom corner of the map.
+    pub fn draw_layer(&mut self, affine: &Affine, map: &TileMap<A>, layer: usize) {
+        self.r.draw_tile_layer(affine, map, layer, self.blend);
     }
 }
@@ -0,0 +1,43 @@
+#version 100
+
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Prepended to custom fragment shaders, which must define main().
+// Custom shaders receive the same inputs as sprite.frag.
+
+precision highp float;
+
+uniform sampler2D tex;
+uniform vec2 inv_tex_dims; // inverse of tex dimensions
+
+varying vec2 fs_inv_tex_sample_dims; // inverse width-height of sampling region, in tex pixels
+varying vec2 fs_tex_vert_rb; // right-bottom vertex of sampling region, in tex pixels
+varying float fs_flash_ratio;
+
+// coordinates to sample for the current fragment, in tex pixels, keeping pixel art crisp
+vec2 gate_tex_coords() {
+    vec2 mid = floor(fs_tex_vert_rb);
+    return mid - 0.5 + min((fs_tex_vert_rb - mid) * fs_inv_tex_sample_dims, 1.0);
+}
+
+// samples the texture at the given coordinates in tex pixels
+vec4 gate_sample(vec2 coords) {
+    return texture2D(tex, coords * inv_tex_dims);
+}
+
+// applies the fade-to-white value of the draw call to a color
+vec4 gate_flash(vec4 color) {
+    return mix(color, vec4(1.0, 1.0, 1.0, 1.0) * color[3], fs_flash_ratio);
+}
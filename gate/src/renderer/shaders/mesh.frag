@@ -0,0 +1,29 @@
+#version 100
+
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+precision highp float;
+
+uniform sampler2D tex;
+uniform vec2 inv_tex_dims; // inverse of tex dimensions
+
+varying vec2 fs_tex_coords; // in tex pixels
+varying vec4 fs_tex_bounds; // left-top and right-bottom of the sprite sampling region, in tex pixels
+varying vec4 fs_color; // premultiplied alpha
+
+void main() {
+    vec2 coords = clamp(fs_tex_coords, fs_tex_bounds.xy, fs_tex_bounds.zw);
+    gl_FragColor = texture2D(tex, coords * inv_tex_dims) * fs_color;
+}
@@ -0,0 +1,31 @@
+#version 100
+
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+attribute vec2 vert;
+attribute vec2 vs_tex_coords;
+attribute vec4 vs_tex_bounds;
+attribute vec4 vs_color;
+
+varying vec2 fs_tex_coords;
+varying vec4 fs_tex_bounds;
+varying vec4 fs_color;
+
+void main() {
+    fs_tex_coords = vs_tex_coords;
+    fs_tex_bounds = vs_tex_bounds;
+    fs_color = vs_color;
+    gl_Position = vec4(vert, 0, 1);
+}
@@ -16,3 +16,9 @@ use std::os::raw::c_char;
 
 pub const VS_SPRITE_SRC: *const c_char = include_c_str!("sprite.vert");
 pub const FS_SPRITE_SRC: *const c_char = include_c_str!("sprite.frag");
+pub const VS_MESH_SRC: *const c_char = include_c_str!("mesh.vert");
+pub const FS_MESH_SRC: *const c_char = include_c_str!("mesh.frag");
+pub const VS_TILE_SRC: *const c_char = include_c_str!("tile.vert");
+
+// prepended to the source of custom fragment shaders
+pub const FS_CUSTOM_PRELUDE: &str = include_str!("custom_prelude.frag");
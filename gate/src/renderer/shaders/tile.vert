@@ -0,0 +1,36 @@
+#version 100
+
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// draws cached tile map vertices using the sprite fragment shader
+
+attribute vec2 vert; // in map units
+attribute vec2 vs_tex_coords; // in tex pixels
+
+uniform vec3 transform_row_0; // rows of the affine transformation from map units to clip coordinates
+uniform vec3 transform_row_1;
+uniform vec2 native_scale; // native pixels per map unit along each map axis
+
+varying vec2 fs_inv_tex_sample_dims;
+varying vec2 fs_tex_vert_rb;
+varying float fs_flash_ratio;
+
+void main() {
+    fs_inv_tex_sample_dims = native_scale;
+    fs_tex_vert_rb = vs_tex_coords + 0.5 / native_scale;
+    fs_flash_ratio = 0.0;
+    vec3 pos = vec3(vert, 1.0);
+    gl_Position = vec4(dot(transform_row_0, pos), dot(transform_row_1, pos), 0, 1);
+}
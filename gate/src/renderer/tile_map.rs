@@ -0,0 +1,124 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::asset_id::{AppAssetId, IdU16};
+
+// width and height of a chunk in tiles
+pub(super) const CHUNK_TILES: u32 = 16;
+
+static NEXT_MAP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A grid of sprite tiles in one or more layers, drawn in "tile mode" (see `Renderer::tile_mode`).
+///
+/// Tiles are grouped into chunks whose vertex data is cached in graphics memory,
+/// so drawing a large map that rarely changes is cheap.
+/// Changing a tile only rebuilds the chunk containing it.
+///
+/// Tile `(col, row)` is drawn with its sprite anchor at the center of the tile,
+/// where column `0` is on the left and row `0` is on the bottom.
+/// Sprites extending more than one tile beyond their own tile may be skipped near the edges of the screen.
+pub struct TileMap<A: AppAssetId> {
+    id: u64,
+    dims: (u32, u32),
+    tile_size: f64,
+    layers: Vec<Vec<Option<u16>>>,
+    versions: Vec<Vec<u64>>, // incremented whenever a chunk changes, indexed by layer and chunk
+    phantom: PhantomData<A>,
+}
+
+impl<A: AppAssetId> TileMap<A> {
+    /// Creates an empty tile map with the given number of `cols`, `rows`, and `layers`.
+    ///
+    /// `tile_size` is the distance between tile centers, typically the width of the tile sprites.
+    pub fn new(cols: u32, rows: u32, layers: usize, tile_size: f64) -> TileMap<A> {
+        assert!(cols > 0 && rows > 0 && layers > 0, "tile map must not be empty");
+        assert!(tile_size > 0. && tile_size.is_finite(), "invalid tile size {}", tile_size);
+        let chunk_count = (cols.div_ceil(CHUNK_TILES) * rows.div_ceil(CHUNK_TILES)) as usize;
+        TileMap {
+            id: NEXT_MAP_ID.fetch_add(1, Ordering::Relaxed),
+            dims: (cols, rows),
+            tile_size,
+            layers: vec![vec![None; cols as usize * rows as usize]; layers],
+            versions: vec![vec![0; chunk_count]; layers],
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the number of (columns, rows) in the map.
+    pub fn dims(&self) -> (u32, u32) { self.dims }
+
+    /// Returns the number of layers in the map.
+    pub fn layer_count(&self) -> usize { self.layers.len() }
+
+    /// Returns the distance between tile centers.
+    pub fn tile_size(&self) -> f64 { self.tile_size }
+
+    /// Returns the sprite at the given tile, if any.
+    pub fn tile(&self, layer: usize, col: u32, row: u32) -> Option<A::Sprite> {
+        self.layers[layer][self.index(col, row)].map(|id| A::Sprite::from_u16(id).unwrap())
+    }
+
+    /// Sets the sprite at the given tile, or clears it if `sprite` is `None`.
+    pub fn set_tile(&mut self, layer: usize, col: u32, row: u32, sprite: Option<A::Sprite>) {
+        let index = self.index(col, row);
+        let id = sprite.map(|s| s.id_u16());
+        if self.layers[layer][index] != id {
+            self.layers[layer][index] = id;
+            let chunk = self.chunk_index(col / CHUNK_TILES, row / CHUNK_TILES);
+            self.versions[layer][chunk] += 1;
+        }
+    }
+
+    /// Returns the (col, row) of the tile containing the given position in map coordinates, if any.
+    pub fn tile_at(&self, x: f64, y: f64) -> Option<(u32, u32)> {
+        let (col, row) = ((x / self.tile_size).floor(), (y / self.tile_size).floor());
+        if col >= 0. && row >= 0. && col < self.dims.0 as f64 && row < self.dims.1 as f64 {
+            Some((col as u32, row as u32))
+        } else {
+            None
+        }
+    }
+
+    pub(super) fn id(&self) -> u64 { self.id }
+
+    // returns the number of (columns, rows) of chunks
+    pub(super) fn chunk_dims(&self) -> (u32, u32) {
+        (self.dims.0.div_ceil(CHUNK_TILES), self.dims.1.div_ceil(CHUNK_TILES))
+    }
+
+    pub(super) fn chunk_index(&self, chunk_col: u32, chunk_row: u32) -> usize {
+        (chunk_row * self.chunk_dims().0 + chunk_col) as usize
+    }
+
+    pub(super) fn chunk_version(&self, layer: usize, chunk: usize) -> u64 { self.versions[layer][chunk] }
+
+    // iterates over the (col, row, sprite id) of non-empty tiles in a chunk
+    pub(super) fn chunk_tiles(&self, layer: usize, chunk_col: u32, chunk_row: u32)
+                              -> impl Iterator<Item = (u32, u32, u16)> + '_ {
+        let cols = (chunk_col * CHUNK_TILES)..((chunk_col + 1) * CHUNK_TILES).min(self.dims.0);
+        let rows = (chunk_row * CHUNK_TILES)..((chunk_row + 1) * CHUNK_TILES).min(self.dims.1);
+        let tiles = &self.layers[layer];
+        rows.flat_map(move |row| cols.clone().map(move |col| (col, row)))
+            .filter_map(move |(col, row)| tiles[(row * self.dims.0 + col) as usize].map(|id| (col, row, id)))
+    }
+
+    fn index(&self, col: u32, row: u32) -> usize {
+        assert!(col < self.dims.0 && row < self.dims.1, "tile ({}, {}) is outside of the map", col, row);
+        row as usize * self.dims.0 as usize + col as usize
+    }
+}
@@ -12,19 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::asset_id::AppAssetId;
+use super::atlas::{Atlas, ImageCoords, NineSlice};
 use super::geom::Affine;
-use super::render_buffer::{RenderBuffer, Mode};
+use super::render_buffer::RenderBuffer;
+use super::renderer::MeshVertex;
+use super::tile_map::TileMap;
 
-pub fn append_sprite(r: &mut RenderBuffer, affine: &Affine, sprite_id: u16, flash_ratio: f64) {
-    assert!(r.mode == Mode::Sprite);
-
-    let img_coords = r.sprite_atlas.images[&sprite_id];
-    let affine = affine.post_scale(r.dims.pixel_scalar);
+pub fn append_sprite(r: &mut RenderBuffer, affine: &Affine, img_coords: ImageCoords, flash_ratio: f64) {
+    let native_affine = affine.post_scale(r.dims.pixel_scalar);
     let flash_ratio = (flash_ratio as f32).max(0.0).min(1.0);
 
     let pad = (
-        0.5 / affine.mat().col_0().len() as f32,
-        0.5 / affine.mat().col_1().len() as f32,
+        0.5 / native_affine.mat().col_0().len() as f32,
+        0.5 / native_affine.mat().col_1().len() as f32,
     );
 
     let lt = img_coords.lt;
@@ -37,9 +38,7 @@ pub fn append_sprite(r: &mut RenderBuffer, affine: &Affine, sprite_id: u16, flas
     let dst_lb = (dst_lt.0, dst_rb.1);
     let dst_rt = (dst_rb.0, dst_lt.1);
 
-    let affine = affine.post_translate(r.dims.native_pre_pad.0 as f64 - 0.5 * r.dims.native_dims.0 as f64,
-                                       r.dims.native_pre_pad.1 as f64 - 0.5 * r.dims.native_dims.1 as f64)
-                       .post_scale_axes(2.0 / r.dims.native_dims.0 as f64, 2.0 / r.dims.native_dims.1 as f64);
+    let affine = clip_affine(r, affine);
     let aff_lt = affine.apply_f32(dst_lt);
     let aff_rb = affine.apply_f32(dst_rb);
     let aff_lb = affine.apply_f32(dst_lb);
@@ -54,6 +53,107 @@ pub fn append_sprite(r: &mut RenderBuffer, affine: &Affine, sprite_id: u16, flas
     add_sprite_vertex(vbo_data, pad, flash_ratio, rb, aff_rb);
 }
 
+pub fn append_mesh(r: &mut RenderBuffer, affine: &Affine, img_coords: ImageCoords, vertices: &[MeshVertex]) {
+    let affine = clip_affine(r, affine);
+    // clamp sampling to the centers of the transparent padding pixels surrounding the sprite
+    let bounds = [img_coords.lt.0 + 0.5, img_coords.lt.1 + 0.5, img_coords.rb.0 - 0.5, img_coords.rb.1 - 0.5];
+    for vertex in vertices {
+        let dst = affine.apply_f32((vertex.pos.0 as f32, vertex.pos.1 as f32));
+        let src = (img_coords.anchor.0 + vertex.tex.0 as f32, img_coords.anchor.1 - vertex.tex.1 as f32);
+        let alpha = vertex.color.3 as f32 / 255.;
+        r.vbo_data.extend_from_slice(&[dst.0, dst.1, src.0, src.1]);
+        r.vbo_data.extend_from_slice(&bounds);
+        r.vbo_data.extend_from_slice(&[
+            alpha * vertex.color.0 as f32 / 255.,
+            alpha * vertex.color.1 as f32 / 255.,
+            alpha * vertex.color.2 as f32 / 255.,
+            alpha,
+        ]);
+    }
+}
+
+pub fn append_nine_slice(r: &mut RenderBuffer, affine: &Affine, slice: NineSlice, dims: (f64, f64), tile: bool) {
+    if slice.is_empty() { return; }
+    let border = slice.border;
+    let src_x = [slice.lt.0, slice.lt.0 + border, slice.rb.0 - border, slice.rb.0];
+    let src_y = [slice.lt.1, slice.lt.1 + border, slice.rb.1 - border, slice.rb.1];
+    // destination offsets from the left and top edges, shrinking the borders if they do not fit
+    let dst_border = ((border as f64).min(0.5 * dims.0), (border as f64).min(0.5 * dims.1));
+    let dst_x = [0., dst_border.0, dims.0 - dst_border.0, dims.0];
+    let dst_y = [0., dst_border.1, dims.1 - dst_border.1, dims.1];
+    let left_top = affine.pre_translate(-0.5 * dims.0, 0.5 * dims.1);
+    for row in 0..3 {
+        for col in 0..3 {
+            let x_segments = slice_segments((src_x[col], src_x[col + 1]), (dst_x[col], dst_x[col + 1]), tile && col == 1);
+            let y_segments = slice_segments((src_y[row], src_y[row + 1]), (dst_y[row], dst_y[row + 1]), tile && row == 1);
+            for &(sx, dx) in &x_segments {
+                for &(sy, dy) in &y_segments {
+                    let img_coords = ImageCoords { lt: (sx.0, sy.0), rb: (sx.1, sy.1), anchor: (sx.0, sy.0) };
+                    let affine = left_top.pre_translate(dx.0, -dy.0).pre_scale_axes(
+                        (dx.1 - dx.0) / (sx.1 - sx.0) as f64,
+                        (dy.1 - dy.0) / (sy.1 - sy.0) as f64,
+                    );
+                    append_sprite(r, &affine, img_coords, 0.);
+                }
+            }
+        }
+    }
+}
+
+// splits a source and destination span of a nine-slice sprite into pairs of source and destination segments,
+// either stretching the source over the destination or repeating it at its original scale
+fn slice_segments(src: (f32, f32), dst: (f64, f64), tile: bool) -> Vec<((f32, f32), (f64, f64))> {
+    if dst.1 <= dst.0 {
+        Vec::new()
+    } else if tile {
+        let len = (src.1 - src.0) as f64;
+        let count = ((dst.1 - dst.0) / len).ceil() as usize;
+        (0..count).map(|i| {
+            let start = dst.0 + i as f64 * len;
+            let end = (start + len).min(dst.1);
+            ((src.0, src.0 + (end - start) as f32), (start, end))
+        }).collect()
+    } else {
+        vec![(src, dst)]
+    }
+}
+
+// returns the vertices of a chunk of tiles for caching, in map units, with 4 floats per vertex
+pub fn tile_chunk_vertices<A: AppAssetId>(atlas: &Atlas, map: &TileMap<A>, layer: usize,
+                                          chunk_col: u32, chunk_row: u32) -> Vec<f32> {
+    let mut result = Vec::new();
+    let tile_size = map.tile_size();
+    for (col, row, sprite_id) in map.chunk_tiles(layer, chunk_col, chunk_row) {
+        let img_coords = atlas.images[&sprite_id];
+        let center = (((col as f64 + 0.5) * tile_size) as f32, ((row as f64 + 0.5) * tile_size) as f32);
+        let (lt, rb) = (img_coords.lt, img_coords.rb);
+        let dst_lt = (center.0 + lt.0 - img_coords.anchor.0, center.1 - (lt.1 - img_coords.anchor.1));
+        let dst_rb = (center.0 + rb.0 - img_coords.anchor.0, center.1 - (rb.1 - img_coords.anchor.1));
+        let corners = [
+            (lt, dst_lt),
+            ((rb.0, lt.1), (dst_rb.0, dst_lt.1)),
+            ((lt.0, rb.1), (dst_lt.0, dst_rb.1)),
+            ((rb.0, lt.1), (dst_rb.0, dst_lt.1)),
+            ((lt.0, rb.1), (dst_lt.0, dst_rb.1)),
+            (rb, dst_rb),
+        ];
+        for &(src, dst) in &corners {
+            result.extend_from_slice(&[dst.0, dst.1, src.0, src.1]);
+        }
+    }
+    result
+}
+
+// transforms app coordinates to OpenGL clip coordinates
+pub fn clip_affine(r: &RenderBuffer, affine: &Affine) -> Affine {
+    // offscreen targets are rendered upside-down, so their textures are top-down like the sprite atlas
+    let y_sign = if r.flip_y { -1.0 } else { 1.0 };
+    affine.post_scale(r.dims.pixel_scalar)
+          .post_translate(r.dims.native_pre_pad.0 as f64 - 0.5 * r.dims.native_dims.0 as f64,
+                          r.dims.native_pre_pad.1 as f64 - 0.5 * r.dims.native_dims.1 as f64)
+          .post_scale_axes(2.0 / r.dims.native_dims.0 as f64, y_sign * 2.0 / r.dims.native_dims.1 as f64)
+}
+
 fn add_sprite_vertex(vbo_data: &mut Vec<f32>, pad: (f32, f32), flash_ratio: f32, src: (f32, f32), dst: (f32, f32)) {
     vbo_data.push(dst.0);
     vbo_data.push(dst.1);
@@ -0,0 +1,261 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Persistent save data, stored in named slots.
+//!
+//! Save data is accessed through `AppContext::read_save`, `AppContext::write_save`,
+//! and `AppContext::delete_save`.
+//! By default, save data is stored in a per-user data directory when running natively,
+//! and in `localStorage` when running in a web browser.
+//! A different storage can be plugged in using `AppInfo::save_storage`.
+//!
+//! Types implementing `SaveData` can be stored in a small versioned container
+//! using `encode` and `decode`, or directly in the app cookie using `read_cookie` and `write_cookie`.
+//! The container holds a checksum, so corrupted save data is detected,
+//! and outdated save data is passed through `SaveData::migrate`.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use crate::AppContext;
+use crate::app_context::MAX_COOKIE_LEN;
+use crate::asset_id::AppAssetId;
+
+const MAGIC: &[u8] = b"GS";
+const HEADER_LEN: usize = 8;
+
+/// Storage backend for save data, which can be passed into `AppInfo::save_storage`.
+///
+/// Slot names passed to these methods are always valid (see `AppContext::write_save`).
+pub trait SaveStorage {
+    /// Reads the data in `slot`, or returns `None` if the slot is empty.
+    fn read(&self, slot: &str) -> Result<Option<Vec<u8>>, SaveError>;
+
+    /// Writes `data` to `slot`, replacing its contents.
+    ///
+    /// If the write fails, the previous contents of the slot should be left intact.
+    fn write(&mut self, slot: &str, data: &[u8]) -> Result<(), SaveError>;
+
+    /// Deletes the data in `slot`, if any.
+    fn delete(&mut self, slot: &str) -> Result<(), SaveError>;
+}
+
+/// Error reading or writing save data.
+#[derive(Debug)]
+pub enum SaveError {
+    /// There is no persistent storage available, such as when browser storage is disabled.
+    Unavailable,
+    /// The storage failed to read or write data.
+    Io(io::Error),
+    /// Save data is malformed or does not match its checksum.
+    Corrupt,
+    /// Save data has a version that cannot be migrated to the current version.
+    UnsupportedVersion(u16),
+    /// Save data is too large to be stored in the cookie.
+    TooLarge,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Unavailable => write!(f, "save data storage is unavailable"),
+            SaveError::Io(err) => write!(f, "save data I/O error: {}", err),
+            SaveError::Corrupt => write!(f, "save data is corrupt"),
+            SaveError::UnsupportedVersion(version) => write!(f, "unsupported save data version {}", version),
+            SaveError::TooLarge => write!(f, "save data is too large"),
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> SaveError { SaveError::Io(err) }
+}
+
+pub(crate) fn is_valid_slot(slot: &str) -> bool {
+    !slot.is_empty() && slot.len() <= 64
+        && slot.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Save data that can be serialized into a versioned container.
+pub trait SaveData: Sized {
+    /// Current version of the serialized format.
+    ///
+    /// This should be incremented whenever the format changes, along with handling the
+    /// previous version in `migrate`.
+    const VERSION: u16;
+
+    /// Serializes the data in the format of the current version.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Deserializes data in the format of the current version, or returns `None` if it is invalid.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+
+    /// Converts `bytes` serialized in an older `from_version` format to the current version's format.
+    ///
+    /// Returns `None` if the version is not supported, which is the default behavior.
+    fn migrate(_from_version: u16, _bytes: Vec<u8>) -> Option<Vec<u8>> { None }
+}
+
+/// Serializes `data` into a container holding its version and a checksum.
+///
+/// The container adds 8 bytes on top of the serialized data.
+pub fn encode<S: SaveData>(data: &S) -> Vec<u8> {
+    let payload = data.to_bytes();
+    let mut result = vec![0; HEADER_LEN];
+    result[..2].copy_from_slice(MAGIC);
+    LittleEndian::write_u16(&mut result[2..4], S::VERSION);
+    LittleEndian::write_u32(&mut result[4..8], crc32(&payload));
+    result.extend(payload);
+    result
+}
+
+/// Deserializes a container created by `encode`, migrating it if it has an older version.
+pub fn decode<S: SaveData>(bytes: &[u8]) -> Result<S, SaveError> {
+    if bytes.len() < HEADER_LEN || &bytes[..2] != MAGIC {
+        return Err(SaveError::Corrupt);
+    }
+    let version = LittleEndian::read_u16(&bytes[2..4]);
+    let payload = &bytes[HEADER_LEN..];
+    if LittleEndian::read_u32(&bytes[4..8]) != crc32(payload) {
+        return Err(SaveError::Corrupt);
+    }
+    if version == S::VERSION {
+        S::from_bytes(payload).ok_or(SaveError::Corrupt)
+    } else if version < S::VERSION {
+        let payload = S::migrate(version, payload.to_vec()).ok_or(SaveError::UnsupportedVersion(version))?;
+        S::from_bytes(&payload).ok_or(SaveError::Corrupt)
+    } else {
+        Err(SaveError::UnsupportedVersion(version))
+    }
+}
+
+/// Reads save data from the app cookie, or returns `None` if the cookie is not set.
+pub fn read_cookie<A: AppAssetId, S: SaveData>(ctx: &AppContext<A>) -> Result<Option<S>, SaveError> {
+    let cookie = ctx.cookie();
+    if cookie.is_empty() { Ok(None) } else { decode(cookie).map(Some) }
+}
+
+/// Writes save data to the app cookie.
+///
+/// Returns `SaveError::TooLarge` if the encoded data does not fit within the cookie size limit
+/// (see `AppContext::set_cookie`).
+pub fn write_cookie<A: AppAssetId, S: SaveData>(ctx: &mut AppContext<A>, data: &S) -> Result<(), SaveError> {
+    let bytes = encode(data);
+    if bytes.len() >= MAX_COOKIE_LEN { return Err(SaveError::TooLarge); }
+    ctx.set_cookie(bytes);
+    Ok(())
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Progress { level: u8, score: u32 }
+
+    impl SaveData for Progress {
+        const VERSION: u16 = 2;
+
+        fn to_bytes(&self) -> Vec<u8> {
+            let mut bytes = vec![self.level, 0, 0, 0, 0];
+            LittleEndian::write_u32(&mut bytes[1..], self.score);
+            bytes
+        }
+
+        fn from_bytes(bytes: &[u8]) -> Option<Self> {
+            if bytes.len() != 5 { return None; }
+            Some(Progress { level: bytes[0], score: LittleEndian::read_u32(&bytes[1..]) })
+        }
+
+        fn migrate(from_version: u16, bytes: Vec<u8>) -> Option<Vec<u8>> {
+            // version 1 only stored the level
+            if from_version == 1 && bytes.len() == 1 { Some(vec![bytes[0], 0, 0, 0, 0]) } else { None }
+        }
+    }
+
+    fn with_version(mut bytes: Vec<u8>, version: u16) -> Vec<u8> {
+        LittleEndian::write_u16(&mut bytes[2..4], version);
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = Progress { level: 3, score: 123_456 };
+        let bytes = encode(&data);
+        assert_eq!(bytes.len(), HEADER_LEN + 5);
+        assert_eq!(decode::<Progress>(&bytes).unwrap(), data);
+    }
+
+    #[test]
+    fn corrupted_byte() {
+        let mut bytes = encode(&Progress { level: 3, score: 123_456 });
+        bytes[HEADER_LEN + 2] ^= 0x10;
+        assert!(matches!(decode::<Progress>(&bytes), Err(SaveError::Corrupt)));
+    }
+
+    #[test]
+    fn wrong_magic() {
+        let mut bytes = encode(&Progress { level: 3, score: 123_456 });
+        bytes[0] = b'X';
+        assert!(matches!(decode::<Progress>(&bytes), Err(SaveError::Corrupt)));
+        assert!(matches!(decode::<Progress>(&bytes[..HEADER_LEN - 1]), Err(SaveError::Corrupt)));
+    }
+
+    #[test]
+    fn wrong_version() {
+        let bytes = encode(&Progress { level: 3, score: 123_456 });
+        let newer = with_version(bytes.clone(), 3);
+        assert!(matches!(decode::<Progress>(&newer), Err(SaveError::UnsupportedVersion(3))));
+        let older = with_version(bytes, 1);
+        assert!(matches!(decode::<Progress>(&older), Err(SaveError::UnsupportedVersion(1))));
+    }
+
+    #[test]
+    fn migrated_version() {
+        let mut bytes = with_version(encode(&Progress { level: 3, score: 0 }), 1);
+        bytes.truncate(HEADER_LEN + 1);
+        LittleEndian::write_u32(&mut bytes[4..8], crc32(&[3]));
+        assert_eq!(decode::<Progress>(&bytes).unwrap(), Progress { level: 3, score: 0 });
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+}
@@ -0,0 +1,255 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+//! Contains structs for loading maps made with the Tiled map editor.
+//!
+//! Maps are packed by `AssetPacker::tiled_maps` in "gate_build",
+//! generating handles in the `MapId` enum.
+//! Map coordinates have the origin at the bottom-left corner of the map, with Y pointing up,
+//! and are measured in the same units as the tile sprites.
+
+use std::io::{self, Read};
+use std::collections::HashMap;
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::asset_id::{AppAssetId, IdU16, MapAsset};
+use crate::renderer::TileMap;
+
+/// A map made with the Tiled map editor, containing tile layers and object layers.
+///
+/// The tile layers are loaded into a `TileMap`, to be drawn in "tile mode".
+pub struct TiledMap<A: AppAssetId> {
+    tiles: TileMap<A>,
+    tile_layer_names: Vec<String>,
+    object_layers: Vec<ObjectLayer>,
+}
+
+impl<A: AppAssetId> TiledMap<A> {
+    /// Loads the packed map referenced by `map`.
+    pub fn new(map: A::Map) -> TiledMap<A> {
+        TiledMap::read(map.data()).expect("failed to read packed map")
+    }
+
+    /// Returns the tile layers of the map.
+    pub fn tiles(&self) -> &TileMap<A> { &self.tiles }
+
+    /// Returns the tile layers of the map, which may be modified.
+    pub fn tiles_mut(&mut self) -> &mut TileMap<A> { &mut self.tiles }
+
+    /// Returns the index of the tile layer named `name` in `self.tiles()`, if any.
+    pub fn tile_layer(&self, name: &str) -> Option<usize> {
+        self.tile_layer_names.iter().position(|n| n == name)
+    }
+
+    /// Returns all object layers, in the order they appear in the map.
+    pub fn object_layers(&self) -> &[ObjectLayer] { &self.object_layers }
+
+    /// Returns the first object layer named `name`, if any.
+    pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.object_layers.iter().find(|l| l.name == name)
+    }
+
+    /// Iterates over the objects of all object layers.
+    pub fn objects(&self) -> impl Iterator<Item = &MapObject> {
+        self.object_layers.iter().flat_map(|l| l.objects.iter())
+    }
+
+    fn read(mut input: &[u8]) -> io::Result<TiledMap<A>> {
+        let input = &mut input;
+        let (cols, rows) = (input.read_u32::<BigEndian>()?, input.read_u32::<BigEndian>()?);
+        let tile_size = input.read_u32::<BigEndian>()? as f64;
+
+        let tile_layer_count = input.read_u16::<BigEndian>()? as usize;
+        let mut tiles = TileMap::new(cols, rows, tile_layer_count.max(1), tile_size);
+        let mut tile_layer_names = Vec::with_capacity(tile_layer_count);
+        for layer in 0..tile_layer_count {
+            tile_layer_names.push(read_str(input)?);
+            for row in 0..rows {
+                for col in 0..cols {
+                    let sprite = match input.read_u16::<BigEndian>()? {
+                        0 => None,
+                        id => Some(A::Sprite::from_u16(id - 1).ok_or(io::ErrorKind::InvalidData)?),
+                    };
+                    tiles.set_tile(layer, col, row, sprite);
+                }
+            }
+        }
+
+        let object_layer_count = input.read_u16::<BigEndian>()?;
+        let mut object_layers = Vec::with_capacity(object_layer_count as usize);
+        for _ in 0..object_layer_count {
+            let name = read_str(input)?;
+            let object_count = input.read_u32::<BigEndian>()?;
+            let objects = (0..object_count).map(|_| MapObject::read(input)).collect::<io::Result<_>>()?;
+            object_layers.push(ObjectLayer { name, objects });
+        }
+
+        Ok(TiledMap { tiles, tile_layer_names, object_layers })
+    }
+}
+
+/// A layer of objects in a `TiledMap`, such as spawn points or triggers.
+#[derive(Clone, Debug)]
+pub struct ObjectLayer {
+    /// Name of the layer.
+    pub name: String,
+    /// Objects in the layer, in the order they appear in the map.
+    pub objects: Vec<MapObject>,
+}
+
+impl ObjectLayer {
+    /// Returns the first object named `name`, if any.
+    pub fn object(&self, name: &str) -> Option<&MapObject> {
+        self.objects.iter().find(|o| o.name == name)
+    }
+}
+
+/// An object in a `TiledMap`.
+#[derive(Clone, Debug)]
+pub struct MapObject {
+    /// Unique ID of the object within the map.
+    pub id: u32,
+    /// Name of the object, which may be empty.
+    pub name: String,
+    /// Class (formerly "type") of the object, which may be empty.
+    pub class: String,
+    /// Shape of the object.
+    pub shape: ObjectShape,
+    /// Bottom-left corner of the object's bounds in map coordinates.
+    ///
+    /// For a point object, this is the position of the point.
+    pub pos: (f64, f64),
+    /// Width and height of the object's bounds.
+    pub dims: (f64, f64),
+    /// Custom properties of the object.
+    pub properties: HashMap<String, Property>,
+}
+
+impl MapObject {
+    /// Returns the center of the object's bounds in map coordinates.
+    pub fn center(&self) -> (f64, f64) {
+        (self.pos.0 + 0.5 * self.dims.0, self.pos.1 + 0.5 * self.dims.1)
+    }
+
+    /// Returns true if the given position in map coordinates is inside the object's bounds.
+    pub fn bounds_contain(&self, x: f64, y: f64) -> bool {
+        x >= self.pos.0 && y >= self.pos.1 && x <= self.pos.0 + self.dims.0 && y <= self.pos.1 + self.dims.1
+    }
+
+    /// Returns the custom property named `name`, if any.
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.get(name)
+    }
+
+    fn read(input: &mut &[u8]) -> io::Result<MapObject> {
+        let id = input.read_u32::<BigEndian>()?;
+        let name = read_str(input)?;
+        let class = read_str(input)?;
+        let shape_id = input.read_u8()?;
+        let pos = (input.read_f64::<BigEndian>()?, input.read_f64::<BigEndian>()?);
+        let dims = (input.read_f64::<BigEndian>()?, input.read_f64::<BigEndian>()?);
+        let shape = match shape_id {
+            0 => ObjectShape::Point,
+            1 => ObjectShape::Rect,
+            2 => ObjectShape::Ellipse,
+            3 => ObjectShape::Polygon(read_points(input)?),
+            4 => ObjectShape::Polyline(read_points(input)?),
+            _ => return Err(io::ErrorKind::InvalidData.into()),
+        };
+        let property_count = input.read_u16::<BigEndian>()?;
+        let mut properties = HashMap::with_capacity(property_count as usize);
+        for _ in 0..property_count {
+            let name = read_str(input)?;
+            let value = match input.read_u8()? {
+                0 => Property::Bool(input.read_u8()? != 0),
+                1 => Property::Int(input.read_i64::<BigEndian>()?),
+                2 => Property::Float(input.read_f64::<BigEndian>()?),
+                3 => Property::String(read_str(input)?),
+                _ => return Err(io::ErrorKind::InvalidData.into()),
+            };
+            properties.insert(name, value);
+        }
+        Ok(MapObject { id, name, class, shape, pos, dims, properties })
+    }
+}
+
+/// Shape of a `MapObject`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjectShape {
+    /// A single point, such as a spawn point.
+    Point,
+    /// A rectangle filling the object's bounds.
+    Rect,
+    /// An ellipse filling the object's bounds.
+    Ellipse,
+    /// A closed polygon, with vertices in map coordinates.
+    Polygon(Vec<(f64, f64)>),
+    /// An open sequence of line segments, with vertices in map coordinates.
+    Polyline(Vec<(f64, f64)>),
+}
+
+/// Value of a custom property of a `MapObject`.
+///
+/// Color and file properties are stored as strings, and object properties as the ID of the object.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    /// A boolean property.
+    Bool(bool),
+    /// An integer or object property.
+    Int(i64),
+    /// A floating point property.
+    Float(f64),
+    /// A string, color, or file property.
+    String(String),
+}
+
+impl Property {
+    /// Returns the value if this is a `Bool` property.
+    pub fn as_bool(&self) -> Option<bool> {
+        if let Property::Bool(value) = *self { Some(value) } else { None }
+    }
+
+    /// Returns the value if this is an `Int` property.
+    pub fn as_int(&self) -> Option<i64> {
+        if let Property::Int(value) = *self { Some(value) } else { None }
+    }
+
+    /// Returns the value if this is a `Float` or `Int` property.
+    pub fn as_float(&self) -> Option<f64> {
+        match *self {
+            Property::Float(value) => Some(value),
+            Property::Int(value) => Some(value as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if this is a `String` property.
+    pub fn as_str(&self) -> Option<&str> {
+        if let Property::String(ref value) = *self { Some(value) } else { None }
+    }
+}
+
+fn read_str(input: &mut &[u8]) -> io::Result<String> {
+    let len = input.read_u16::<BigEndian>()? as usize;
+    let mut bytes = vec![0; len];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|_| io::ErrorKind::InvalidData.into())
+}
+
+fn read_points(input: &mut &[u8]) -> io::Result<Vec<(f64, f64)>> {
+    let count = input.read_u32::<BigEndian>()?;
+    (0..count).map(|_| Ok((input.read_f64::<BigEndian>()?, input.read_f64::<BigEndian>()?))).collect()
+}
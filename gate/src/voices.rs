@@ -0,0 +1,110 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Range;
+
+use crate::asset_id::SoundSettings;
+use crate::core::CoreAudio;
+
+#[derive(Copy, Clone)]
+struct Voice { priority: u8, start: f64, sound: u16 }
+
+// Assigns sounds to a fixed set of voices (audio channels), enforcing the per-sound limits
+// and channel groups declared in the build script.
+// Group channels come first, and the remaining channels are for sounds without a group.
+pub(crate) struct Voices {
+    voices: Vec<Option<Voice>>,
+    group_ranges: Vec<Range<usize>>,
+    ungrouped_range: Range<usize>,
+    last_started: Vec<Option<f64>>,
+    time: f64,
+}
+
+impl Voices {
+    pub fn new(voice_count: u16, group_channels: &[u16], sound_count: u16) -> Voices {
+        let mut group_ranges = Vec::with_capacity(group_channels.len());
+        let mut start = 0;
+        for &channels in group_channels {
+            group_ranges.push(start..(start + channels as usize));
+            start += channels as usize;
+        }
+        // groups may reserve every channel, in which case sounds without a group are not played
+        assert!(start <= voice_count as usize,
+                "sound groups use {} channels, but only {} audio channels are available", start, voice_count);
+        Voices {
+            voices: vec![None; voice_count as usize],
+            group_ranges,
+            ungrouped_range: start..(voice_count as usize),
+            last_started: vec![None; sound_count as usize],
+            time: 0.,
+        }
+    }
+
+    pub fn advance(&mut self, seconds: f64) { self.time += seconds; }
+
+    pub fn play(&mut self, core: &mut CoreAudio, sound: u16, settings: SoundSettings) {
+        if let Some(last_started) = self.last_started[sound as usize] {
+            if self.time - last_started < settings.cooldown { return; }
+        }
+        self.clear_finished(core);
+
+        let range = self.range(settings.group);
+        let instances = range.clone().filter(|&idx| self.voices[idx].is_some_and(|v| v.sound == sound));
+        let voice = if settings.max_instances.is_some_and(|max| instances.clone().count() >= max as usize) {
+            self.oldest(instances)
+        } else if let Some(idx) = range.clone().find(|&idx| self.voices[idx].is_none()) {
+            Some(idx)
+        } else {
+            let candidates = range.filter(|&idx| self.voices[idx].is_some_and(|v| v.priority <= settings.priority));
+            let lowest = candidates.clone().filter_map(|idx| self.voices[idx]).map(|v| v.priority).min();
+            self.oldest(candidates.filter(|&idx| self.voices[idx].map(|v| v.priority) == lowest))
+        };
+
+        if let Some(voice) = voice {
+            core.play_sound(sound, voice);
+            self.voices[voice] = Some(Voice { priority: settings.priority, start: self.time, sound });
+            self.last_started[sound as usize] = Some(self.time);
+        }
+    }
+
+    pub fn stop_group(&mut self, core: &mut CoreAudio, group: u16) {
+        for idx in self.group_ranges[group as usize].clone() {
+            if self.voices[idx].take().is_some() {
+                core.stop_voice(idx);
+            }
+        }
+    }
+
+    fn range(&self, group: Option<u16>) -> Range<usize> {
+        match group {
+            Some(group) => self.group_ranges[group as usize].clone(),
+            None => self.ungrouped_range.clone(),
+        }
+    }
+
+    fn oldest<I: Iterator<Item = usize>>(&self, voices: I) -> Option<usize> {
+        voices.min_by(|&a, &b| {
+            let (a, b) = (self.voices[a].unwrap().start, self.voices[b].unwrap().start);
+            a.partial_cmp(&b).unwrap()
+        })
+    }
+
+    fn clear_finished(&mut self, core: &CoreAudio) {
+        for (idx, voice) in self.voices.iter_mut().enumerate() {
+            if voice.is_some() && !core.is_voice_playing(idx) {
+                *voice = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+#[allow(dead_code)]
+impl {0} {{
+    /// Returns the sprite and duration in seconds of each frame of the animation.
+    pub fn frames(self) -> &'static [(SpriteId, f64)] {{
+        const FRAMES: [&[(SpriteId, f64)]; {2}] = [
+{1}        ];
+        FRAMES[self.id_u16() as usize]
+    }}
+
+    /// Returns the duration in seconds of one loop of the animation.
+    pub fn duration(self) -> f64 {{
+        self.frames().iter().map(|f| f.1).sum()
+    }}
+
+    /// Returns the sprite to draw `time` seconds after the animation started, looping forever.
+    pub fn frame_at(self, time: f64) -> SpriteId {{
+        let frames = self.frames();
+        let total = self.duration();
+        if total <= 0. {{ return frames[0].0; }}
+        let mut time = time.rem_euclid(total);
+        for &(sprite, duration) in frames {{
+            if time < duration {{ return sprite; }}
+            time -= duration;
+        }}
+        frames[frames.len() - 1].0
+    }}
+}}
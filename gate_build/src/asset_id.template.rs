@@ -21,9 +21,17 @@ impl AppAssetId for AssetId {{
     type Sprite = SpriteId;
     type Music = MusicId;
     type Sound = SoundId;
-}}
+    type SoundGroup = SoundGroupId;
+    type Map = MapId;
+{}{}}}
 
 
+{}
+
+{}
+
+{}
+
 {}
 
 {}
@@ -16,8 +16,12 @@ use std::path::{Path, PathBuf};
 use std::fs::{self, File};
 use std::io::{self, Write};
 use std::ffi::OsStr;
+use std::mem;
 
-use crate::atlas::form_atlas;
+use crate::atlas::{read_images, write_atlas, TextureFilter};
+use crate::hot_reload::SpriteSources;
+use crate::sprite_sheet::Animation;
+use crate::tiled::{self, TiledMaps};
 use crate::html;
 use crate::rerun_print;
 
@@ -33,28 +37,65 @@ pub struct AssetPacker {
     assets_dir: PathBuf,
     check_rerun: bool,
     mp3_fallback: bool,
+    sprite_filter: Option<TextureFilter>,
+    collision_cell_size: Option<u32>,
+    hot_reload: bool,
+    embed_assets: bool,
+    loading_sprites: Vec<String>,
     sprites: Option<Vec<String>>,
+    anims: Vec<Animation>,
+    tiled_maps: Option<TiledMaps>,
+    tiled_maps_dir: Option<PathBuf>,
     music: Option<Vec<String>>,
+    music_formats: Vec<&'static str>,
     sounds: Option<Vec<String>>,
+    sound_formats: Vec<&'static str>,
+    sound_settings: Vec<SoundSettings>,
+    sound_groups: Vec<(String, u16)>,
     js: bool,
 }
 
+// mirrors `gate::asset_id::SoundSettings`
+#[derive(Copy, Clone)]
+struct SoundSettings {
+    group: Option<usize>,
+    max_instances: Option<u16>,
+    cooldown: f64,
+    priority: u8,
+}
+
+// (file extension, name of the matching `gate::asset_id::AudioFormat` variant)
+const AUDIO_FORMATS: [(&str, &str); 3] = [("ogg", "Ogg"), ("wav", "Wav"), ("flac", "Flac")];
+
 impl AssetPacker {
     /// Constructs a new `AssetPacker` where packed asset files will be written to `assets_dir`.
     ///
-    /// Except when building to WASM,
-    /// the `assets_dir` must be a directory named "assets" relative to
-    /// the current directory used when the Gate application is run.
-    /// This is usually in the base directory of the Rust project.
+    /// Except when building to WASM, the Gate application loads the packed assets
+    /// from a directory at runtime, unless they are embedded with `embed_assets`.
+    /// By default, that is a directory named "assets" next to the executable or in the current directory
+    /// (see `AppInfo::assets_dir` in "gate"),
+    /// so `assets_dir` is usually an "assets" directory in the base directory of the Rust project.
     pub fn new(assets_dir: &Path) -> AssetPacker {
         fs::create_dir_all(assets_dir).expect("failed to create assets directory");
         AssetPacker {
             assets_dir: assets_dir.to_path_buf(),
             sprites: None,
+            anims: Vec::new(),
             check_rerun: false,
             mp3_fallback: false,
+            sprite_filter: None,
+            collision_cell_size: None,
+            hot_reload: false,
+            embed_assets: false,
+            loading_sprites: Vec::new(),
+            tiled_maps: None,
+            tiled_maps_dir: None,
             music: None,
+            music_formats: Vec::new(),
             sounds: None,
+            sound_formats: Vec::new(),
+            sound_settings: Vec::new(),
+            sound_groups: Vec::new(),
             js: false,
         }
     }
@@ -67,12 +108,12 @@ impl AssetPacker {
     ///
     /// Panics if called after calling methods to pack assets.
     pub fn cargo_rerun_if_changed(&mut self) {
-        assert!(self.sprites.is_none() && self.music.is_none() && self.sounds.is_none(),
+        assert!(self.sprites.is_none() && self.tiled_maps.is_none() && self.music.is_none() && self.sounds.is_none(),
                 "cannot add rerun checks after asset packing has already started");
         self.check_rerun = true;
     }
 
-    /// Invoke this method to copy `.mp3` files along with `.ogg` files for audio.
+    /// Invoke this method to copy `.mp3` files along with `.ogg`, `.wav`, and `.flac` files for audio.
     ///
     /// This is useful when compiling to the WASM target architecture.
     /// The Safari browser does not support the `.ogg` file format,
@@ -85,6 +126,70 @@ impl AssetPacker {
         self.mp3_fallback = true;
     }
 
+    /// Specifies the texture filter for the sprite atlas,
+    /// overriding the filter specified in the app's `AppInfo`.
+    ///
+    /// Panics if called after packing sprites.
+    pub fn sprite_filter(&mut self, filter: TextureFilter) {
+        assert!(self.sprites.is_none(), "cannot set sprite filter after sprites have already been packed");
+        self.sprite_filter = Some(filter);
+    }
+
+    /// Generates collision data for each sprite from the alpha channel of its image,
+    /// to be accessed at runtime with `AppContext::sprite_collider` and tested with `gate::collision`.
+    ///
+    /// Pixels with non-zero alpha are considered solid.
+    /// The collision data of a sprite consists of its trimmed bounds, the convex hull of its solid pixels,
+    /// and a bitmask with one bit per `cell_size` by `cell_size` block of pixels,
+    /// which is set if any pixel in the block is solid.
+    /// A `cell_size` of 1 gives pixel-perfect masks, while larger cell sizes use less memory.
+    /// The collision data of nine-slice sprites matches their image before it is resized.
+    ///
+    /// Panics if called after packing sprites, or if `cell_size` is not between 1 and 255.
+    pub fn collision_masks(&mut self, cell_size: u32) {
+        assert!(self.sprites.is_none(), "cannot generate collision masks after sprites have already been packed");
+        assert!((1..=255).contains(&cell_size), "collision mask cell size must be between 1 and 255");
+        self.collision_cell_size = Some(cell_size);
+    }
+
+    /// Records the source directories and settings of the sprite atlas in the assets directory,
+    /// so that a debug build of the app can re-pack the sprites while it is running
+    /// when the "hot_reload" feature of "gate" is enabled (see `gate_build::hot_reload`).
+    ///
+    /// The recorded directories are absolute paths on the build machine,
+    /// so this should only be invoked for debug builds, e.g. when the `PROFILE` environment variable is "debug".
+    ///
+    /// Panics if called after packing sprites.
+    pub fn hot_reload(&mut self) {
+        assert!(self.sprites.is_none(), "cannot enable hot reloading after sprites have already been packed");
+        self.hot_reload = true;
+    }
+
+    /// Embeds the packed asset files in the generated code with `include_bytes!`,
+    /// so that a native build of the app does not need to load them from the assets directory at runtime.
+    ///
+    /// This has no effect when compiling to WASM, since assets are loaded by the web page.
+    pub fn embed_assets(&mut self) { self.embed_assets = true; }
+
+    /// Packs the sprites with the given generated `names` into a small separate atlas as well,
+    /// so that the app can show a loading screen drawn with these sprites
+    /// while the rest of the assets are loading.
+    ///
+    /// When this is invoked, the Gate application is created as soon as this loading atlas is loaded,
+    /// and the main sprite atlas and sounds are loaded while the app is running
+    /// (see `AppContext::load_progress` in "gate").
+    /// Until then, sprites other than these are drawn as empty.
+    /// Collision data is not generated for the loading atlas.
+    ///
+    /// Panics if called after packing sprites or generating javascript,
+    /// or later on when packing sprites if any of the `names` is not a sprite.
+    pub fn loading_sprites(&mut self, names: &[&str]) {
+        assert!(self.sprites.is_none(), "cannot set loading sprites after sprites have already been packed");
+        assert!(!self.js, "cannot set loading sprites after javascript has already been generated");
+        assert!(!names.is_empty(), "must specify at least one loading sprite");
+        self.loading_sprites = names.iter().map(|&name| name.to_owned()).collect();
+    }
+
     /// Packs sprite images into an atlas, to be rendered by Gate renderer in "sprite" mode.
     ///
     /// Image `.png` files are read from `in_dir`,
@@ -94,6 +199,16 @@ impl AssetPacker {
     ///
     /// The center of the image is used as the anchor point.
     /// If the width or height is odd, then the center of the image is between pixels.
+    /// The anchor is the point that sprites are positioned, rotated, and scaled about when drawn.
+    ///
+    /// If an image filename ends with "_p#x#", where the #s are numbers,
+    /// then it will use the point # pixels from the left and # pixels from the top
+    /// of the untrimmed image as the anchor point instead,
+    /// e.g. "Sword_p3x14.png" for a sword rotated about its hilt.
+    /// The numbers may have a fractional part, which is rounded to the nearest half pixel.
+    /// The suffix is omitted from the generated name,
+    /// and may be combined with the "_t#" suffix below, applying the anchor to every tile,
+    /// in which case the anchor suffix comes last, e.g. "Tiles_t16_p8x16.png".
     /// Transparent pixels padded around the image rectangle will be stripped before packing,
     /// so there is no need to worry about efficiency in regards to padded pixels.
     ///
@@ -104,40 +219,158 @@ impl AssetPacker {
     /// referencing the row and column number.
     /// Empty tiles will be omitted.
     ///
+    /// If any image filename ends with "_9s#", where # is a number,
+    /// then it will interpret that image as a nine-slice sprite with borders # pixels wide,
+    /// which can be drawn resized to any dimensions using `FlashRenderer::draw_nine_slice`.
+    /// The suffix is omitted from the generated name, and the image is not trimmed.
+    ///
+    /// If an image has a `.json` file with the same name next to it,
+    /// then it is read as a sprite sheet exported by Aseprite or TexturePacker,
+    /// in either the JSON hash or JSON array format.
+    /// Rotated and trimmed frames are supported.
+    /// TexturePacker frames are named after their original image files, converted to CamelCase
+    /// (e.g. "items/iron_sword.png" becomes "ItemsIronSword"),
+    /// while Aseprite frames are named after the sheet, suffixed with "F#" for the frame index.
+    /// Pivot points of TexturePacker frames and of Aseprite slices are used as sprite anchors.
+    /// Each Aseprite tag generates a handle in the `AnimId` enum,
+    /// named after the sheet followed by the CamelCase tag name, with the frame durations of the tag.
+    /// An Aseprite sheet without tags generates a single `AnimId` handle named after the sheet.
+    ///
     /// Note: in the current implementation of `gate` there is only one atlas for sprites,
     /// but in the future there are plans to allow for larger games that may need
     /// multiple atlases loaded at different times.
     pub fn sprites(&mut self, in_dir: &Path) -> &[String] {
         assert!(self.sprites.is_none(), "self.sprites(...) was already invoked");
         let output = &self.assets_dir.join("sprites");
-        self.sprites = Some(form_atlas(in_dir, output, 1, self.check_rerun));
+        let tile_images = self.tiled_maps.as_mut().map(|m| mem::take(&mut m.tile_images)).unwrap_or_default();
+        let (images, anims) = read_images(in_dir, tile_images, self.check_rerun);
+        if !self.loading_sprites.is_empty() {
+            for name in &self.loading_sprites {
+                assert!(images.iter().any(|image| &image.name == name), "loading sprite {} was not found", name);
+            }
+            let loading_images = images.iter()
+                .filter(|image| self.loading_sprites.contains(&image.name))
+                .cloned()
+                .collect();
+            write_atlas(loading_images, &self.assets_dir.join("loading_sprites"), 1, self.sprite_filter,
+                        None, self.check_rerun);
+        }
+        let names = write_atlas(images, output, 1, self.sprite_filter, self.collision_cell_size, self.check_rerun);
+        if self.hot_reload {
+            let tileset_files = self.tiled_maps.as_ref().map(|m| m.tileset_files.as_slice()).unwrap_or(&[]);
+            SpriteSources::new(in_dir, self.tiled_maps_dir.as_deref(), tileset_files, self.sprite_filter,
+                               self.collision_cell_size, names.clone())
+                .write(&self.assets_dir).expect("failed to record sprite sources");
+        }
+        self.sprites = Some(names);
+        self.anims = anims;
         self.sprites.as_ref().unwrap()
     }
 
+    /// Reads maps made with the Tiled map editor (see <https://www.mapeditor.org/>),
+    /// to be loaded at runtime as a `gate::tiled::TiledMap`.
+    ///
+    /// Map `.tmx` (XML) and `.tmj` (JSON) files are read from `in_dir`,
+    /// generating handles in the `MapId` enum with the same names as the map files.
+    /// Returns the list of these handles indexed by ID,
+    /// in the same order that they appear in the generated enum code.
+    ///
+    /// Maps must be orthogonal and finite, with square tiles and tile layer data in CSV format.
+    /// Tilesets may be embedded in the map or saved in separate `.tsx` or `.tsj` files,
+    /// and must each be based on a single image.
+    /// The tiles of each tileset are packed into the sprite atlas along with the other sprites,
+    /// with generated names made from the tileset name suffixed with "R#C#",
+    /// referencing the row and column of the tile in the tileset image.
+    /// Tiles that are flipped or rotated in the map are not supported.
+    /// Object layers are kept, including the custom properties of each object,
+    /// but rotated objects are not supported.
+    ///
+    /// The packed maps are embedded in the generated code.
+    ///
+    /// Panics if called after packing sprites.
+    pub fn tiled_maps(&mut self, in_dir: &Path) -> &[String] {
+        assert!(self.tiled_maps.is_none(), "self.tiled_maps(...) was already invoked");
+        assert!(self.sprites.is_none(), "cannot read Tiled maps after sprites have already been packed");
+        self.tiled_maps = Some(tiled::read_maps(in_dir, self.check_rerun));
+        self.tiled_maps_dir = Some(in_dir.to_path_buf());
+        &self.tiled_maps.as_ref().unwrap().names
+    }
+
     /// Creates handles for and copies music files from `in_dir` to the assets directory.
     ///
-    /// Music files are expected to be in `.ogg` format,
+    /// Music files are expected to be in `.ogg`, `.wav`, or `.flac` format,
     /// generating enum handles with the same names as the audio files.
+    /// Files are copied as-is, keeping their format,
+    /// and the format of each file is recorded in the generated enum code.
     /// Returns the list of these handles indexed by ID,
     /// in the same order that they appear in the generated enum code.
     pub fn music(&mut self, in_dir: &Path) -> &[String] {
         assert!(self.music.is_none(), "self.music(...) was already invoked");
-        self.music = Some(enumerate_audio(in_dir, &self.assets_dir, "music", self.mp3_fallback, self.check_rerun));
+        let (names, formats) = enumerate_audio(in_dir, &self.assets_dir, "music", self.mp3_fallback, self.check_rerun);
+        self.music = Some(names);
+        self.music_formats = formats;
         self.music.as_ref().unwrap()
     }
 
     /// Creates handles for and copies sound files from `in_dir` to the assets directory.
     ///
-    /// Music files are expected to be in `.ogg` format,
+    /// Sound files are expected to be in `.ogg`, `.wav`, or `.flac` format,
     /// generating enum handles with the same names as the audio files.
+    /// Files are copied as-is, keeping their format,
+    /// and the format of each file is recorded in the generated enum code.
     /// Returns the list of these handles indexed by ID,
     /// in the same order that they appear in the generated enum code.
     pub fn sounds(&mut self, in_dir: &Path) -> &[String] {
         assert!(self.sounds.is_none(), "self.sounds(...) was already invoked");
-        self.sounds = Some(enumerate_audio(in_dir, &self.assets_dir, "sound", self.mp3_fallback, self.check_rerun));
+        let (names, formats) = enumerate_audio(in_dir, &self.assets_dir, "sound", self.mp3_fallback, self.check_rerun);
+        let default_settings = SoundSettings { group: None, max_instances: None, cooldown: 0., priority: 0 };
+        self.sound_settings = vec![default_settings; names.len()];
+        self.sounds = Some(names);
+        self.sound_formats = formats;
         self.sounds.as_ref().unwrap()
     }
 
+    /// Declares a sound group named `name`, generating a handle for it in the `SoundGroupId` enum.
+    ///
+    /// Sounds in a group only play on the `channels` audio channels reserved for that group,
+    /// and sounds without a group only play on the remaining channels (if any remain).
+    /// This can be used to ensure that, for example, user interface sounds are never cut off
+    /// by a flood of in-game sounds.
+    /// `sounds` are the names of the sounds in the group, as returned by `self.sounds(...)`.
+    /// A sound may belong to at most one group.
+    ///
+    /// Panics if called before `self.sounds(...)`.
+    pub fn sound_group(&mut self, name: &str, channels: u16, sounds: &[&str]) {
+        assert!(channels > 0, "sound group {} must have at least one channel", name);
+        assert!(self.sound_groups.iter().all(|g| g.0 != name), "sound group {} was already declared", name);
+        let group = self.sound_groups.len();
+        for &sound in sounds {
+            let settings = &mut self.sound_settings[sound_index(&self.sounds, sound)];
+            assert!(settings.group.is_none(), "sound {} already belongs to a group", sound);
+            settings.group = Some(group);
+        }
+        self.sound_groups.push((name.to_owned(), channels));
+    }
+
+    /// Limits how the sound named `sound` (as returned by `self.sounds(...)`) is played.
+    ///
+    /// At most `max_instances` of the sound will play at once,
+    /// with the oldest instance being cut off if the sound is played again.
+    /// The sound will not be played if `cooldown` seconds have not passed since it was last played.
+    /// When no audio channels are free, a playing sound with lower or equal `priority` is cut off
+    /// to make room for this sound.
+    /// Sounds without declared limits have unlimited instances, no cooldown, and priority `0`.
+    ///
+    /// Panics if called before `self.sounds(...)`.
+    pub fn sound_limits(&mut self, sound: &str, max_instances: u16, cooldown: f64, priority: u8) {
+        assert!(max_instances > 0, "max_instances must be positive");
+        assert!(cooldown >= 0., "cooldown must not be negative");
+        let settings = &mut self.sound_settings[sound_index(&self.sounds, sound)];
+        settings.max_instances = Some(max_instances);
+        settings.cooldown = cooldown;
+        settings.priority = priority;
+    }
+
     /// Creates a "gate.js" and "index.html" file in the assets directory for use with the
     /// WASM target architecture.
     ///
@@ -148,6 +381,8 @@ impl AssetPacker {
     /// Specifically, copy the file "target/wasm32-unknown-unknown/release/my_app.wasm"
     /// to "my_assets_dir/gate_app.wasm".
     /// You will also need a copy of "howler.js" (see <https://howlerjs.com/>).
+    ///
+    /// If `self.loading_sprites(...)` is used, it must be invoked before this.
     pub fn gen_javascript_and_html(&mut self) {
         self.gen_javascript();
         create_file(&self.assets_dir, "index.html", html::INDEX_HTML, self.check_rerun);
@@ -161,13 +396,14 @@ impl AssetPacker {
     pub fn gen_javascript(&mut self) {
         assert!(!self.js, "javascript has already been generated");
         self.js = true;
-        create_file(&self.assets_dir, "gate.js", html::GATE_JS, self.check_rerun);
+        let gate_js = html::gate_js(!self.loading_sprites.is_empty());
+        create_file(&self.assets_dir, "gate.js", &gate_js, self.check_rerun);
     }
 
     /// Generates Rust enums to use as handles for all of the packed assets.
     ///
-    /// The generated code will consist of four enums:
-    /// `SpriteId`, `MusicId`, and `SoundId`.
+    /// The generated code will consist of six enums:
+    /// `SpriteId`, `MusicId`, `SoundId`, `SoundGroupId`, `MapId`, and `AnimId`.
     /// These types are collected together in the type `AssetId`,
     /// which implements `gate::asset_id::AppAssetId`.
     /// Constructing a `gate::App` instance with this as the Asset ID type
@@ -175,7 +411,8 @@ impl AssetPacker {
     ///
     /// This method should be called after packing all of the assets.
     /// The `sprites` method must be called before this,
-    /// but `music` and `sounds` may be omitted if there is no audio.
+    /// but `music` and `sounds` may be omitted if there is no audio,
+    /// and `tiled_maps` may be omitted if there are no maps.
     ///
     /// The generated Rust code is written to `out`.
     /// This will typically be a file in the `env::var("OUT_DIR")` directory.
@@ -185,14 +422,28 @@ impl AssetPacker {
     }
 
     fn gen_asset_id_code_checked(self, out: &Path) -> io::Result<()> {
-        let sprites_enum = gen_asset_enum("SpriteId", &self.sprites.expect("self.sprites(...) was not invoked"));
-        let music_enum = gen_asset_enum("MusicId", &self.music.unwrap_or(vec![]));
-        let sounds_enum = gen_asset_enum("SoundId", &self.sounds.unwrap_or(vec![]));
+        let sprites = self.sprites.expect("self.sprites(...) was not invoked");
+        let sprites_enum = gen_asset_enum("SpriteId", &sprites);
+        let anims_enum = gen_anims("AnimId", &self.anims);
+        let music_enum = gen_audio_enum("MusicId", &self.music.unwrap_or(vec![]), &self.music_formats);
+        let sounds_enum = gen_audio_enum("SoundId", &self.sounds.unwrap_or(vec![]), &self.sound_formats)
+                        + &gen_sound_settings("SoundId", &self.sound_settings);
+        let groups_enum = gen_channel_groups("SoundGroupId", &self.sound_groups);
 
-        let code = format!(include_str!("asset_id.template.rs"), sprites_enum, music_enum, sounds_enum);
-        if let Some(out_dir) = out.parent() {
-            fs::create_dir_all(out_dir)?;
-        }
+        let out_dir = out.parent().unwrap_or_else(|| Path::new(""));
+        fs::create_dir_all(out_dir)?;
+        let maps_enum = gen_maps(&self.tiled_maps.unwrap_or_default(), &sprites, out_dir, self.check_rerun)?;
+
+        let loading_fn = gen_loading_sprites(&sprites, &self.loading_sprites);
+        let embedded_fn = if self.embed_assets {
+            gen_embedded_files(&self.assets_dir, !self.loading_sprites.is_empty(),
+                               &self.music_formats, &self.sound_formats)?
+        } else {
+            String::new()
+        };
+
+        let code = format!(include_str!("asset_id.template.rs"), loading_fn, embedded_fn,
+                           sprites_enum, music_enum, sounds_enum, groups_enum, maps_enum, anims_enum);
         let mut file = File::create(out)?;
         file.write_all(code.as_bytes())?;
         Ok(())
@@ -205,21 +456,31 @@ fn create_file(out_dir: &Path, filename: &str, contents: &str, check_rerun: bool
     rerun_print(check_rerun, &out_path);
 }
 
-fn enumerate_audio(in_dir: &Path, out_dir: &Path, prefix: &str, mp3_fallback: bool, check_rerun: bool) -> Vec<String> {
+// returns the enum handle names and the `AudioFormat` variant names, both indexed by ID
+fn enumerate_audio(in_dir: &Path, out_dir: &Path, prefix: &str, mp3_fallback: bool, check_rerun: bool)
+    -> (Vec<String>, Vec<&'static str>)
+{
     let mut paths: Vec<_> = in_dir.read_dir().unwrap()
                                   .filter_map(|p| p.ok())
                                   .map(|p| p.path())
-                                  .filter(|p| p.extension() == Some(OsStr::new("ogg")))
+                                  .filter_map(|p| audio_format(&p).map(|format| (p, format)))
                                   .collect();
-    paths.sort_unstable();
-    for (id, path) in paths.iter().enumerate() {
-        let out_path = out_dir.join(format!("{}{}.{}", prefix, id, "ogg"));
+    // sorted by stem so that duplicate names are adjacent, e.g. "a.ogg" and "a.flac" around "a.g.ogg"
+    paths.sort_unstable_by(|(a, _), (b, _)| a.file_stem().cmp(&b.file_stem()));
+    let names: Vec<_> = paths.iter().map(|(p, _)| p.file_stem().unwrap().to_str().unwrap().to_owned()).collect();
+    assert!(names.windows(2).all(|w| w[0] != w[1]), "should have no duplicate {} names", prefix);
+    for (id, &(ref path, (ext, _))) in paths.iter().enumerate() {
+        let out_path = out_dir.join(format!("{}{}.{}", prefix, id, ext));
         copy_file(path, &out_path, check_rerun);
         if mp3_fallback {
             copy_file(&path.with_extension("mp3"), &out_path.with_extension("mp3"), check_rerun);
         }
     }
-    paths.iter().map(|p| p.file_stem().unwrap().to_str().unwrap().to_owned()).collect()
+    (names, paths.iter().map(|&(_, (_, variant))| variant).collect())
+}
+
+fn audio_format(path: &Path) -> Option<(&'static str, &'static str)> {
+    AUDIO_FORMATS.iter().cloned().find(|&(ext, _)| path.extension() == Some(OsStr::new(ext)))
 }
 
 fn copy_file(from: &Path, to: &Path, check_rerun: bool) {
@@ -231,6 +492,99 @@ fn copy_file(from: &Path, to: &Path, check_rerun: bool) {
     });
 }
 
+fn sound_index(sounds: &Option<Vec<String>>, sound: &str) -> usize {
+    let sounds = sounds.as_ref().expect("self.sounds(...) was not invoked");
+    sounds.iter().position(|s| s == sound).unwrap_or_else(|| panic!("unknown sound {}", sound))
+}
+
+fn gen_sound_settings(name: &str, settings: &[SoundSettings]) -> String {
+    let mut settings_str = String::new();
+    for s in settings {
+        settings_str.push_str(&format!(
+            "            SoundSettings {{ group: {:?}, max_instances: {:?}, cooldown: {:?}, priority: {} }},\n",
+            s.group, s.max_instances, s.cooldown, s.priority,
+        ));
+    }
+    format!(include_str!("sound_settings.template.rs"), name, settings_str, settings.len())
+}
+
+fn gen_anims(name: &str, anims: &[Animation]) -> String {
+    let mut frames_str = String::new();
+    for anim in anims {
+        frames_str.push_str("            &[");
+        for (sprite, duration) in &anim.frames {
+            frames_str.push_str(&format!("(SpriteId::{}, {:?}), ", sprite, duration));
+        }
+        frames_str.push_str("],\n");
+    }
+    let names: Vec<_> = anims.iter().map(|a| a.name.clone()).collect();
+    let anims_impl = format!(include_str!("anim.template.rs"), name, frames_str, anims.len());
+    gen_asset_enum(name, &names) + &anims_impl
+}
+
+// writes the packed maps next to the generated code, which embeds them using `include_bytes!`
+fn gen_maps(maps: &TiledMaps, sprites: &[String], out_dir: &Path, check_rerun: bool) -> io::Result<String> {
+    let mut data_str = String::new();
+    for (id, map) in maps.maps.iter().enumerate() {
+        let path = out_dir.join(format!("map{}.bin", id));
+        map.write_bin_to_file(sprites, &path)?;
+        rerun_print(check_rerun, &path);
+        let path = fs::canonicalize(&path)?;
+        data_str.push_str(&format!("            include_bytes!({:?}),\n", path.to_str().expect("invalid map path")));
+    }
+    let maps_impl = format!(include_str!("map_asset.template.rs"), "MapId", data_str, maps.maps.len());
+    Ok(gen_asset_enum("MapId", &maps.names) + &maps_impl)
+}
+
+// lists the packed asset files in the same way that they are named in the assets directory
+fn gen_loading_sprites(sprites: &[String], loading_sprites: &[String]) -> String {
+    if loading_sprites.is_empty() { return String::new(); }
+    let ids: Vec<_> = sprites.iter().enumerate()
+        .filter(|(_, name)| loading_sprites.contains(name))
+        .map(|(id, _)| id.to_string())
+        .collect();
+    format!("    fn loading_sprites() -> &'static [u16] {{ &[{}] }}\n", ids.join(", "))
+}
+
+fn gen_embedded_files(assets_dir: &Path, loading_sprites: bool, music_formats: &[&str], sound_formats: &[&str])
+                      -> io::Result<String> {
+    let extension = |format: &str| AUDIO_FORMATS.iter().find(|&&(_, variant)| variant == format).unwrap().0;
+    let mut names = vec!["sprites.atlas".to_owned(), "sprites.png".to_owned()];
+    if loading_sprites {
+        names.extend(vec!["loading_sprites.atlas".to_owned(), "loading_sprites.png".to_owned()]);
+    }
+    names.extend(music_formats.iter().enumerate().map(|(id, &format)| format!("music{}.{}", id, extension(format))));
+    names.extend(sound_formats.iter().enumerate().map(|(id, &format)| format!("sound{}.{}", id, extension(format))));
+    let mut files_str = String::new();
+    for name in &names {
+        let path = fs::canonicalize(assets_dir.join(name))?;
+        files_str.push_str(&format!("            ({:?}, include_bytes!({:?})),\n",
+                                    name, path.to_str().expect("invalid asset path")));
+    }
+    Ok(format!(include_str!("embedded_files.template.rs"), files_str, names.len()))
+}
+
+fn gen_channel_groups(name: &str, groups: &[(String, u16)]) -> String {
+    let names: Vec<_> = groups.iter().map(|g| g.0.clone()).collect();
+    let mut channels_str = String::new();
+    for &(_, channels) in groups {
+        channels_str.push_str(&format!("{}, ", channels));
+    }
+    let groups_impl = format!(include_str!("channel_group.template.rs"), name, channels_str, groups.len());
+    gen_asset_enum(name, &names) + &groups_impl
+}
+
+fn gen_audio_enum(name: &str, ids: &[String], formats: &[&str]) -> String {
+    let mut formats_str = String::new();
+    for format in formats {
+        formats_str.push_str("AudioFormat::");
+        formats_str.push_str(format);
+        formats_str.push_str(", ");
+    }
+    let audio_impl = format!(include_str!("audio_id.template.rs"), name, formats_str, formats.len());
+    gen_asset_enum(name, ids) + &audio_impl
+}
+
 fn gen_asset_enum(name: &str, ids: &[String]) -> String {
     let mut ids_str = String::new();
     for id in ids {
@@ -23,54 +23,123 @@ use byteorder::BigEndian;
 use regex::Regex;
 
 use crate::rect_packer::{Rect, Pack};
+use crate::collision;
+use crate::sprite_sheet::{read_sheet, Animation};
 use crate::rerun_print;
 
 const MAX_DIM: u32 = 512;
 
-pub fn form_atlas(images_dir: &Path, out: &Path, pad: u32, check_rerun: bool) -> Vec<String> {
-    assert!(out.extension() == None, "out must not have an extension, will use .png and .atlas extensions");
-    rerun_print(check_rerun, images_dir);
-    let image_out = out.with_extension("png");
-    let atlas_out = out.with_extension("atlas");
+/// How the sprite atlas texture is sampled when drawn at a different scale than its source pixels.
+///
+/// Mirrors `gate::renderer::TextureFilter`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Linear interpolation, combined with the pixel art sampling of the sprite shader.
+    Linear,
+    /// Nearest-neighbor sampling, for blocky pixels without any smoothing.
+    Nearest,
+    /// Linear interpolation with mipmaps, for smooth scaling of zoomed-out views.
+    ///
+    /// The atlas image is padded to a power-of-two size, which mipmaps require.
+    Mipmap,
+}
+
+pub fn form_atlas(images_dir: &Path, extra_images: Vec<SpriteImage>, out: &Path, pad: u32,
+                  filter: Option<TextureFilter>, collision_cell_size: Option<u32>, check_rerun: bool)
+                  -> (Vec<String>, Vec<Animation>) {
+    let (images, anims) = read_images(images_dir, extra_images, check_rerun);
+    (write_atlas(images, out, pad, filter, collision_cell_size, check_rerun), anims)
+}
 
-    let mut images: Vec<(String, RgbaImage)> = images_dir.read_dir().expect("failed to form atlas")
+// reads the sprite images in `images_dir` along with `extra_images`, sorted by name
+pub fn read_images(images_dir: &Path, extra_images: Vec<SpriteImage>, check_rerun: bool)
+                   -> (Vec<SpriteImage>, Vec<Animation>) {
+    rerun_print(check_rerun, images_dir);
+    let image_paths = images_dir.read_dir().expect("failed to form atlas")
         .map(|image_path| image_path.expect("failed to form atlas").path())
-        .filter(|image_path| image_path.is_file() && image_path.extension() == Some(OsStr::new("png")))
-        .flat_map(|image_path| {
-            rerun_print(check_rerun, &image_path);
-            let image = image::open(&image_path).expect("failed to form atlas");
-            let name = image_path.file_stem().expect("failed to form atlas").to_str().expect("failed to form atlas");
-            // TODO check name validity
-            split_tiled_image(name.to_owned(), image.to_rgba())
-        }).collect();
+        .filter(|image_path| image_path.is_file() && image_path.extension() == Some(OsStr::new("png")));
+    let mut images = extra_images;
+    let mut anims = Vec::new();
+    for image_path in image_paths {
+        rerun_print(check_rerun, &image_path);
+        let image = image::open(&image_path).expect("failed to form atlas").to_rgba();
+        let name = image_path.file_stem().expect("failed to form atlas").to_str().expect("failed to form atlas");
+        // TODO check name validity
+        let sheet_path = image_path.with_extension("json");
+        if sheet_path.is_file() {
+            rerun_print(check_rerun, &sheet_path);
+            read_sheet(name, &image, &sheet_path, &mut images, &mut anims);
+        } else {
+            let (name, pivot) = split_pivot(name);
+            let mut sprites = match NINE_SLICE_REGEX.captures(name) {
+                Some(caps) => vec![nine_slice_image(caps[1].to_owned(), &caps[2], image)],
+                None => split_tiled_image(name.to_owned(), image),
+            };
+            for sprite in &mut sprites {
+                sprite.pivot = pivot;
+            }
+            images.extend(sprites);
+        }
+    }
+
+    anims.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    assert!(anims.windows(2).all(|w| w[0].name != w[1].name), "should have no duplicate animation names");
+    images.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    assert!(images.windows(2).all(|w| w[0].name != w[1].name), "should have no duplicate names");
+    (images, anims)
+}
 
-    images.sort_unstable_by(|a, b| a.0.cmp(&b.0));
-    assert!(images.windows(2).all(|w| w[0].0 != w[1].0), "should have no duplicate names");
+// packs `images` into an atlas written to `out` with .png and .atlas extensions, returning the image names
+pub fn write_atlas(images: Vec<SpriteImage>, out: &Path, pad: u32, filter: Option<TextureFilter>,
+                   collision_cell_size: Option<u32>, check_rerun: bool) -> Vec<String> {
+    assert!(out.extension() == None, "out must not have an extension, will use .png and .atlas extensions");
+    let image_out = out.with_extension("png");
+    let atlas_out = out.with_extension("atlas");
 
-    let atlas = Atlas::pack(images, pad).expect("failed to form atlas");
+    let atlas = Atlas::pack(images, pad, filter, collision_cell_size).expect("failed to form atlas");
     atlas.image.save(&image_out).expect("failed to form atlas");
     rerun_print(check_rerun, &image_out);
     atlas.write_bin_to_file(&atlas_out).expect("failed to form atlas");
     rerun_print(check_rerun, &atlas_out);
 
     let mut regions = atlas.regions;
-    let image_names = regions.drain(..).map(|(name, _)| name).collect();
-    image_names
+    regions.drain(..).map(|(name, _)| name).collect()
+}
+
+#[derive(Clone)]
+pub struct SpriteImage {
+    pub name: String,
+    pub image: RgbaImage,
+    pub slice_border: Option<u32>, // border width in pixels, for nine-slice sprites
+    pub pivot: Option<(f64, f64)>, // anchor in pixels relative to the top-left of the untrimmed image
 }
 
 struct AtlasRegion {
     atlas_rect: Rect, // rect of the image in the packed atlas
     raw_sprite_rect: Rect, // rect of the trimmed image in the raw sprite coordinates, same dims as atlas_rect
     raw_sprite_dims: (u32, u32), // original height and width of the untrimmed sprite
+    slice_border: Option<u32>,
+    pivot: Option<(f64, f64)>, // anchor relative to the untrimmed sprite, or None for the center
 }
 
 impl AtlasRegion {
-    fn write_bin(&self, out: &mut Vec<u8>) {
+    fn write_bin(&self, name: &str, out: &mut Vec<u8>) {
         let lt = (self.atlas_rect.pos.1 as u16, self.atlas_rect.pos.0 as u16);
         let rb = (lt.0 + self.atlas_rect.dims.1 as u16, lt.1 + self.atlas_rect.dims.0 as u16);
+        let pivot_x2 = match self.pivot {
+            Some(pivot) => ((2. * pivot.0).round(), (2. * pivot.1).round()),
+            None => (self.raw_sprite_dims.1 as f64, self.raw_sprite_dims.0 as f64),
+        };
+        // the anchor is stored in half pixels relative to the atlas, which a pivot far outside the image does not fit in
+        let anchor_x2 = |lt: u16, pivot_x2: f64, trim: u32| {
+            let anchor_x2 = 2. * lt as f64 + pivot_x2 - 2. * trim as f64;
+            assert!((i16::MIN as f64..=i16::MAX as f64).contains(&anchor_x2),
+                    "pivot of sprite {} is too far outside of its image", name);
+            anchor_x2 as i16
+        };
         let anchor_x2 = (
-            2 * lt.0 as i16 + self.raw_sprite_dims.1 as i16 - 2 * self.raw_sprite_rect.pos.1 as i16,
-            2 * lt.1 as i16 + self.raw_sprite_dims.0 as i16 - 2 * self.raw_sprite_rect.pos.0 as i16,
+            anchor_x2(lt.0, pivot_x2.0, self.raw_sprite_rect.pos.1),
+            anchor_x2(lt.1, pivot_x2.1, self.raw_sprite_rect.pos.0),
         );
 
         use byteorder::WriteBytesExt;
@@ -80,6 +149,11 @@ impl AtlasRegion {
         out.write_u16::<BigEndian>(rb.1).unwrap();
         out.write_i16::<BigEndian>(anchor_x2.0).unwrap();
         out.write_i16::<BigEndian>(anchor_x2.1).unwrap();
+        out.write_u16::<BigEndian>(self.slice_border.unwrap_or(0) as u16).unwrap();
+        out.write_u16::<BigEndian>(self.raw_sprite_dims.1 as u16).unwrap();
+        out.write_u16::<BigEndian>(self.raw_sprite_dims.0 as u16).unwrap();
+        out.write_u16::<BigEndian>(self.raw_sprite_rect.pos.1 as u16).unwrap();
+        out.write_u16::<BigEndian>(self.raw_sprite_rect.pos.0 as u16).unwrap();
     }
 }
 
@@ -95,18 +169,29 @@ fn pre_multiply_alpha(image: &mut RgbaImage) {
 struct Atlas {
     regions: Vec<(String, AtlasRegion)>,
     image: RgbaImage,
+    filter: Option<TextureFilter>,
+    collision_cell_size: Option<u32>,
 }
 
 impl Atlas {
-    fn pack(mut images: Vec<(String, RgbaImage)>, pad: u32) -> Option<Atlas> {
-        let trimmed_rects: Vec<_> = images.iter().map(|&(_, ref i)| trim(i)).collect();
+    fn pack(mut images: Vec<SpriteImage>, pad: u32, filter: Option<TextureFilter>,
+            collision_cell_size: Option<u32>) -> Option<Atlas> {
+        // nine-slice sprites are not trimmed, so that their borders stay intact
+        let trimmed_rects: Vec<_> = images.iter().map(|i| match i.slice_border {
+            Some(_) => Rect { pos: (0, 0), dims: (i.image.height(), i.image.width()) },
+            None => trim(&i.image),
+        }).collect();
         let image_dims: Vec<_> = trimmed_rects.iter()
             .map(|r| (r.dims.0 + 2 * pad, r.dims.1 + 2 * pad))
             .collect();
         Pack::pack(MAX_DIM, &image_dims).map(|pack| {
-            let mut image = RgbaImage::new(pack.width().max(1), pack.height().max(1));
+            let mut dims = (pack.width().max(1), pack.height().max(1));
+            if filter == Some(TextureFilter::Mipmap) {
+                dims = (dims.0.next_power_of_two(), dims.1.next_power_of_two());
+            }
+            let mut image = RgbaImage::new(dims.0, dims.1);
             let mut regions = Vec::new();
-            for (idx, (name, sprite)) in images.drain(..).enumerate() {
+            for (idx, sprite) in images.drain(..).enumerate() {
                 let rect = pack.rects()[idx];
                 let rect = Rect {
                     pos: (rect.pos.0 + pad, rect.pos.1 + pad),
@@ -115,13 +200,15 @@ impl Atlas {
                 let region = AtlasRegion {
                     atlas_rect: rect,
                     raw_sprite_rect: trimmed_rects[idx],
-                    raw_sprite_dims: (sprite.height(), sprite.width()),
+                    raw_sprite_dims: (sprite.image.height(), sprite.image.width()),
+                    slice_border: sprite.slice_border,
+                    pivot: sprite.pivot,
                 };
-                render_sprite(&mut image, &sprite, region.atlas_rect, region.raw_sprite_rect);
-                regions.push((name, region));
+                render_sprite(&mut image, &sprite.image, region.atlas_rect, region.raw_sprite_rect);
+                regions.push((sprite.name, region));
             }
             pre_multiply_alpha(&mut image);
-            Atlas { regions, image }
+            Atlas { regions, image, filter, collision_cell_size }
         })
     }
 
@@ -131,8 +218,20 @@ impl Atlas {
         out.write_u16::<BigEndian>(self.image.width() as u16).unwrap();
         out.write_u16::<BigEndian>(self.image.height() as u16).unwrap();
         out.write_u16::<BigEndian>(self.regions.len() as u16).unwrap();
-        for &(_, ref region) in self.regions.iter() {
-            region.write_bin(&mut out);
+        out.write_u8(match self.filter {
+            None => 0,
+            Some(TextureFilter::Linear) => 1,
+            Some(TextureFilter::Nearest) => 2,
+            Some(TextureFilter::Mipmap) => 3,
+        }).unwrap();
+        for (name, region) in self.regions.iter() {
+            region.write_bin(name, &mut out);
+        }
+        out.write_u8(self.collision_cell_size.unwrap_or(0) as u8).unwrap();
+        if let Some(cell_size) = self.collision_cell_size {
+            for (_, region) in &self.regions {
+                collision::write_bin(&self.image, region.atlas_rect, cell_size, &mut out);
+            }
         }
         out
     }
@@ -145,10 +244,33 @@ impl Atlas {
 }
 
 lazy_static! {
-    static ref TILED_REGEX: Regex = Regex::new("(.*)_t([0-9]+)").unwrap();
+    static ref TILED_REGEX: Regex = Regex::new("^(.*)_t([0-9]+)$").unwrap();
+    static ref NINE_SLICE_REGEX: Regex = Regex::new("^(.*)_9s([0-9]+)$").unwrap();
+    static ref PIVOT_REGEX: Regex = Regex::new("^(.*)_p([0-9]+(?:\\.[0-9]+)?)x([0-9]+(?:\\.[0-9]+)?)$").unwrap();
+    static ref INNER_PIVOT_REGEX: Regex = Regex::new("_p[0-9]+(?:\\.[0-9]+)?x[0-9]+(?:\\.[0-9]+)?_").unwrap();
+}
+
+// strips a "_p#x#" pivot suffix from an image name, returning the pivot if present
+fn split_pivot(name: &str) -> (&str, Option<(f64, f64)>) {
+    assert!(!INNER_PIVOT_REGEX.is_match(name), "the pivot suffix must come last in image name {:?}", name);
+    match PIVOT_REGEX.captures(name) {
+        Some(caps) => {
+            let pivot = (f64::from_str(&caps[2]).unwrap(), f64::from_str(&caps[3]).unwrap());
+            (caps.get(1).unwrap().as_str(), Some(pivot))
+        },
+        None => (name, None),
+    }
+}
+
+fn nine_slice_image(name: String, border: &str, image: RgbaImage) -> SpriteImage {
+    let border = u32::from_str(border).expect("invalid nine-slice border width");
+    assert!(border > 0, "nine-slice border width must be positive");
+    assert!(2 * border < image.width() && 2 * border < image.height(),
+            "nine-slice image {} is too small for its border width", name);
+    SpriteImage { name, image, slice_border: Some(border), pivot: None }
 }
 
-fn split_tiled_image(name: String, mut image: RgbaImage) -> Vec<(String, RgbaImage)> {
+fn split_tiled_image(name: String, mut image: RgbaImage) -> Vec<SpriteImage> {
     let result = TILED_REGEX.captures(&name).map(|caps| {
         let tile_width = u32::from_str(&caps[2]).expect("invalid tile width");
         assert!(tile_width > 0, "tile width must be positive");
@@ -161,13 +283,13 @@ fn split_tiled_image(name: String, mut image: RgbaImage) -> Vec<(String, RgbaIma
                 if sub_image.pixels().any(|(_, _, p)| p[3] != 0) {
                     let sub_image = sub_image.to_image();
                     let name = format!("{}R{}C{}", &caps[1], row, col);
-                    result.push((name, sub_image));
+                    result.push(SpriteImage { name, image: sub_image, slice_border: None, pivot: None });
                 }
             }
         }
         result
     });
-    result.unwrap_or(vec![(name, image)])
+    result.unwrap_or(vec![SpriteImage { name, image, slice_border: None, pivot: None }])
 }
 
 fn render_sprite(atlas: &mut RgbaImage, sprite: &RgbaImage, dst_rect: Rect, src_rect: Rect) {
@@ -180,13 +302,18 @@ fn render_sprite(atlas: &mut RgbaImage, sprite: &RgbaImage, dst_rect: Rect, src_
     }
 }
 
+// returns the bounds of the pixels with non-zero alpha, or an empty rect if the image is blank
+// (such as an empty frame of a sprite sheet animation)
 fn trim(image: &RgbaImage) -> Rect {
     let rows = 0..image.height();
     let cols = 0..image.width();
     let row_has_pixel = |&row: &u32| cols.clone().any(|col| image.get_pixel(col, row)[3] != 0);
     let col_has_pixel = |&col: &u32| rows.clone().any(|row| image.get_pixel(col, row)[3] != 0);
 
-    let top = rows.clone().find(&row_has_pixel).expect("image contains no pixels with non-zero alpha");
+    let top = match rows.clone().find(&row_has_pixel) {
+        Some(top) => top,
+        None => return Rect { pos: (0, 0), dims: (0, 0) },
+    };
     let left = cols.clone().find(&col_has_pixel).unwrap();
     let bottom = rows.clone().rev().find(&row_has_pixel).unwrap() + 1;
     let right = cols.clone().rev().find(&col_has_pixel).unwrap() + 1;
@@ -0,0 +1,136 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use image::RgbaImage;
+
+use crate::rect_packer::Rect;
+
+// Writes collision data for the sprite at `rect` in the atlas `image`,
+// derived from the pixels with non-zero alpha:
+// a convex hull with vertices at pixel corners relative to the top-left of `rect`
+// in counter-clockwise order as the image is viewed,
+// followed by a bitmask with one bit per `cell_size` by `cell_size` block of pixels,
+// in row-major order starting from the top-left of `rect`
+pub fn write_bin(image: &RgbaImage, rect: Rect, cell_size: u32, out: &mut Vec<u8>) {
+    let solid = |row: u32, col: u32| image.get_pixel(rect.pos.1 + col, rect.pos.0 + row)[3] != 0;
+
+    let hull = convex_hull(rect.dims, &solid);
+    out.write_u16::<BigEndian>(hull.len() as u16).unwrap();
+    for &(x, y) in &hull {
+        out.write_u16::<BigEndian>(x as u16).unwrap();
+        out.write_u16::<BigEndian>(y as u16).unwrap();
+    }
+
+    let rows = rect.dims.0.div_ceil(cell_size);
+    let cols = rect.dims.1.div_ceil(cell_size);
+    let mut bits = vec![0u8; (rows * cols).div_ceil(8) as usize];
+    for cell_row in 0..rows {
+        for cell_col in 0..cols {
+            let row_range = (cell_row * cell_size)..((cell_row + 1) * cell_size).min(rect.dims.0);
+            let col_range = (cell_col * cell_size)..((cell_col + 1) * cell_size).min(rect.dims.1);
+            let is_set = row_range.clone().any(|row| col_range.clone().any(|col| solid(row, col)));
+            if is_set {
+                let index = (cell_row * cols + cell_col) as usize;
+                bits[index / 8] |= 0x80 >> (index % 8);
+            }
+        }
+    }
+    out.extend_from_slice(&bits);
+}
+
+// computes the convex hull of the solid pixels within a (height, width) region,
+// in counter-clockwise order as the image is viewed,
+// using the monotone chain algorithm over the outer corners of each row
+fn convex_hull<F: Fn(u32, u32) -> bool>(dims: (u32, u32), solid: &F) -> Vec<(i64, i64)> {
+    let mut points = Vec::new();
+    for row in 0..dims.0 {
+        let left = (0..dims.1).find(|&col| solid(row, col));
+        let right = (0..dims.1).rev().find(|&col| solid(row, col));
+        if let (Some(left), Some(right)) = (left, right) {
+            let (row, left, right) = (row as i64, left as i64, right as i64 + 1);
+            points.extend_from_slice(&[(left, row), (left, row + 1), (right, row), (right, row + 1)]);
+        }
+    }
+    points.sort_unstable();
+    points.dedup();
+
+    let mut hull = half_hull(points.iter());
+    let mut upper = half_hull(points.iter().rev());
+    hull.pop();
+    upper.pop();
+    hull.extend(upper);
+    // the monotone chain winds clockwise as the image is viewed, since +Y is down
+    hull.reverse();
+    hull
+}
+
+// builds the lower (or upper) half of a convex hull from points sorted by X (or in reverse)
+fn half_hull<'a, I: Iterator<Item = &'a (i64, i64)>>(points: I) -> Vec<(i64, i64)> {
+    let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+    let mut hull: Vec<(i64, i64)> = Vec::new();
+    for &point in points {
+        while hull.len() >= 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0 {
+            hull.pop();
+        }
+        hull.push(point);
+    }
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hull_of(mask: &[&str]) -> Vec<(i64, i64)> {
+        let dims = (mask.len() as u32, mask[0].len() as u32);
+        convex_hull(dims, &|row, col| mask[row as usize].as_bytes()[col as usize] == b'#')
+    }
+
+    // twice the signed area, which is negative for counter-clockwise order since +Y is down
+    fn double_area(hull: &[(i64, i64)]) -> i64 {
+        (0..hull.len()).map(|i| {
+            let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+            a.0 * b.1 - b.0 * a.1
+        }).sum()
+    }
+
+    #[test]
+    fn hull_corners() {
+        let hull = hull_of(&[
+            "#..",
+            "##.",
+            "###",
+        ]);
+        assert_eq!(hull, vec![(0, 3), (3, 3), (3, 2), (1, 0), (0, 0)]);
+        assert_eq!(double_area(&hull), -2 * 7);
+    }
+
+    #[test]
+    fn hull_ignores_interior_and_margin() {
+        let hull = hull_of(&[
+            "....",
+            ".##.",
+            ".#..",
+            "....",
+        ]);
+        assert_eq!(hull, vec![(1, 3), (2, 3), (3, 2), (3, 1), (1, 1)]);
+        assert!(double_area(&hull) < 0);
+    }
+
+    #[test]
+    fn empty_hull() {
+        assert!(hull_of(&["..", ".."]).is_empty());
+    }
+}
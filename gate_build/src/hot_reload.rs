@@ -0,0 +1,133 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Contains `SpriteSources`, used by the "hot_reload" feature of the "gate" crate
+//! to re-pack sprites while a Gate application is running.
+//!
+//! The sources are recorded in the assets directory when `AssetPacker::hot_reload` is invoked.
+
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::time::SystemTime;
+
+use serde_json::{json, Value};
+
+use crate::atlas::{form_atlas, TextureFilter};
+use crate::tiled;
+
+const SOURCES_FILE: &str = "sprites.reload";
+
+/// The source directories and packing settings of the sprite atlas,
+/// as recorded by an `AssetPacker` when it packed the sprites.
+pub struct SpriteSources {
+    sprites_dir: PathBuf,
+    maps_dir: Option<PathBuf>,
+    tileset_files: Vec<PathBuf>, // may lie outside of the source directories
+    filter: Option<TextureFilter>,
+    collision_cell_size: Option<u32>,
+    names: Vec<String>,
+}
+
+impl SpriteSources {
+    pub(crate) fn new(sprites_dir: &Path, maps_dir: Option<&Path>, tileset_files: &[PathBuf],
+                      filter: Option<TextureFilter>, collision_cell_size: Option<u32>,
+                      names: Vec<String>) -> SpriteSources {
+        let canonical = |path: &Path| path.canonicalize().expect("failed to resolve sprite source path");
+        SpriteSources {
+            sprites_dir: canonical(sprites_dir),
+            maps_dir: maps_dir.map(canonical),
+            tileset_files: tileset_files.iter().map(|file| canonical(file)).collect(),
+            filter,
+            collision_cell_size,
+            names,
+        }
+    }
+
+    /// Reads the sprite sources recorded in `assets_dir`.
+    ///
+    /// Returns `Ok(None)` if no sources were recorded.
+    pub fn read(assets_dir: &Path) -> io::Result<Option<SpriteSources>> {
+        let path = assets_dir.join(SOURCES_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let value: Value = serde_json::from_reader(BufReader::new(File::open(&path)?))?;
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, format!("invalid sprite sources in {:?}", path));
+        let path_value = |value: &Value| value.as_str().map(PathBuf::from).ok_or_else(invalid);
+        let filter = match value["filter"].as_str() {
+            None => None,
+            Some("Linear") => Some(TextureFilter::Linear),
+            Some("Nearest") => Some(TextureFilter::Nearest),
+            Some("Mipmap") => Some(TextureFilter::Mipmap),
+            Some(_) => return Err(invalid()),
+        };
+        let tileset_files = value["tileset_files"].as_array().ok_or_else(invalid)?.iter()
+            .map(path_value)
+            .collect::<io::Result<_>>()?;
+        let names = value["names"].as_array().ok_or_else(invalid)?.iter()
+            .map(|name| name.as_str().map(str::to_owned).ok_or_else(invalid))
+            .collect::<io::Result<_>>()?;
+        Ok(Some(SpriteSources {
+            sprites_dir: path_value(&value["sprites_dir"])?,
+            maps_dir: if value["maps_dir"].is_null() { None } else { Some(path_value(&value["maps_dir"])?) },
+            tileset_files,
+            filter,
+            collision_cell_size: value["collision_cell_size"].as_u64().map(|size| size as u32),
+            names,
+        }))
+    }
+
+    pub(crate) fn write(&self, assets_dir: &Path) -> io::Result<()> {
+        let value = json!({
+            "sprites_dir": self.sprites_dir.to_str().expect("path could not be converted to string"),
+            "maps_dir": self.maps_dir.as_ref().map(|dir| dir.to_str().expect("path could not be converted to string")),
+            "tileset_files": self.tileset_files.iter()
+                .map(|file| file.to_str().expect("path could not be converted to string"))
+                .collect::<Vec<_>>(),
+            "filter": self.filter.map(|filter| format!("{:?}", filter)),
+            "collision_cell_size": self.collision_cell_size,
+            "names": self.names,
+        });
+        fs::write(assets_dir.join(SOURCES_FILE), serde_json::to_string_pretty(&value)?)
+    }
+
+    /// Returns the sprite names in the order of their IDs, as they were when the sprites were packed.
+    pub fn names(&self) -> &[String] { &self.names }
+
+    /// Returns the latest modification time of the source files,
+    /// used to detect when the sprites should be re-packed.
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        let mut latest = SystemTime::UNIX_EPOCH;
+        for dir in Some(&self.sprites_dir).into_iter().chain(self.maps_dir.as_ref()) {
+            latest = latest.max(fs::metadata(dir)?.modified()?);
+            for entry in dir.read_dir()? {
+                latest = latest.max(entry?.metadata()?.modified()?);
+            }
+        }
+        for file in &self.tileset_files {
+            latest = latest.max(fs::metadata(file)?.modified()?);
+        }
+        Ok(latest)
+    }
+
+    /// Packs the sprites again from the source files, writing the atlas to `out` with `.png` and `.atlas` extensions.
+    ///
+    /// Returns the new sprite names in the order of their IDs in the written atlas.
+    /// Panics if the source files are invalid.
+    pub fn repack(&self, out: &Path) -> Vec<String> {
+        let tile_images = self.maps_dir.as_ref().map(|dir| tiled::read_maps(dir, false).tile_images).unwrap_or_default();
+        form_atlas(&self.sprites_dir, tile_images, out, 1, self.filter, self.collision_cell_size, false).0
+    }
+}
@@ -17,8 +17,9 @@
 //   wrapperDiv: div surrounding the gate canvas, user controls the size
 //   canvas: canvas that will fill up the wrapperDiv to display the app
 //   wasmFilePath: path to the WebAssembly file for the app
-//   onloadprogress(coreRatio, extraResourcesRatio): updates loading progress
-//   onload: invoked when the app has finished loading
+//   onloadprogress(coreRatio, extraResourcesRatio): updates loading progress,
+//                  continuing after onload if the app shows a loading screen with loading sprites
+//   onload: invoked when the app has finished loading, or when it starts showing its loading screen
 //   onquit: invoked when a quit event is signalled from the app
 //   onerror(err): invoked if an error is thrown at any point
 //   readCookie() -> str: if cookie save data is used, this is a function that reads
@@ -39,11 +40,19 @@ function gate(args) {
   const readCookie = args.readCookie;
   const writeCookie = args.writeCookie;
 
+  // replaced by gate_build according to whether AssetPacker::loading_sprites was invoked
+  const hasLoadingSprites = /*HAS_LOADING_SPRITES*/false;
+
   var gateIsBroken = false;
   var Module = {};
+  Module.hasLoadingSprites = hasLoadingSprites;
   Module.loadingAudioCount = 0;
+  Module.voices = [];
+  Module.audioUnlocked = false;
   Module.currentlyRunning = false;
   Module.appQuit = false;
+  Module.targets = [];
+  Module.boundFramebuffer = null;
 
   function gateFail(err) {
     if (gateIsBroken) { return; }
@@ -51,6 +60,10 @@ function gate(args) {
     if (Module.currentMusic != null) {
       Module.currentMusic.stop();
     }
+    if (Module.streamNode != null) {
+      Module.streamNode.onaudioprocess = null;
+      Module.streamNode.disconnect();
+    }
     Module = { currentlyRunning: false };
     if (onerror) {
       onerror(err);
@@ -61,7 +74,12 @@ function gate(args) {
 
   try {
     function bytesToBase64(bytes) {
-      return btoa(String.fromCharCode.apply(null, bytes)).replace(/\=/g, '.');
+      var str = "";
+      // converted in chunks, since apply has a limit on the number of arguments
+      for (var i = 0; i < bytes.length; i += 8192) {
+        str += String.fromCharCode.apply(null, bytes.subarray(i, i + 8192));
+      }
+      return btoa(str).replace(/\=/g, '.');
     }
     function base64ToBytes(base64) {
       return new Uint8Array(atob(base64.replace(/\./g, '=')).split("").map(function(c) { return c.charCodeAt(0); }));
@@ -76,6 +94,14 @@ function gate(args) {
       return null;
     }
 
+    // accessing localStorage throws in some browsers when storage is disabled
+    function getLocalStorage() {
+      try {
+        return window.localStorage || null;
+      } catch(err) { }
+      return null;
+    }
+
     const floatSize = 4;
 
     function makeKeycodesMap () {
@@ -115,11 +141,78 @@ function gate(args) {
 
     var cursorPos = { x: 0, y: 0 };
 
-    function setSpriteAttribPointers () {
-      gl.vertexAttribPointer(Module.spriteProg.attribs.vert, 2, gl.FLOAT, false, 7 * floatSize, 0);
-      gl.vertexAttribPointer(Module.spriteProg.attribs.vsInvTexSampleDims, 2, gl.FLOAT, false, 7 * floatSize, 2 * floatSize);
-      gl.vertexAttribPointer(Module.spriteProg.attribs.vsTexVertRb, 2, gl.FLOAT, false, 7 * floatSize, 4 * floatSize);
-      gl.vertexAttribPointer(Module.spriteProg.attribs.vsFlashRatio, 1, gl.FLOAT, false, 7 * floatSize, 6 * floatSize);
+    // a shader id of -1 means the built-in sprite program
+    function spriteProgram (shader) {
+      return shader < 0 ? Module.spriteProg : Module.shaders[shader];
+    }
+
+    // blend factors by blend mode id, assuming premultiplied alpha:
+    // alpha, additive, multiply, screen
+    const blendFactors = [
+      [gl.ONE, gl.ONE_MINUS_SRC_ALPHA],
+      [gl.ONE, gl.ONE],
+      [gl.DST_COLOR, gl.ONE_MINUS_SRC_ALPHA],
+      [gl.ONE, gl.ONE_MINUS_SRC_COLOR],
+    ];
+
+    function drawSprites (spriteProg, blend, tex, texWidth, texHeight, size, dataPtr) {
+      gl.enable(gl.SCISSOR_TEST);
+      gl.useProgram(spriteProg.prog);
+      gl.blendFunc(blendFactors[blend][0], blendFactors[blend][1]);
+
+      gl.activeTexture(gl.TEXTURE0);
+      gl.bindTexture(gl.TEXTURE_2D, tex);
+      gl.uniform1i(spriteProg.uniformTex, 0);
+      gl.uniform2f(spriteProg.uniformInvTexDims, 1.0 / texWidth, 1.0 / texHeight);
+
+      spriteProg.setAttribPointers(spriteProg);
+
+      gl.bufferData(gl.ARRAY_BUFFER, new Uint8Array(Module.memory.buffer, dataPtr, size), gl.STREAM_DRAW);
+
+      gl.drawArrays(gl.TRIANGLES, 0, size / (spriteProg.vertexLen * floatSize));
+      gl.disable(gl.SCISSOR_TEST);
+    }
+
+    function setSpriteAttribPointers (spriteProg) {
+      const attribs = spriteProg.attribs;
+      setAttribPointer(attribs.vert, 2, 0, 7);
+      setAttribPointer(attribs.vsInvTexSampleDims, 2, 2, 7);
+      setAttribPointer(attribs.vsTexVertRb, 2, 4, 7);
+      setAttribPointer(attribs.vsFlashRatio, 1, 6, 7);
+    }
+
+    function setMeshAttribPointers (meshProg) {
+      const attribs = meshProg.attribs;
+      setAttribPointer(attribs.vert, 2, 0, 12);
+      setAttribPointer(attribs.vsTexCoords, 2, 2, 12);
+      setAttribPointer(attribs.vsTexBounds, 4, 4, 12);
+      setAttribPointer(attribs.vsColor, 4, 8, 12);
+    }
+
+    function isPowerOfTwo (x) {
+      return (x & (x - 1)) == 0;
+    }
+
+    // sets the filter of the bound texture by filter id (linear, nearest, mipmap), generating mipmaps if needed
+    function setTextureFilter (filter, width, height) {
+      if (filter == 2 && !(isPowerOfTwo(width) && isPowerOfTwo(height))) {
+        filter = 0; // WebGL 1 only supports mipmaps for power-of-two textures
+      }
+      const magFilter = filter == 1 ? gl.NEAREST : gl.LINEAR;
+      const minFilter = filter == 1 ? gl.NEAREST : (filter == 2 ? gl.LINEAR_MIPMAP_LINEAR : gl.LINEAR);
+      gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MAG_FILTER, magFilter);
+      gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MIN_FILTER, minFilter);
+      if (filter == 2) {
+        gl.generateMipmap(gl.TEXTURE_2D);
+      }
+    }
+
+    // attributes may be optimized out when a custom fragment shader ignores their varyings
+    function setAttribPointer (attrib, size, offset, vertexLen) {
+      if (attrib >= 0) {
+        gl.enableVertexAttribArray(attrib);
+        gl.vertexAttribPointer(attrib, size, gl.FLOAT, false, vertexLen * floatSize, offset * floatSize);
+      }
     }
 
     const imports = {
@@ -133,22 +226,113 @@ function gate(args) {
           gl.clear(gl.COLOR_BUFFER_BIT);
           gl.disable(gl.SCISSOR_TEST);
         },
-        gateWasmDrawSprites: function (size, dataPtr) {
+        gateWasmDrawSprites: function (shader, blend, size, dataPtr) {
+          drawSprites(spriteProgram(shader), blend, Module.spriteTex, Module.spriteTexWidth, Module.spriteTexHeight, size, dataPtr);
+        },
+        gateWasmDrawMesh: function (blend, size, dataPtr) {
+          drawSprites(Module.meshProg, blend, Module.spriteTex, Module.spriteTexWidth, Module.spriteTexHeight, size, dataPtr);
+        },
+        gateWasmCreateTileChunk: function (size, dataPtr) {
+          const buffer = gl.createBuffer();
+          gl.bindBuffer(gl.ARRAY_BUFFER, buffer);
+          gl.bufferData(gl.ARRAY_BUFFER, new Uint8Array(Module.memory.buffer, dataPtr, size), gl.STATIC_DRAW);
+          gl.bindBuffer(gl.ARRAY_BUFFER, vbo);
+          var id = Module.tileChunks.indexOf(null);
+          if (id < 0) {
+            id = Module.tileChunks.length;
+            Module.tileChunks.push(null);
+          }
+          Module.tileChunks[id] = buffer;
+          return id;
+        },
+        gateWasmDeleteTileChunk: function (id) {
+          gl.deleteBuffer(Module.tileChunks[id]);
+          Module.tileChunks[id] = null;
+        },
+        gateWasmDrawTileChunk: function (id, blend, vertexCount, paramsPtr) {
+          // params are the two rows of the transform, followed by the native scale
+          const params = new Float32Array(Module.memory.buffer, paramsPtr, 8);
+          const tileProg = Module.tileProg;
           gl.enable(gl.SCISSOR_TEST);
-          gl.useProgram(Module.spriteProg.prog);
+          gl.useProgram(tileProg.prog);
+          gl.blendFunc(blendFactors[blend][0], blendFactors[blend][1]);
 
           gl.activeTexture(gl.TEXTURE0);
           gl.bindTexture(gl.TEXTURE_2D, Module.spriteTex);
-          gl.uniform1i(Module.spriteProg.uniformTex, 0);
-          gl.uniform2f(Module.spriteProg.uniformInvTexDims, 1.0 / Module.spriteTexWidth, 1.0 / Module.spriteTexHeight);
-
-          setSpriteAttribPointers();
-
-          gl.bufferData(gl.ARRAY_BUFFER, new Uint8Array(Module.memory.buffer, dataPtr, size), gl.STREAM_DRAW);
+          gl.uniform1i(tileProg.uniformTex, 0);
+          gl.uniform2f(tileProg.uniformInvTexDims, 1.0 / Module.spriteTexWidth, 1.0 / Module.spriteTexHeight);
+          gl.uniform3f(tileProg.uniformTransformRow0, params[0], params[1], params[2]);
+          gl.uniform3f(tileProg.uniformTransformRow1, params[3], params[4], params[5]);
+          gl.uniform2f(tileProg.uniformNativeScale, params[6], params[7]);
 
-          gl.drawArrays(gl.TRIANGLES, 0, size / 28);
+          gl.bindBuffer(gl.ARRAY_BUFFER, Module.tileChunks[id]);
+          setAttribPointer(tileProg.attribs.vert, 2, 0, 4);
+          setAttribPointer(tileProg.attribs.vsTexCoords, 2, 2, 4);
+          gl.drawArrays(gl.TRIANGLES, 0, vertexCount);
+          gl.bindBuffer(gl.ARRAY_BUFFER, vbo);
           gl.disable(gl.SCISSOR_TEST);
         },
+        gateWasmDrawTargetSprites: function (id, shader, blend, size, dataPtr) {
+          const target = Module.targets[id];
+          drawSprites(spriteProgram(shader), blend, target.tex, target.width, target.height, size, dataPtr);
+        },
+        gateWasmCreateTarget: function (width, height, filter) {
+          const tex = gl.createTexture();
+          gl.bindTexture(gl.TEXTURE_2D, tex);
+          gl.texImage2D(gl.TEXTURE_2D, 0, gl.RGBA, width, height, 0, gl.RGBA, gl.UNSIGNED_BYTE, null);
+          setTextureFilter(filter, width, height);
+          gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_WRAP_S, gl.CLAMP_TO_EDGE);
+          gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_WRAP_T, gl.CLAMP_TO_EDGE);
+          const fbo = gl.createFramebuffer();
+          gl.bindFramebuffer(gl.FRAMEBUFFER, fbo);
+          gl.framebufferTexture2D(gl.FRAMEBUFFER, gl.COLOR_ATTACHMENT0, gl.TEXTURE_2D, tex, 0);
+          if (gl.checkFramebufferStatus(gl.FRAMEBUFFER) != gl.FRAMEBUFFER_COMPLETE) {
+            throw "incomplete render target framebuffer";
+          }
+          gl.clearColor(0, 0, 0, 0);
+          gl.clear(gl.COLOR_BUFFER_BIT);
+          gl.bindFramebuffer(gl.FRAMEBUFFER, Module.boundFramebuffer);
+          var id = Module.targets.indexOf(null);
+          if (id < 0) {
+            id = Module.targets.length;
+          }
+          Module.targets[id] = { tex: tex, fbo: fbo, width: width, height: height };
+          return id;
+        },
+        gateWasmDeleteTarget: function (id) {
+          const target = Module.targets[id];
+          gl.deleteFramebuffer(target.fbo);
+          gl.deleteTexture(target.tex);
+          Module.targets[id] = null;
+        },
+        gateWasmCreateShader: function (srcLen, srcPtr) {
+          const frag = loadShader(gl.FRAGMENT_SHADER, readStr(srcPtr, srcLen));
+          Module.shaders.push(makeSpriteProg(Module.spriteVert, frag));
+          return Module.shaders.length - 1;
+        },
+        gateWasmSetUniform: function (shader, nameLen, namePtr, kind, x, y, z, w) {
+          const spriteProg = Module.shaders[shader];
+          const name = readStr(namePtr, nameLen);
+          if (!(name in spriteProg.uniforms)) {
+            spriteProg.uniforms[name] = gl.getUniformLocation(spriteProg.prog, name);
+          }
+          const location = spriteProg.uniforms[name];
+          if (location === null) { return; }
+          gl.useProgram(spriteProg.prog);
+          // kind codes match gate::renderer::Uniform variants, see core_renderer/wasm.rs
+          switch (kind) {
+            case 0: gl.uniform1f(location, x); break;
+            case 1: gl.uniform2f(location, x, y); break;
+            case 2: gl.uniform3f(location, x, y, z); break;
+            case 3: gl.uniform4f(location, x, y, z, w); break;
+            case 4: gl.uniform1i(location, x); break;
+          }
+        },
+        gateWasmBindTarget: function (id, viewportWidth, viewportHeight) {
+          Module.boundFramebuffer = id < 0 ? null : Module.targets[id].fbo;
+          gl.bindFramebuffer(gl.FRAMEBUFFER, Module.boundFramebuffer);
+          gl.viewport(0, 0, viewportWidth, viewportHeight);
+        },
         gateWasmLoopMusic: function (id) {
           if (Module.currentMusic != null) {
             Module.currentMusic.stop();
@@ -171,8 +355,79 @@ function gate(args) {
             Module.currentMusic = null;
           }
         },
-        gateWasmPlaySound: function (id) {
-          Module.sounds[id].play();
+        gateWasmPlaySound: function (id, voice) {
+          imports.env.gateWasmStopVoice(voice);
+          const sound = Module.sounds[id];
+          Module.voices[voice] = { sound: sound, playId: sound.play() };
+        },
+        gateWasmIsVoicePlaying: function (voice) {
+          const v = Module.voices[voice];
+          return v != null && v.sound.playing(v.playId);
+        },
+        gateWasmStopVoice: function (voice) {
+          const v = Module.voices[voice];
+          if (v != null) {
+            v.sound.stop(v.playId);
+            Module.voices[voice] = null;
+          }
+        },
+        gateWasmPlaySamples: function (len, dataPtr, sampleRate, channels) {
+          const ctx = initAudioContext();
+          const data = new Float32Array(Module.memory.buffer, dataPtr, len);
+          const frameCount = len / channels;
+          const buffer = ctx.createBuffer(channels, frameCount, sampleRate);
+          for (var c = 0; c < channels; c++) {
+            const channelData = buffer.getChannelData(c);
+            for (var i = 0; i < frameCount; i++) {
+              channelData[i] = data[i * channels + c];
+            }
+          }
+          const source = ctx.createBufferSource();
+          source.buffer = buffer;
+          source.connect(Module.audioOut);
+          source.start();
+        },
+        gateWasmOpenStream: function () {
+          const ctx = initAudioContext();
+          imports.env.gateWasmCloseStream();
+          // ScriptProcessorNode is used rather than AudioWorklet, since worklets run on a
+          // separate thread that cannot call into the app synchronously
+          Module.streamNode = ctx.createScriptProcessor(1024, 0, 2);
+          Module.streamNode.onaudioprocess = function (e) {
+            try {
+              const left = e.outputBuffer.getChannelData(0);
+              const right = e.outputBuffer.getChannelData(1);
+              const frameCount = e.outputBuffer.length;
+              const dataPtr = Module.gateWasmFillStream(frameCount);
+              const data = new Float32Array(Module.memory.buffer, dataPtr, 2 * frameCount);
+              for (var i = 0; i < frameCount; i++) {
+                left[i] = data[2 * i];
+                right[i] = data[2 * i + 1];
+              }
+            } catch(err) { gateFail(err); }
+          };
+          Module.streamNode.connect(Module.audioOut);
+        },
+        gateWasmCloseStream: function () {
+          if (Module.streamNode != null) {
+            Module.streamNode.onaudioprocess = null;
+            Module.streamNode.disconnect();
+            Module.streamNode = null;
+          }
+        },
+        gateWasmAudioSampleRate: function () {
+          return initAudioContext().sampleRate;
+        },
+        gateWasmSetMuted: function (muted) {
+          Howler.mute(muted != 0);
+          // a stream using its own audio context is not affected by Howler.mute
+          if (Module.audioContext != null && Module.audioContext !== Howler.ctx) {
+            if (muted) {
+              Module.audioContext.suspend();
+            } else {
+              Module.audioContext.resume();
+            }
+          }
         },
         gateWasmSpriteAtlasBinSize: function () {
           return Module.spriteAtlas.length;
@@ -180,6 +435,10 @@ function gate(args) {
         gateWasmSpriteAtlasBinFill: function(bufferPtr) {
           new Uint8Array(Module.memory.buffer).set(Module.spriteAtlas, bufferPtr);
         },
+        gateWasmSetSpriteFilter: function (filter) {
+          gl.bindTexture(gl.TEXTURE_2D, Module.spriteTex);
+          setTextureFilter(filter, Module.spriteTexWidth, Module.spriteTexHeight);
+        },
         gateWasmRequestFullscreen: function () {
           if (wrapperDiv.requestFullscreen) {
             wrapperDiv.requestFullscreen();
@@ -218,6 +477,39 @@ function gate(args) {
         gateWasmWriteCookie: function (size, dataPtr) {
           writeCookie(bytesToBase64(new Uint8Array(Module.memory.buffer, dataPtr, size)));
         },
+        // storage functions return -1 for an empty slot, -2 if storage is unavailable,
+        // and -3 if the operation failed
+        gateWasmStorageRead: function (keyLen, keyPtr) {
+          const storage = getLocalStorage();
+          if (storage === null) { return -2; }
+          try {
+            const base64 = storage.getItem(readStr(keyPtr, keyLen));
+            if (base64 === null) { return -1; }
+            Module.storageData = base64ToBytes(base64);
+            return Module.storageData.length;
+          } catch(err) { return -3; }
+        },
+        gateWasmStorageFill: function (bufferPtr) {
+          new Uint8Array(Module.memory.buffer).set(Module.storageData, bufferPtr);
+          Module.storageData = null;
+        },
+        gateWasmStorageWrite: function (keyLen, keyPtr, size, dataPtr) {
+          const storage = getLocalStorage();
+          if (storage === null) { return -2; }
+          try {
+            const base64 = bytesToBase64(new Uint8Array(Module.memory.buffer, dataPtr, size));
+            storage.setItem(readStr(keyPtr, keyLen), base64);
+            return 0;
+          } catch(err) { return -3; }
+        },
+        gateWasmStorageDelete: function (keyLen, keyPtr) {
+          const storage = getLocalStorage();
+          if (storage === null) { return -2; }
+          try {
+            storage.removeItem(readStr(keyPtr, keyLen));
+            return 0;
+          } catch(err) { return -3; }
+        },
         Math_atan2: Math.atan2,
         cos: Math.cos,
         sin: Math.sin,
@@ -227,28 +519,67 @@ function gate(args) {
       }
     };
 
-    fetch("sprites.atlas").then(response =>
-      response.arrayBuffer()
-    ).then(bytes => {
-      Module.spriteAtlas = new Uint8Array(bytes);
-      tryStart();
-    }).catch(gateFail);
+    // loads the atlas and texture of the sprites packed to `name`, invoking onload once both are loaded
+    function loadSprites (name, onload) {
+      var sprites = { atlas: null, tex: null, texWidth: 0, texHeight: 0 };
+      fetch(`${name}.atlas`).then(response =>
+        response.arrayBuffer()
+      ).then(bytes => {
+        sprites.atlas = new Uint8Array(bytes);
+        if (sprites.tex) { onload(); }
+      }).catch(gateFail);
 
-    const spriteImage = new Image();
-    spriteImage.onload = function () {
-      try {
-        Module.spriteTexWidth = spriteImage.width;
-        Module.spriteTexHeight = spriteImage.height;
-        Module.spriteTex = gl.createTexture();
-        gl.bindTexture(gl.TEXTURE_2D, Module.spriteTex);
-        gl.texImage2D(gl.TEXTURE_2D, 0, gl.RGBA, gl.RGBA, gl.UNSIGNED_BYTE, spriteImage);
-        gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MIN_FILTER, gl.LINEAR);
-        gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MAG_FILTER, gl.LINEAR);
+      const spriteImage = new Image();
+      spriteImage.onload = function () {
+        try {
+          sprites.texWidth = spriteImage.width;
+          sprites.texHeight = spriteImage.height;
+          sprites.tex = gl.createTexture();
+          gl.bindTexture(gl.TEXTURE_2D, sprites.tex);
+          gl.texImage2D(gl.TEXTURE_2D, 0, gl.RGBA, gl.RGBA, gl.UNSIGNED_BYTE, spriteImage);
+          gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MIN_FILTER, gl.LINEAR);
+          gl.texParameteri(gl.TEXTURE_2D, gl.TEXTURE_MAG_FILTER, gl.LINEAR);
+          if (sprites.atlas) { onload(); }
+        } catch(err) { gateFail(err); }
+      };
+      spriteImage.onerror = function() { gateFail(`failed to load ${name}.png`) };
+      spriteImage.src = `${name}.png`;
+      return sprites;
+    }
+
+    // makes `sprites` the atlas and texture that the app reads and draws with
+    function useSprites (sprites) {
+      Module.spriteAtlas = sprites.atlas;
+      Module.spriteTex = sprites.tex;
+      Module.spriteTexWidth = sprites.texWidth;
+      Module.spriteTexHeight = sprites.texHeight;
+    }
+
+    function isLoaded (sprites) {
+      return sprites != null && sprites.atlas != null && sprites.tex != null;
+    }
+
+    // if the app has loading sprites, it starts with those while the main sprites are loading
+    function startSprites () {
+      return Module.hasLoadingSprites ? Module.loadingSprites : Module.mainSprites;
+    }
+
+    function loadMainSprites () {
+      Module.mainSprites = loadSprites("sprites", () => {
         tryStart();
-      } catch(err) { gateFail(err); }
-    };
-    spriteImage.onerror = function() { gateFail("failed to load sprites.png") };
-    spriteImage.src = "sprites.png";
+        checkAssetsLoaded();
+      });
+    }
+
+    // the main sprites are fetched after the loading sprites, so that they do not delay the loading screen
+    if (Module.hasLoadingSprites) {
+      Module.loadingSprites = loadSprites("loading_sprites", () => {
+        loadMainSprites();
+        tryStart();
+      });
+    } else {
+      loadMainSprites();
+    }
 
     fetch(wasmFilePath).then(response =>
       response.arrayBuffer()
@@ -267,10 +598,31 @@ function gate(args) {
         Module.gateWasmMouseEvent = mod.exports.gateWasmMouseEvent;
         Module.gateWasmMusicCount = mod.exports.gateWasmMusicCount;
         Module.gateWasmSoundCount = mod.exports.gateWasmSoundCount;
+        Module.gateWasmMusicFormat = mod.exports.gateWasmMusicFormat;
+        Module.gateWasmSoundFormat = mod.exports.gateWasmSoundFormat;
         Module.gateWasmSpriteVertSrc = mod.exports.gateWasmSpriteVertSrc;
         Module.gateWasmSpriteFragSrc = mod.exports.gateWasmSpriteFragSrc;
+        Module.gateWasmMeshVertSrc = mod.exports.gateWasmMeshVertSrc;
+        Module.gateWasmMeshFragSrc = mod.exports.gateWasmMeshFragSrc;
+        Module.gateWasmTileVertSrc = mod.exports.gateWasmTileVertSrc;
         Module.gateWasmOnRestart = mod.exports.gateWasmOnRestart;
         Module.gateWasmCookieDataPtr = mod.exports.gateWasmCookieDataPtr;
+        Module.gateWasmFillStream = mod.exports.gateWasmFillStream;
+        Module.gateWasmAudioUnlocked = mod.exports.gateWasmAudioUnlocked;
+        Module.gateWasmFocusEvent = mod.exports.gateWasmFocusEvent;
+        Module.gateWasmHasLoadingSprites = mod.exports.gateWasmHasLoadingSprites;
+        Module.gateWasmLoadProgress = mod.exports.gateWasmLoadProgress;
+        Module.gateWasmAssetsLoaded = mod.exports.gateWasmAssetsLoaded;
+        if (!Module.gateWasmIsAppDefined()) {
+          Module.main();
+          if (!Module.gateWasmIsAppDefined()) {
+            alert("gate::run(...) was not invoked in main");
+            throw "gate::run(...) was not invoked in main";
+          }
+        }
+        if ((Module.gateWasmHasLoadingSprites() != 0) != Module.hasLoadingSprites) {
+          throw "gate.js does not match the app's loading sprites, regenerate it using gate_build";
+        }
         tryStart();
       } catch(err) { gateFail(err); }
     }).catch(gateFail);
@@ -301,44 +653,87 @@ function gate(args) {
     }
 
     function makeSpriteAttribs (spriteProg) {
-      const attribs = {
+      return {
         vert: gl.getAttribLocation(spriteProg, "vert"),
         vsInvTexSampleDims: gl.getAttribLocation(spriteProg, "vs_inv_tex_sample_dims"),
         vsTexVertRb: gl.getAttribLocation(spriteProg, "vs_tex_vert_rb"),
         vsFlashRatio: gl.getAttribLocation(spriteProg, "vs_flash_ratio"),
       };
+    }
 
-      gl.enableVertexAttribArray(attribs.vert);
-      gl.enableVertexAttribArray(attribs.vsInvTexSampleDims);
-      gl.enableVertexAttribArray(attribs.vsTexVertRb);
-      gl.enableVertexAttribArray(attribs.vsFlashRatio);
+    function makeSpriteProg (vertShader, fragShader) {
+      const prog = linkShaderProgram(vertShader, fragShader);
+      return {
+        prog: prog,
+        attribs: makeSpriteAttribs(prog),
+        vertexLen: 7,
+        setAttribPointers: setSpriteAttribPointers,
+        uniformTex: gl.getUniformLocation(prog, "tex"),
+        uniformInvTexDims: gl.getUniformLocation(prog, "inv_tex_dims"),
+        uniforms: {},
+      };
+    }
 
-      return attribs;
+    function makeMeshProg (vertShader, fragShader) {
+      const prog = linkShaderProgram(vertShader, fragShader);
+      return {
+        prog: prog,
+        attribs: {
+          vert: gl.getAttribLocation(prog, "vert"),
+          vsTexCoords: gl.getAttribLocation(prog, "vs_tex_coords"),
+          vsTexBounds: gl.getAttribLocation(prog, "vs_tex_bounds"),
+          vsColor: gl.getAttribLocation(prog, "vs_color"),
+        },
+        vertexLen: 12,
+        setAttribPointers: setMeshAttribPointers,
+        uniformTex: gl.getUniformLocation(prog, "tex"),
+        uniformInvTexDims: gl.getUniformLocation(prog, "inv_tex_dims"),
+      };
     }
 
-    function initSpriteProg () {
-      Module.spriteVert = loadShader(gl.VERTEX_SHADER, readCStr(Module.gateWasmSpriteVertSrc()));
-      Module.spriteFrag = loadShader(gl.FRAGMENT_SHADER, readCStr(Module.gateWasmSpriteFragSrc()));
-      const prog = linkShaderProgram(Module.spriteVert, Module.spriteFrag);
-      Module.spriteProg = {
+    function makeTileProg (vertShader, fragShader) {
+      const prog = linkShaderProgram(vertShader, fragShader);
+      return {
         prog: prog,
-        attribs: makeSpriteAttribs(prog),
+        attribs: {
+          vert: gl.getAttribLocation(prog, "vert"),
+          vsTexCoords: gl.getAttribLocation(prog, "vs_tex_coords"),
+        },
         uniformTex: gl.getUniformLocation(prog, "tex"),
         uniformInvTexDims: gl.getUniformLocation(prog, "inv_tex_dims"),
+        uniformTransformRow0: gl.getUniformLocation(prog, "transform_row_0"),
+        uniformTransformRow1: gl.getUniformLocation(prog, "transform_row_1"),
+        uniformNativeScale: gl.getUniformLocation(prog, "native_scale"),
       };
     }
 
-    function initAudioArray (prefix, count, loop) {
+    function initSpriteProg () {
+      Module.spriteVert = loadShader(gl.VERTEX_SHADER, readCStr(Module.gateWasmSpriteVertSrc()));
+      Module.spriteFrag = loadShader(gl.FRAGMENT_SHADER, readCStr(Module.gateWasmSpriteFragSrc()));
+      Module.spriteProg = makeSpriteProg(Module.spriteVert, Module.spriteFrag);
+      Module.meshProg = makeMeshProg(loadShader(gl.VERTEX_SHADER, readCStr(Module.gateWasmMeshVertSrc())),
+                                     loadShader(gl.FRAGMENT_SHADER, readCStr(Module.gateWasmMeshFragSrc())));
+      Module.tileProg = makeTileProg(loadShader(gl.VERTEX_SHADER, readCStr(Module.gateWasmTileVertSrc())),
+                                     Module.spriteFrag);
+      Module.tileChunks = [];
+      Module.shaders = [];
+    }
+
+    // indexed by the gate::asset_id::AudioFormat enum
+    const audioExtensions = ["ogg", "wav", "flac"];
+
+    function initAudioArray (prefix, count, loop, formatFn) {
       Module.loadingAudioCount += count;
       var result = new Array(count);
       for (var i = 0; i < count; i++) {
         let audioSrc = `${prefix}${i}`;
         result[i] = new Howl({
-          src: [`${audioSrc}.ogg`, `${audioSrc}.mp3`],
+          src: [`${audioSrc}.${audioExtensions[formatFn(i)]}`, `${audioSrc}.mp3`],
           loop: loop,
           onload: function () {
             Module.loadingAudioCount -= 1;
             tryStart2();
+            checkAssetsLoaded();
           },
           onloaderror: function() { gateFail("failed to load " + audioSrc); }
         });
@@ -346,39 +741,55 @@ function gate(args) {
       return result;
     }
 
+    // shares the Howler audio context when available, so that volume and unlocking apply to both
+    function initAudioContext () {
+      if (Module.audioContext == null) {
+        if (Howler.ctx && Howler.masterGain) {
+          Module.audioContext = Howler.ctx;
+          Module.audioOut = Howler.masterGain;
+        } else {
+          Module.audioContext = new (window.AudioContext || window.webkitAudioContext)();
+          Module.audioOut = Module.audioContext.destination;
+        }
+      }
+      return Module.audioContext;
+    }
+
     function updateLoadProgress () {
-      if (!gateIsBroken && onloadprogress) {
+      if (gateIsBroken) { return; }
+      // resources loaded after the core resources, including the main sprites if there are loading sprites
+      var extraRatio = 0.0;
+      if (Module.musics && Module.sounds) {
+        var totalCount = Module.musics.length + Module.sounds.length;
+        var loadedCount = totalCount - Module.loadingAudioCount;
+        if (Module.hasLoadingSprites) {
+          totalCount += 1;
+          if (isLoaded(Module.mainSprites)) { loadedCount += 1; }
+        }
+        extraRatio = totalCount > 0 ? loadedCount / totalCount : 1;
+      }
+      if (Module.started && !Module.assetsLoaded) {
+        Module.gateWasmLoadProgress(extraRatio);
+      }
+      if (onloadprogress) {
         var coreCount = 0;
-        if (Module.spriteAtlas) { coreCount += 1; }
+        let sprites = startSprites();
+        if (sprites && sprites.atlas) { coreCount += 1; }
         if (Module.memory) { coreCount += 1; }
-        if (Module.spriteTex) { coreCount += 1; }
-        var audioRatio = 0.0;
-        if (Module.musics && Module.sounds) {
-          let totalAudioCount = Module.musics.length + Module.sounds.length;
-          if (totalAudioCount > 0) {
-            audioRatio = 1 - Module.loadingAudioCount / totalAudioCount;
-          } else {
-            audioRatio = 1;
-          }
-        }
-        onloadprogress(coreCount / 3, audioRatio);
+        if (sprites && sprites.tex) { coreCount += 1; }
+        onloadprogress(coreCount / 3, extraRatio);
       }
     }
 
     function tryStart () {
       updateLoadProgress();
-      if (!gateIsBroken && Module.spriteAtlas && Module.memory && Module.spriteTex) {
-        if (!Module.gateWasmIsAppDefined()) {
-          Module.main();
-          if (!Module.gateWasmIsAppDefined()) {
-            alert("gate::run(...) was not invoked in main");
-            throw "gate::run(...) was not invoked in main";
-          }
-        }
+      if (!gateIsBroken && !Module.starting && Module.memory && isLoaded(startSprites())) {
+        Module.starting = true;
+        useSprites(startSprites());
         loadCookieIntoMemory();
         initSpriteProg();
-        Module.musics = initAudioArray("music", Module.gateWasmMusicCount(), true);
-        Module.sounds = initAudioArray("sound", Module.gateWasmSoundCount(), false);
+        Module.musics = initAudioArray("music", Module.gateWasmMusicCount(), true, Module.gateWasmMusicFormat);
+        Module.sounds = initAudioArray("sound", Module.gateWasmSoundCount(), false, Module.gateWasmSoundFormat);
         tryStart2();
       }
     }
@@ -393,8 +804,10 @@ function gate(args) {
 
     function tryStart2 () {
       updateLoadProgress();
-      if (!gateIsBroken && Module.loadingAudioCount == 0) {
+      if (!gateIsBroken && !Module.started && (Module.hasLoadingSprites || Module.loadingAudioCount == 0)) {
         try {
+          Module.started = true;
+          Module.assetsLoaded = !Module.hasLoadingSprites;
           Module.currentlyRunning = true;
           Module.currentMusic = null;
           if (onload) {
@@ -402,7 +815,7 @@ function gate(args) {
           }
           Module.gateWasmInit();
           Module.gateWasmOnResize(canvas.width, canvas.height);
-          setSpriteAttribPointers();
+          setSpriteAttribPointers(Module.spriteProg);
           requestAnimationFrame(updateAndDraw);
           document.addEventListener('keydown', e => handleKeyEvent(e.key, true));
           document.addEventListener('keyup', e => handleKeyEvent(e.key, false));
@@ -413,6 +826,24 @@ function gate(args) {
           canvas.addEventListener("touchend", handleTouchEnd, false);
           canvas.addEventListener("touchcancel", handleTouchEnd, false);
           canvas.addEventListener("touchmove", handleTouchMove, false);
+          window.addEventListener('focus', () => handleFocusEvent(true));
+          window.addEventListener('blur', () => handleFocusEvent(false));
+          document.addEventListener('visibilitychange', () => handleFocusEvent(!document.hidden));
+        } catch(err) { gateFail(err); }
+        checkAssetsLoaded();
+      }
+    }
+
+    // swaps in the main sprites once they and the audio have loaded, if the app started with the loading sprites
+    function checkAssetsLoaded () {
+      if (!gateIsBroken && Module.started && !Module.assetsLoaded
+          && isLoaded(Module.mainSprites) && Module.loadingAudioCount == 0) {
+        try {
+          Module.assetsLoaded = true;
+          gl.deleteTexture(Module.spriteTex);
+          useSprites(Module.mainSprites);
+          Module.gateWasmAssetsLoaded();
+          updateLoadProgress();
         } catch(err) { gateFail(err); }
       }
     }
@@ -422,7 +853,10 @@ function gate(args) {
       try {
         if (Module.currentlyRunning) {
           resizeCanvas();
-          const continuing = Module.gateWasmUpdateAndDraw(now, cursorPos.x, cursorPos.y);
+          var continuing = checkAudioUnlocked();
+          if (continuing) {
+            continuing = Module.gateWasmUpdateAndDraw(now, cursorPos.x, cursorPos.y);
+          }
           if (!continuing) {
             quitApp();
           }
@@ -431,6 +865,23 @@ function gate(args) {
       } catch(err) { gateFail(err); }
     }
 
+    // browsers keep audio suspended until the user interacts with the page,
+    // Howler resumes its audio context on the first interaction
+    function checkAudioUnlocked() {
+      if (Module.audioUnlocked) { return true; }
+      if (Howler.usingWebAudio && (!Howler.ctx || Howler.ctx.state !== "running")) { return true; }
+      Module.audioUnlocked = true;
+      return Module.gateWasmAudioUnlocked();
+    }
+
+    function handleFocusEvent(focused) {
+      if (!gateIsBroken) {
+        try {
+          Module.gateWasmFocusEvent(focused);
+        } catch(err) { gateFail(err); }
+      }
+    }
+
     function handleKeyEvent(codeStr, down) {
       if (Module.currentlyRunning) {
         try {
@@ -538,6 +989,10 @@ function gate(args) {
       }
     }
 
+    function readStr(ptr, len) {
+      return new TextDecoder("UTF-8").decode(new Uint8Array(Module.memory.buffer, ptr, len));
+    }
+
     function readCStr(ptr) {
       const memory = new Uint8Array(Module.memory.buffer);
       var endPtr = ptr;
@@ -12,5 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub const GATE_JS: &'static str = include_str!("gate.js");
+const GATE_JS: &'static str = include_str!("gate.js");
 pub const INDEX_HTML: &'static str = include_str!("index.html");
+
+// returns the contents of "gate.js", which needs to know up front whether to fetch loading sprites
+pub fn gate_js(has_loading_sprites: bool) -> String {
+    GATE_JS.replace("/*HAS_LOADING_SPRITES*/false", &has_loading_sprites.to_string())
+}
@@ -21,7 +21,8 @@
 //! # Example build script
 //!
 //! In the below example, the user should place sprite png files in the "sprites" directory,
-//! music ogg files in the "music" directory, and sound ogg files in the "sounds" directory.
+//! music files in the "music" directory, and sound files in the "sounds" directory.
+//! Audio files may be in ogg, wav, or flac format.
 //!
 //! ```rust,no_run
 //! extern crate gate_build;
@@ -47,18 +48,32 @@ extern crate image;
 extern crate byteorder;
 extern crate regex;
 #[macro_use] extern crate lazy_static;
+extern crate roxmltree;
+extern crate serde_json;
 
 mod rect_packer;
 mod atlas;
+mod collision;
+mod sprite_sheet;
 mod asset_packer;
+mod tiled;
 mod html;
+pub mod hot_reload;
 
 pub use crate::asset_packer::AssetPacker;
+pub use crate::atlas::TextureFilter;
 
 use std::path::Path;
+use std::fs::File;
+use std::io::BufReader;
 
 fn rerun_print(check_rerun_flag: bool, path: &Path) {
     if check_rerun_flag {
         println!("cargo:rerun-if-changed={}", path.to_str().expect("path could not be converted to string"));
     }
 }
+
+fn read_json(path: &Path) -> serde_json::Value {
+    let file = File::open(path).unwrap_or_else(|e| panic!("failed to read {:?}: {}", path, e));
+    serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|e| panic!("failed to parse {:?}: {}", path, e))
+}
@@ -0,0 +1,172 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::path::Path;
+
+use image::{imageops, RgbaImage, GenericImage, GenericImageView};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::atlas::SpriteImage;
+use crate::read_json;
+
+// frame duration used when a sheet does not specify one, in seconds
+const DEFAULT_FRAME_DURATION: f64 = 0.1;
+
+/// An animation read from a sprite sheet, as a list of sprite names and frame durations in seconds.
+pub struct Animation {
+    pub name: String,
+    pub frames: Vec<(String, f64)>,
+}
+
+lazy_static! {
+    static ref WORD_SEPARATOR_REGEX: Regex = Regex::new("[^A-Za-z0-9]+").unwrap();
+}
+
+struct Frame<'a> {
+    key: &'a str,
+    value: &'a Value,
+}
+
+// reads the frames of a sprite sheet exported by Aseprite or TexturePacker in JSON hash or array format,
+// where `name` is the name of the sheet and `sheet` is its image
+pub fn read_sheet(name: &str, sheet: &RgbaImage, path: &Path,
+                  images: &mut Vec<SpriteImage>, anims: &mut Vec<Animation>) {
+    let json = read_json(path);
+    let frames: Vec<Frame> = match json["frames"] {
+        Value::Object(ref frames) => frames.iter().map(|(key, value)| Frame { key, value }).collect(),
+        Value::Array(ref frames) => frames.iter().map(|value| {
+            Frame { key: value["filename"].as_str().expect("sprite sheet frame is missing filename"), value }
+        }).collect(),
+        _ => panic!("sprite sheet {:?} is missing frames", path),
+    };
+    let is_aseprite = json["meta"]["app"].as_str().is_some_and(|app| app.contains("aseprite"));
+
+    let mut names = Vec::with_capacity(frames.len());
+    let mut durations = Vec::with_capacity(frames.len());
+    for (index, frame) in frames.iter().enumerate() {
+        // Aseprite names frames after the source file, so they are named by index instead
+        let sprite_name = if is_aseprite { format!("{}F{}", name, index) } else { frame_name(frame.key) };
+        let pivot = if is_aseprite {
+            slice_pivot(&json["meta"]["slices"], index)
+        } else {
+            texture_packer_pivot(frame.value)
+        };
+        let image = frame_image(sheet, frame.value, path);
+        images.push(SpriteImage { name: sprite_name.clone(), image, slice_border: None, pivot });
+        names.push(sprite_name);
+        durations.push(frame.value["duration"].as_f64().map_or(DEFAULT_FRAME_DURATION, |ms| 0.001 * ms));
+    }
+
+    let tags = json["meta"]["frameTags"].as_array().map(|t| t.as_slice()).unwrap_or(&[]);
+    for tag in tags {
+        let tag_name = tag["name"].as_str().expect("animation tag is missing name");
+        let (from, to) = (json_index(&tag["from"]), json_index(&tag["to"]));
+        assert!(from <= to && to < frames.len(), "animation tag {} is out of range", tag_name);
+        let frames = tag_frames(from, to, tag["direction"].as_str().unwrap_or("forward"));
+        let frames = frames.into_iter().map(|i| (names[i].clone(), durations[i])).collect();
+        anims.push(Animation { name: anim_name(name, tag_name), frames });
+    }
+    if tags.is_empty() && frames.len() > 1 && is_aseprite {
+        let frames = names.into_iter().zip(durations).collect();
+        anims.push(Animation { name: name.to_owned(), frames });
+    }
+}
+
+fn frame_name(key: &str) -> String {
+    let key = key.rsplit_once('.').filter(|(_, ext)| !ext.contains('/')).map_or(key, |(stem, _)| stem);
+    let name = camel_case(key);
+    assert!(name.starts_with(|c: char| c.is_ascii_alphabetic()), "invalid sprite name {:?}", key);
+    name
+}
+
+fn anim_name(sheet_name: &str, tag_name: &str) -> String {
+    format!("{}{}", sheet_name, camel_case(tag_name))
+}
+
+// converts a name such as "items/iron_sword" to "ItemsIronSword", for use as an enum handle
+fn camel_case(name: &str) -> String {
+    WORD_SEPARATOR_REGEX.split(name).flat_map(|word| {
+        let mut chars = word.chars();
+        chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars)
+    }).collect()
+}
+
+// returns the frame indices of an Aseprite tag, expanding reversed and ping-pong directions
+fn tag_frames(from: usize, to: usize, direction: &str) -> Vec<usize> {
+    let forward = from..=to;
+    match direction {
+        "forward" => forward.collect(),
+        "reverse" => forward.rev().collect(),
+        "pingpong" => forward.clone().chain(forward.rev().skip(1).take((to - from).saturating_sub(1))).collect(),
+        "pingpong_reverse" => forward.clone().rev().chain(forward.skip(1).take((to - from).saturating_sub(1))).collect(),
+        _ => panic!("unknown animation direction {}", direction),
+    }
+}
+
+// cuts the untrimmed image of a frame out of the sheet, undoing rotation and trimming done by the packer
+fn frame_image(sheet: &RgbaImage, frame: &Value, path: &Path) -> RgbaImage {
+    let rect = json_rect(&frame["frame"]);
+    let rotated = frame["rotated"] == true;
+    let sheet_dims = if rotated { (rect.3, rect.2) } else { (rect.2, rect.3) };
+    assert!(rect.0 + sheet_dims.0 <= sheet.width() && rect.1 + sheet_dims.1 <= sheet.height(),
+            "sprite sheet frame is outside of the image {:?}", path);
+    let sub_image = sheet.view(rect.0, rect.1, sheet_dims.0, sheet_dims.1);
+    // TexturePacker rotates frames clockwise
+    let image = if rotated { imageops::rotate270(&sub_image) } else { sub_image.to_image() };
+    if frame["trimmed"] == true {
+        let source_dims = (json_u32(&frame["sourceSize"]["w"]), json_u32(&frame["sourceSize"]["h"]));
+        let offset = json_rect(&frame["spriteSourceSize"]);
+        let mut untrimmed = RgbaImage::new(source_dims.0, source_dims.1);
+        untrimmed.copy_from(&image, offset.0, offset.1).expect("sprite sheet frame is outside of its source size");
+        untrimmed
+    } else {
+        image
+    }
+}
+
+// TexturePacker pivots are relative to the untrimmed size of the frame
+fn texture_packer_pivot(frame: &Value) -> Option<(f64, f64)> {
+    let pivot = &frame["pivot"];
+    let dims = if frame["trimmed"] == true { &frame["sourceSize"] } else { &frame["frame"] };
+    match (pivot["x"].as_f64(), pivot["y"].as_f64(), dims["w"].as_f64(), dims["h"].as_f64()) {
+        (Some(x), Some(y), Some(w), Some(h)) => Some((x * w, y * h)),
+        _ => None,
+    }
+}
+
+// Aseprite pivots are set on slices, with keys that apply from a frame index onward
+fn slice_pivot(slices: &Value, index: usize) -> Option<(f64, f64)> {
+    slices.as_array()?.iter().find_map(|slice| {
+        let key = slice["keys"].as_array()?.iter()
+                               .filter(|key| json_index(&key["frame"]) <= index)
+                               .max_by_key(|key| json_index(&key["frame"]))?;
+        let bounds = json_rect(&key["bounds"]);
+        let pivot = key.get("pivot")?;
+        Some((bounds.0 as f64 + json_u32(&pivot["x"]) as f64, bounds.1 as f64 + json_u32(&pivot["y"]) as f64))
+    })
+}
+
+fn json_rect(rect: &Value) -> (u32, u32, u32, u32) {
+    (json_u32(&rect["x"]), json_u32(&rect["y"]), json_u32(&rect["w"]), json_u32(&rect["h"]))
+}
+
+fn json_u32(value: &Value) -> u32 {
+    value.as_u64().expect("invalid sprite sheet value") as u32
+}
+
+fn json_index(value: &Value) -> usize {
+    json_u32(value) as usize
+}
@@ -0,0 +1,569 @@
+// Copyright 2017-2020 Matthew D. Michelotti
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ffi::OsStr;
+use std::collections::HashMap;
+
+use image::{self, GenericImage, GenericImageView};
+use byteorder::{BigEndian, WriteBytesExt};
+use regex::Regex;
+use serde_json::Value;
+
+use crate::atlas::SpriteImage;
+use crate::{read_json, rerun_print};
+
+// bits of a global tile ID that are used for flipping and rotating tiles
+const GID_FLAG_BITS: u32 = 0xF000_0000;
+
+/// Maps read from a directory of Tiled files, along with the tiles of their tilesets.
+#[derive(Default)]
+pub struct TiledMaps {
+    pub names: Vec<String>,
+    pub maps: Vec<Map>,
+    pub tile_images: Vec<SpriteImage>,
+    /// Tileset images and external tileset files that the maps depend on,
+    /// which may lie outside of the maps directory.
+    pub tileset_files: Vec<PathBuf>,
+}
+
+pub struct Map {
+    dims: (u32, u32),
+    tile_size: u32,
+    tile_layers: Vec<(String, Vec<Option<String>>)>, // sprite names, with row 0 at the bottom
+    object_layers: Vec<(String, Vec<MapObject>)>,
+}
+
+struct Tileset {
+    name: String,
+    image: PathBuf,
+    file: Option<PathBuf>, // set for tilesets saved in separate files
+    tile_dims: (u32, u32),
+    spacing: u32,
+    margin: u32,
+    tile_count: u32,
+    columns: u32,
+}
+
+impl Tileset {
+    fn sprite_name(&self, local_id: u32) -> String {
+        format!("{}R{}C{}", self.name, local_id / self.columns, local_id % self.columns)
+    }
+}
+
+// a map as read from a Tiled file, before converting global tile IDs and object coordinates
+struct RawMap {
+    dims: (u32, u32),
+    tile_size: u32,
+    tilesets: Vec<(u32, Tileset)>, // first global tile ID of each tileset
+    tile_layers: Vec<(String, Vec<u32>)>, // global tile IDs, with row 0 at the top
+    object_layers: Vec<(String, Vec<RawObject>)>,
+}
+
+// an object in Tiled coordinates, where Y points down
+struct RawObject {
+    id: u32,
+    name: String,
+    class: String,
+    pos: (f64, f64),
+    dims: (f64, f64),
+    is_tile: bool, // tile objects are positioned by their bottom-left corner instead of top-left
+    shape: Shape,
+    properties: Vec<(String, Property)>,
+}
+
+// an object in map coordinates, where Y points up and the origin is the bottom-left of the map
+struct MapObject {
+    id: u32,
+    name: String,
+    class: String,
+    pos: (f64, f64), // bottom-left corner of the bounds
+    dims: (f64, f64),
+    shape: Shape, // polygon and polyline points are absolute
+    properties: Vec<(String, Property)>,
+}
+
+enum Shape { Point, Rect, Ellipse, Polygon(Vec<(f64, f64)>), Polyline(Vec<(f64, f64)>) }
+
+#[derive(Clone)]
+enum Property { Bool(bool), Int(i64), Float(f64), String(String) }
+
+lazy_static! {
+    static ref NAME_REGEX: Regex = Regex::new("^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+}
+
+pub fn read_maps(in_dir: &Path, check_rerun: bool) -> TiledMaps {
+    rerun_print(check_rerun, in_dir);
+    let mut paths: Vec<_> = in_dir.read_dir().expect("failed to read Tiled maps")
+        .map(|p| p.expect("failed to read Tiled maps").path())
+        .filter(|p| p.is_file() && is_map_file(p))
+        .collect();
+    paths.sort_unstable();
+
+    let mut names = Vec::new();
+    let mut maps = Vec::new();
+    let mut tilesets: HashMap<String, Tileset> = HashMap::new();
+    let mut tileset_files = Vec::new();
+    for path in paths {
+        rerun_print(check_rerun, &path);
+        let name = path.file_stem().unwrap().to_str().expect("invalid map name").to_owned();
+        assert!(NAME_REGEX.is_match(&name), "invalid map name {:?}", name);
+        assert!(names.iter().all(|n| *n != name), "should have no duplicate map names");
+        let raw = if path.extension() == Some(OsStr::new("tmx")) {
+            read_tmx(&path, check_rerun)
+        } else {
+            read_tmj(&path, check_rerun)
+        };
+        maps.push(convert_map(&raw));
+        names.push(name);
+        for (_, tileset) in raw.tilesets {
+            for file in tileset.file.iter().chain(Some(&tileset.image)) {
+                if !tileset_files.contains(file) {
+                    tileset_files.push(file.clone());
+                }
+            }
+            if let Some(existing) = tilesets.get(&tileset.name) {
+                assert!(existing.image == tileset.image, "tilesets named {} use different images", tileset.name);
+            } else {
+                tilesets.insert(tileset.name.clone(), tileset);
+            }
+        }
+    }
+
+    let mut tile_images = Vec::new();
+    for tileset in tilesets.values() {
+        rerun_print(check_rerun, &tileset.image);
+        split_tileset(tileset, &mut tile_images);
+    }
+    TiledMaps { names, maps, tile_images, tileset_files }
+}
+
+fn is_map_file(path: &Path) -> bool {
+    ["tmx", "tmj"].iter().any(|&ext| path.extension() == Some(OsStr::new(ext)))
+}
+
+fn split_tileset(tileset: &Tileset, out: &mut Vec<SpriteImage>) {
+    let mut image = image::open(&tileset.image).expect("failed to read tileset image").to_rgba8();
+    let (tile_w, tile_h) = tileset.tile_dims;
+    for local_id in 0..tileset.tile_count {
+        let x = tileset.margin + (local_id % tileset.columns) * (tile_w + tileset.spacing);
+        let y = tileset.margin + (local_id / tileset.columns) * (tile_h + tileset.spacing);
+        assert!(x + tile_w <= image.width() && y + tile_h <= image.height(),
+                "tileset {} does not fit in its image", tileset.name);
+        let sub_image = image.sub_image(x, y, tile_w, tile_h);
+        if sub_image.pixels().any(|(_, _, p)| p[3] != 0) {
+            let name = tileset.sprite_name(local_id);
+            out.push(SpriteImage { name, image: sub_image.to_image(), slice_border: None, pivot: None });
+        }
+    }
+}
+
+fn convert_map(raw: &RawMap) -> Map {
+    let (cols, rows) = raw.dims;
+    let tile_layers = raw.tile_layers.iter().map(|(name, gids)| {
+        assert!(gids.len() == (cols * rows) as usize, "tile layer {} has the wrong number of tiles", name);
+        let mut tiles = Vec::with_capacity(gids.len());
+        for row in (0..rows).rev() {
+            for col in 0..cols {
+                tiles.push(tile_sprite_name(&raw.tilesets, name, gids[(row * cols + col) as usize]));
+            }
+        }
+        (name.clone(), tiles)
+    }).collect();
+    let height = (rows * raw.tile_size) as f64;
+    let object_layers = raw.object_layers.iter().map(|(name, objects)| {
+        (name.clone(), objects.iter().map(|o| convert_object(o, height)).collect())
+    }).collect();
+    Map { dims: raw.dims, tile_size: raw.tile_size, tile_layers, object_layers }
+}
+
+fn tile_sprite_name(tilesets: &[(u32, Tileset)], layer: &str, gid: u32) -> Option<String> {
+    assert!(gid & GID_FLAG_BITS == 0, "flipped and rotated tiles are not supported, in tile layer {}", layer);
+    if gid == 0 { return None; }
+    let (first_gid, tileset) = tilesets.iter().filter(|t| t.0 <= gid).max_by_key(|t| t.0)
+                                       .expect("tile does not belong to any tileset");
+    let local_id = gid - first_gid;
+    assert!(local_id < tileset.tile_count, "tile {} is outside of tileset {}", gid, tileset.name);
+    Some(tileset.sprite_name(local_id))
+}
+
+fn convert_object(raw: &RawObject, height: f64) -> MapObject {
+    let flip = |(x, y): (f64, f64)| (raw.pos.0 + x, height - raw.pos.1 - y);
+    let (shape, pos, dims) = match raw.shape {
+        Shape::Polygon(ref points) | Shape::Polyline(ref points) => {
+            let points: Vec<_> = points.iter().cloned().map(flip).collect();
+            let min = points.iter().fold((f64::INFINITY, f64::INFINITY), |m, p| (m.0.min(p.0), m.1.min(p.1)));
+            let max = points.iter().fold((f64::NEG_INFINITY, f64::NEG_INFINITY), |m, p| (m.0.max(p.0), m.1.max(p.1)));
+            let shape = match raw.shape {
+                Shape::Polygon(_) => Shape::Polygon(points),
+                _ => Shape::Polyline(points),
+            };
+            (shape, min, (max.0 - min.0, max.1 - min.1))
+        },
+        Shape::Point => (Shape::Point, flip((0., 0.)), (0., 0.)),
+        Shape::Rect | Shape::Ellipse => {
+            let bottom = if raw.is_tile { 0. } else { raw.dims.1 };
+            let shape = if let Shape::Ellipse = raw.shape { Shape::Ellipse } else { Shape::Rect };
+            (shape, flip((0., bottom)), raw.dims)
+        },
+    };
+    let properties = raw.properties.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+    MapObject { id: raw.id, name: raw.name.clone(), class: raw.class.clone(), pos, dims, shape, properties }
+}
+
+fn check_map(path: &Path, orientation: Option<&str>, infinite: bool, tile_dims: (u32, u32)) {
+    assert!(orientation == Some("orthogonal"), "map {:?} must have orthogonal orientation", path);
+    assert!(!infinite, "map {:?} must not be infinite", path);
+    assert!(tile_dims.0 == tile_dims.1 && tile_dims.0 > 0, "map {:?} must have square tiles", path);
+}
+
+fn check_object(path: &Path, rotation: f64) {
+    assert!(rotation == 0., "rotated objects are not supported, in map {:?}", path);
+}
+
+fn check_tileset_name(name: &str) {
+    assert!(NAME_REGEX.is_match(name), "invalid tileset name {:?}", name);
+}
+
+fn parse_property(name: &str, kind: &str, value: &str) -> Option<Property> {
+    let invalid = || -> ! { panic!("invalid value for property {}", name) };
+    Some(match kind {
+        "bool" => Property::Bool(value.parse().unwrap_or_else(|_| invalid())),
+        "int" | "object" => Property::Int(value.parse().unwrap_or_else(|_| invalid())),
+        "float" => Property::Float(value.parse().unwrap_or_else(|_| invalid())),
+        "string" | "color" | "file" => Property::String(value.to_owned()),
+        _ => return None, // class properties are not supported
+    })
+}
+
+fn parse_csv(path: &Path, data: &str) -> Vec<u32> {
+    data.split(',').map(|gid| gid.trim().parse().unwrap_or_else(|_| panic!("invalid tile data in map {:?}", path)))
+        .collect()
+}
+
+fn parse_points(path: &Path, points: &str) -> Vec<(f64, f64)> {
+    let invalid = || -> ! { panic!("invalid polygon points in map {:?}", path) };
+    points.split_whitespace().map(|point| {
+        let mut coords = point.split(',').map(|c| c.parse().unwrap_or_else(|_| invalid()));
+        match (coords.next(), coords.next(), coords.next()) {
+            (Some(x), Some(y), None) => (x, y),
+            _ => invalid(),
+        }
+    }).collect()
+}
+
+// TMX (XML) format
+
+fn read_tmx(path: &Path, check_rerun: bool) -> RawMap {
+    let text = fs::read_to_string(path).expect("failed to read Tiled map");
+    let doc = roxmltree::Document::parse(&text).unwrap_or_else(|e| panic!("failed to parse map {:?}: {}", path, e));
+    let map = doc.root_element();
+    let tile_dims = (xml_attr(path, map, "tilewidth"), xml_attr(path, map, "tileheight"));
+    check_map(path, map.attribute("orientation"), map.attribute("infinite") == Some("1"), tile_dims);
+
+    let tilesets = xml_children(map, "tileset").map(|node| {
+        let first_gid = xml_attr(path, node, "firstgid");
+        let tileset = match node.attribute("source") {
+            Some(source) => read_external_tileset(&path.parent().unwrap().join(source), check_rerun),
+            None => xml_tileset(path, node),
+        };
+        (first_gid, tileset)
+    }).collect();
+    let mut raw = RawMap {
+        dims: (xml_attr(path, map, "width"), xml_attr(path, map, "height")),
+        tile_size: tile_dims.0,
+        tilesets,
+        tile_layers: Vec::new(),
+        object_layers: Vec::new(),
+    };
+    xml_layers(path, map, &mut raw);
+    raw
+}
+
+fn xml_layers(path: &Path, parent: roxmltree::Node, raw: &mut RawMap) {
+    for node in parent.children().filter(|n| n.is_element()) {
+        let name = node.attribute("name").unwrap_or("").to_owned();
+        match node.tag_name().name() {
+            "layer" => {
+                let data = xml_children(node, "data").next().expect("tile layer is missing data");
+                assert!(data.attribute("encoding") == Some("csv") && data.attribute("compression").is_none(),
+                        "tile layer data in map {:?} must use CSV format", path);
+                raw.tile_layers.push((name, parse_csv(path, data.text().unwrap_or(""))));
+            },
+            "objectgroup" => {
+                let objects = xml_children(node, "object").map(|o| xml_object(path, o)).collect();
+                raw.object_layers.push((name, objects));
+            },
+            "group" => xml_layers(path, node, raw),
+            _ => {},
+        }
+    }
+}
+
+fn xml_object(path: &Path, node: roxmltree::Node) -> RawObject {
+    check_object(path, xml_opt_attr(path, node, "rotation").unwrap_or(0.));
+    let shape_node = node.children().find(|n| n.is_element() && n.tag_name().name() != "properties");
+    let shape = match shape_node.map(|n| (n.tag_name().name(), n)) {
+        Some(("point", _)) => Shape::Point,
+        Some(("ellipse", _)) => Shape::Ellipse,
+        Some(("polygon", n)) => Shape::Polygon(parse_points(path, n.attribute("points").unwrap_or(""))),
+        Some(("polyline", n)) => Shape::Polyline(parse_points(path, n.attribute("points").unwrap_or(""))),
+        _ => Shape::Rect,
+    };
+    let properties = xml_children(node, "properties").flat_map(|n| xml_children(n, "property")).filter_map(|p| {
+        let name = p.attribute("name").unwrap_or("");
+        let value = p.attribute("value").or_else(|| p.text()).unwrap_or("");
+        parse_property(name, p.attribute("type").unwrap_or("string"), value).map(|v| (name.to_owned(), v))
+    }).collect();
+    RawObject {
+        id: xml_opt_attr(path, node, "id").unwrap_or(0),
+        name: node.attribute("name").unwrap_or("").to_owned(),
+        class: node.attribute("class").or_else(|| node.attribute("type")).unwrap_or("").to_owned(),
+        pos: (xml_attr(path, node, "x"), xml_attr(path, node, "y")),
+        dims: (xml_opt_attr(path, node, "width").unwrap_or(0.), xml_opt_attr(path, node, "height").unwrap_or(0.)),
+        is_tile: node.attribute("gid").is_some(),
+        shape,
+        properties,
+    }
+}
+
+fn xml_tileset(path: &Path, node: roxmltree::Node) -> Tileset {
+    let name = node.attribute("name").unwrap_or("").to_owned();
+    check_tileset_name(&name);
+    let image = xml_children(node, "image").next().expect("only tilesets based on a single image are supported");
+    Tileset {
+        name,
+        image: path.parent().unwrap().join(image.attribute("source").expect("tileset image is missing source")),
+        file: None,
+        tile_dims: (xml_attr(path, node, "tilewidth"), xml_attr(path, node, "tileheight")),
+        spacing: xml_opt_attr(path, node, "spacing").unwrap_or(0),
+        margin: xml_opt_attr(path, node, "margin").unwrap_or(0),
+        tile_count: xml_attr(path, node, "tilecount"),
+        columns: xml_attr(path, node, "columns"),
+    }
+}
+
+fn xml_children<'a, 'input: 'a>(node: roxmltree::Node<'a, 'input>, tag: &'a str)
+                                -> impl Iterator<Item = roxmltree::Node<'a, 'input>> {
+    node.children().filter(move |n| n.has_tag_name(tag))
+}
+
+fn xml_opt_attr<T: std::str::FromStr>(path: &Path, node: roxmltree::Node, name: &str) -> Option<T> {
+    node.attribute(name).map(|value| {
+        value.parse().unwrap_or_else(|_| panic!("invalid {} attribute in {:?}", name, path))
+    })
+}
+
+fn xml_attr<T: std::str::FromStr>(path: &Path, node: roxmltree::Node, name: &str) -> T {
+    xml_opt_attr(path, node, name).unwrap_or_else(|| panic!("missing {} attribute in {:?}", name, path))
+}
+
+// TMJ (JSON) format
+
+fn read_tmj(path: &Path, check_rerun: bool) -> RawMap {
+    let map = read_json(path);
+    let tile_dims = (json_u32(path, &map, "tilewidth"), json_u32(path, &map, "tileheight"));
+    check_map(path, map["orientation"].as_str(), map["infinite"].as_bool().unwrap_or(false), tile_dims);
+
+    let tilesets = json_array(&map, "tilesets").iter().map(|node| {
+        let first_gid = json_u32(path, node, "firstgid");
+        let tileset = match node["source"].as_str() {
+            Some(source) => read_external_tileset(&path.parent().unwrap().join(source), check_rerun),
+            None => json_tileset(path, node),
+        };
+        (first_gid, tileset)
+    }).collect();
+    let mut raw = RawMap {
+        dims: (json_u32(path, &map, "width"), json_u32(path, &map, "height")),
+        tile_size: tile_dims.0,
+        tilesets,
+        tile_layers: Vec::new(),
+        object_layers: Vec::new(),
+    };
+    json_layers(path, &map, &mut raw);
+    raw
+}
+
+fn json_layers(path: &Path, parent: &Value, raw: &mut RawMap) {
+    for node in json_array(parent, "layers") {
+        let name = node["name"].as_str().unwrap_or("").to_owned();
+        match node["type"].as_str() {
+            Some("tilelayer") => {
+                assert!(node.get("encoding").is_none() || node["encoding"] == "csv",
+                        "tile layer data in map {:?} must use CSV format", path);
+                let gids = json_array(node, "data").iter()
+                    .map(|gid| gid.as_u64().unwrap_or_else(|| panic!("invalid tile data in map {:?}", path)) as u32)
+                    .collect();
+                raw.tile_layers.push((name, gids));
+            },
+            Some("objectgroup") => {
+                let objects = json_array(node, "objects").iter().map(|o| json_object(path, o)).collect();
+                raw.object_layers.push((name, objects));
+            },
+            Some("group") => json_layers(path, node, raw),
+            _ => {},
+        }
+    }
+}
+
+fn json_object(path: &Path, node: &Value) -> RawObject {
+    check_object(path, node["rotation"].as_f64().unwrap_or(0.));
+    let points = |key| json_array(node, key).iter().map(|p| (json_f64(path, p, "x"), json_f64(path, p, "y"))).collect();
+    let shape = if node["point"] == true {
+        Shape::Point
+    } else if node["ellipse"] == true {
+        Shape::Ellipse
+    } else if node.get("polygon").is_some() {
+        Shape::Polygon(points("polygon"))
+    } else if node.get("polyline").is_some() {
+        Shape::Polyline(points("polyline"))
+    } else {
+        Shape::Rect
+    };
+    let properties = json_array(node, "properties").iter().filter_map(|p| {
+        let name = p["name"].as_str().unwrap_or("");
+        let value = match p["value"] {
+            Value::String(ref value) => value.clone(),
+            ref value => value.to_string(),
+        };
+        parse_property(name, p["type"].as_str().unwrap_or("string"), &value).map(|v| (name.to_owned(), v))
+    }).collect();
+    RawObject {
+        id: node["id"].as_u64().unwrap_or(0) as u32,
+        name: node["name"].as_str().unwrap_or("").to_owned(),
+        class: node["class"].as_str().or_else(|| node["type"].as_str()).unwrap_or("").to_owned(),
+        pos: (json_f64(path, node, "x"), json_f64(path, node, "y")),
+        dims: (node["width"].as_f64().unwrap_or(0.), node["height"].as_f64().unwrap_or(0.)),
+        is_tile: node.get("gid").is_some(),
+        shape,
+        properties,
+    }
+}
+
+fn json_tileset(path: &Path, node: &Value) -> Tileset {
+    let name = node["name"].as_str().unwrap_or("").to_owned();
+    check_tileset_name(&name);
+    let image = node["image"].as_str().expect("only tilesets based on a single image are supported");
+    Tileset {
+        name,
+        image: path.parent().unwrap().join(image),
+        file: None,
+        tile_dims: (json_u32(path, node, "tilewidth"), json_u32(path, node, "tileheight")),
+        spacing: node["spacing"].as_u64().unwrap_or(0) as u32,
+        margin: node["margin"].as_u64().unwrap_or(0) as u32,
+        tile_count: json_u32(path, node, "tilecount"),
+        columns: json_u32(path, node, "columns"),
+    }
+}
+
+fn json_array<'a>(node: &'a Value, key: &str) -> &'a [Value] {
+    node[key].as_array().map(|a| a.as_slice()).unwrap_or(&[])
+}
+
+fn json_u32(path: &Path, node: &Value, key: &str) -> u32 {
+    node[key].as_u64().unwrap_or_else(|| panic!("missing {} value in {:?}", key, path)) as u32
+}
+
+fn json_f64(path: &Path, node: &Value, key: &str) -> f64 {
+    node[key].as_f64().unwrap_or_else(|| panic!("missing {} value in {:?}", key, path))
+}
+
+// tilesets saved in separate files, in either TSX (XML) or TSJ (JSON) format
+
+fn read_external_tileset(path: &Path, check_rerun: bool) -> Tileset {
+    rerun_print(check_rerun, path);
+    let mut tileset = if path.extension() == Some(OsStr::new("tsx")) {
+        let text = fs::read_to_string(path).expect("failed to read tileset");
+        let doc = roxmltree::Document::parse(&text).unwrap_or_else(|e| panic!("failed to parse {:?}: {}", path, e));
+        xml_tileset(path, doc.root_element())
+    } else {
+        json_tileset(path, &read_json(path))
+    };
+    tileset.file = Some(path.to_owned());
+    tileset
+}
+
+// binary output, read by `gate::tiled::TiledMap`
+
+impl Map {
+    /// Writes the map in binary form, where `sprites` are the sorted names of all packed sprites.
+    pub fn write_bin_to_file(&self, sprites: &[String], path: &Path) -> io::Result<()> {
+        let mut out = Vec::new();
+        out.write_u32::<BigEndian>(self.dims.0)?;
+        out.write_u32::<BigEndian>(self.dims.1)?;
+        out.write_u32::<BigEndian>(self.tile_size)?;
+        out.write_u16::<BigEndian>(self.tile_layers.len() as u16)?;
+        for (name, tiles) in &self.tile_layers {
+            write_str(&mut out, name)?;
+            for tile in tiles {
+                // tiles missing from the atlas were fully transparent, so are left empty
+                let id = tile.as_ref().and_then(|t| sprites.binary_search(t).ok());
+                out.write_u16::<BigEndian>(id.map_or(0, |id| id as u16 + 1))?;
+            }
+        }
+        out.write_u16::<BigEndian>(self.object_layers.len() as u16)?;
+        for (name, objects) in &self.object_layers {
+            write_str(&mut out, name)?;
+            out.write_u32::<BigEndian>(objects.len() as u32)?;
+            for object in objects {
+                object.write_bin(&mut out)?;
+            }
+        }
+        File::create(path)?.write_all(&out)
+    }
+}
+
+impl MapObject {
+    fn write_bin(&self, out: &mut Vec<u8>) -> io::Result<()> {
+        out.write_u32::<BigEndian>(self.id)?;
+        write_str(out, &self.name)?;
+        write_str(out, &self.class)?;
+        out.write_u8(match self.shape {
+            Shape::Point => 0,
+            Shape::Rect => 1,
+            Shape::Ellipse => 2,
+            Shape::Polygon(_) => 3,
+            Shape::Polyline(_) => 4,
+        })?;
+        for &value in &[self.pos.0, self.pos.1, self.dims.0, self.dims.1] {
+            out.write_f64::<BigEndian>(value)?;
+        }
+        if let Shape::Polygon(ref points) | Shape::Polyline(ref points) = self.shape {
+            out.write_u32::<BigEndian>(points.len() as u32)?;
+            for &(x, y) in points {
+                out.write_f64::<BigEndian>(x)?;
+                out.write_f64::<BigEndian>(y)?;
+            }
+        }
+        out.write_u16::<BigEndian>(self.properties.len() as u16)?;
+        for (name, value) in &self.properties {
+            write_str(out, name)?;
+            match *value {
+                Property::Bool(value) => { out.write_u8(0)?; out.write_u8(value as u8)?; },
+                Property::Int(value) => { out.write_u8(1)?; out.write_i64::<BigEndian>(value)?; },
+                Property::Float(value) => { out.write_u8(2)?; out.write_f64::<BigEndian>(value)?; },
+                Property::String(ref value) => { out.write_u8(3)?; write_str(out, value)?; },
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, value: &str) -> io::Result<()> {
+    out.write_u16::<BigEndian>(value.len() as u16)?;
+    out.write_all(value.as_bytes())
+}